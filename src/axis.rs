@@ -0,0 +1,446 @@
+use std::io;
+
+use crate::{curve::ResponseCurve, G29State};
+
+///
+/// Axis
+///
+/// The analog axes of the G29 that can be read as normalized values
+///
+#[derive(Debug, PartialEq, Copy, Clone, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Axis {
+    /// -1.0 (fully left) to 1.0 (fully right)
+    Steering,
+    /// 0.0 (released) to 1.0 (fully pressed)
+    Throttle,
+    /// 0.0 (released) to 1.0 (fully pressed)
+    Brake,
+    /// 0.0 (released) to 1.0 (fully pressed)
+    Clutch,
+    /// -1.0 to 1.0
+    ShifterX,
+    /// -1.0 to 1.0
+    ShifterY,
+    /// Throttle minus brake, -1.0 (full brake) to 1.0 (full throttle)
+    CombinedPedals,
+}
+
+impl Axis {
+    /// Every axis, in declaration order.
+    pub const ALL: [Axis; 7] = [
+        Axis::Steering,
+        Axis::Throttle,
+        Axis::Brake,
+        Axis::Clutch,
+        Axis::ShifterX,
+        Axis::ShifterY,
+        Axis::CombinedPedals,
+    ];
+
+    /// Returns `true` for the throttle, brake and clutch pedals.
+    pub fn is_pedal(&self) -> bool {
+        matches!(self, Axis::Throttle | Axis::Brake | Axis::Clutch)
+    }
+
+    /// Get the raw value of an axis in a state.
+    /// Steering is 16-bit, every other axis is 8-bit. `CombinedPedals` has no raw value.
+    pub fn raw(&self, state: &G29State) -> Option<u16> {
+        match self {
+            Axis::Steering => Some(state.steering_raw()),
+            Axis::Throttle => Some(state.throttle as u16),
+            Axis::Brake => Some(state.brake as u16),
+            Axis::Clutch => Some(state.clutch as u16),
+            Axis::ShifterX => Some(state.shifter_x as u16),
+            Axis::ShifterY => Some(state.shifter_y as u16),
+            Axis::CombinedPedals => None,
+        }
+    }
+
+    /// The name of the axis, as used in profiles.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Axis::Steering => "steering",
+            Axis::Throttle => "throttle",
+            Axis::Brake => "brake",
+            Axis::Clutch => "clutch",
+            Axis::ShifterX => "shifter_x",
+            Axis::ShifterY => "shifter_y",
+            Axis::CombinedPedals => "combined_pedals",
+        }
+    }
+
+    /// Find an axis by its name.
+    pub fn from_name(name: &str) -> Option<Axis> {
+        Axis::ALL.into_iter().find(|axis| axis.name() == name)
+    }
+
+    fn index(&self) -> usize {
+        *self as usize
+    }
+}
+
+///
+/// AxisCalibration
+///
+/// The raw values an axis actually reaches
+/// - min: `u16` - The lowest raw value seen
+/// - max: `u16` - The highest raw value seen
+/// - rest: `u16` - The raw value when the control is left alone (released pedal, centered stick)
+///
+/// Pedals are normalized from `rest` (0.0) to whichever of `min` or `max` is furthest away (1.0).
+/// Steering only uses `rest` as its center, it always turns 65535 counts over the wheel range.
+/// Every other axis is normalized from `min` (-1.0) through `rest` (0.0) to `max` (1.0).
+///
+#[derive(Debug, PartialEq, Copy, Clone, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AxisCalibration {
+    pub min: u16,
+    pub max: u16,
+    pub rest: u16,
+}
+
+impl AxisCalibration {
+    /// Start a calibration where the axis has only been seen at rest.
+    pub fn at_rest(raw: u16) -> AxisCalibration {
+        AxisCalibration {
+            min: raw,
+            max: raw,
+            rest: raw,
+        }
+    }
+
+    /// Extend the calibrated range to include a raw value.
+    pub fn observe(&mut self, raw: u16) {
+        self.min = self.min.min(raw);
+        self.max = self.max.max(raw);
+    }
+
+    /// Normalize a raw value with this calibration.
+    pub fn normalize(&self, raw: u16, pedal: bool) -> f32 {
+        let offset = raw as f32 - self.rest as f32;
+
+        if pedal {
+            let pressed = if self.rest.abs_diff(self.min) > self.rest.abs_diff(self.max) {
+                self.min
+            } else {
+                self.max
+            };
+
+            return ratio(offset, pressed as f32 - self.rest as f32).clamp(0.0, 1.0);
+        }
+
+        let span = if offset >= 0.0 {
+            self.max as f32 - self.rest as f32
+        } else {
+            self.rest as f32 - self.min as f32
+        };
+
+        ratio(offset, span).clamp(-1.0, 1.0)
+    }
+}
+
+///
+/// AxisSettings
+///
+/// How a single axis is normalized
+/// - inverted: `bool` - Flip the direction of the axis (default: `false`)
+/// - calibration: `Option<AxisCalibration>` - The calibrated range of the axis, the full raw range if `None` (default: `None`)
+/// - curve: `ResponseCurve` - The response curve applied after calibration and inversion (default: `ResponseCurve::Linear`)
+///
+#[derive(Debug, PartialEq, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AxisSettings {
+    pub inverted: bool,
+    pub calibration: Option<AxisCalibration>,
+    pub curve: ResponseCurve,
+}
+
+///
+/// AxisConfig
+///
+/// The settings for every axis, used to turn raw values into normalized ones.
+///
+/// # Example
+///
+/// ```rust
+/// use lib_g29::axis::{Axis, AxisConfig};
+///
+/// let mut config = AxisConfig::default();
+/// config.set_inverted(Axis::ShifterY, true);
+///
+/// let state = g29.snapshot();
+/// println!("Throttle: {:.2}", config.value(Axis::Throttle, &state));
+/// ```
+///
+#[derive(Debug, PartialEq, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AxisConfig {
+    settings: [AxisSettings; 7],
+}
+
+impl AxisConfig {
+    /// Get the settings of an axis.
+    pub fn settings(&self, axis: Axis) -> &AxisSettings {
+        &self.settings[axis.index()]
+    }
+
+    /// Get the settings of an axis for modification.
+    pub fn settings_mut(&mut self, axis: Axis) -> &mut AxisSettings {
+        &mut self.settings[axis.index()]
+    }
+
+    /// Flip the direction of an axis.
+    pub fn set_inverted(&mut self, axis: Axis, inverted: bool) {
+        self.settings_mut(axis).inverted = inverted;
+    }
+
+    /// Set the response curve of an axis.
+    /// Returns an `InvalidInput` error, keeping the current curve, if the curve does not `validate`.
+    pub fn set_curve(&mut self, axis: Axis, curve: ResponseCurve) -> io::Result<()> {
+        curve.validate()?;
+        self.settings_mut(axis).curve = curve;
+
+        Ok(())
+    }
+
+    /// Get the normalized value of an axis in a state.
+    /// Pedals are 0.0 (released) to 1.0 (fully pressed), every other axis is -1.0 to 1.0.
+    pub fn value(&self, axis: Axis, state: &G29State) -> f32 {
+        let settings = self.settings(axis);
+
+        let value = match axis {
+            // the combined axis is built from the other pedals
+            Axis::CombinedPedals => {
+                self.value(Axis::Throttle, state) - self.value(Axis::Brake, state)
+            }
+            _ => self.calibrated(axis, state),
+        };
+
+        let value = if !settings.inverted {
+            value
+        } else if axis.is_pedal() {
+            1.0 - value
+        } else {
+            -value
+        };
+
+        if axis.is_pedal() {
+            settings.curve.apply(value)
+        } else {
+            settings.curve.apply_centered(value)
+        }
+    }
+
+    /// Get the value of an axis in a state with only its calibration applied, not its inversion
+    /// or curve. `CombinedPedals` has no calibration of its own and is the throttle minus the brake.
+    pub fn calibrated(&self, axis: Axis, state: &G29State) -> f32 {
+        let Some(raw) = axis.raw(state) else {
+            return self.calibrated(Axis::Throttle, state) - self.calibrated(Axis::Brake, state);
+        };
+
+        match self.settings(axis).calibration {
+            // the scale of the steering is fixed by the range, only its center moves
+            Some(calibration) if axis == Axis::Steering => {
+                ((raw as f32 - calibration.rest as f32) / 32767.5).clamp(-1.0, 1.0)
+            }
+            Some(calibration) => calibration.normalize(raw, axis.is_pedal()),
+            None if axis == Axis::Steering => state.steering_normalized(),
+            None if axis.is_pedal() => pedal(raw as u8),
+            None => centered(raw as u8),
+        }
+    }
+
+    /// Extend the calibration of every axis but the steering to include the values in a state.
+    /// Axes without a calibration start one, treating the current value as the rest position.
+    /// The steering is left out so pressing the pedals cannot change its scale, set its `rest`
+    /// directly instead.
+    pub fn observe(&mut self, state: &G29State) {
+        for axis in Axis::ALL {
+            if axis == Axis::Steering {
+                continue;
+            }
+
+            if let Some(raw) = axis.raw(state) {
+                self.settings_mut(axis)
+                    .calibration
+                    .get_or_insert_with(|| AxisCalibration::at_rest(raw))
+                    .observe(raw);
+            }
+        }
+    }
+
+    /// Remove the calibration of every axis.
+    pub fn clear_calibration(&mut self) {
+        for settings in self.settings.iter_mut() {
+            settings.calibration = None;
+        }
+    }
+}
+
+fn ratio(value: f32, span: f32) -> f32 {
+    if span == 0.0 {
+        0.0
+    } else {
+        value / span
+    }
+}
+
+// pedals report 255 when released and 0 when fully pressed
+fn pedal(raw: u8) -> f32 {
+    (255 - raw) as f32 / 255.0
+}
+
+fn centered(raw: u8) -> f32 {
+    (raw as f32 - 127.5) / 127.5
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::axis::{Axis, AxisCalibration, AxisConfig};
+    use crate::curve::ResponseCurve;
+
+    fn get_test_state() -> crate::G29State {
+        let mut frame = [0u8; 12];
+        frame[5] = 0x80;
+        frame[6] = 255;
+        frame[7] = 255;
+        frame[8] = 255;
+
+        crate::G29State::from_frame(&frame, 0, std::time::Duration::ZERO)
+    }
+
+    #[test]
+    fn test_pedals_released() {
+        let config = AxisConfig::default();
+        let state = get_test_state();
+
+        assert_eq!(config.value(Axis::Throttle, &state), 0.0);
+        assert_eq!(config.value(Axis::Brake, &state), 0.0);
+        assert_eq!(config.value(Axis::Clutch, &state), 0.0);
+        assert_eq!(config.value(Axis::CombinedPedals, &state), 0.0);
+    }
+
+    #[test]
+    fn test_pedals_pressed() {
+        let config = AxisConfig::default();
+        let mut state = get_test_state();
+        state.throttle = 0;
+        state.brake = 51;
+
+        assert_eq!(config.value(Axis::Throttle, &state), 1.0);
+        assert_eq!(config.value(Axis::Brake, &state), 0.8);
+        assert!((config.value(Axis::CombinedPedals, &state) - 0.2).abs() < 0.0001);
+    }
+
+    #[test]
+    fn test_calibrated_pedal() {
+        let mut config = AxisConfig::default();
+        config.settings_mut(Axis::Throttle).calibration = Some(AxisCalibration {
+            min: 10,
+            max: 250,
+            rest: 250,
+        });
+
+        let mut state = get_test_state();
+        assert_eq!(config.value(Axis::Throttle, &state), 0.0);
+
+        state.throttle = 10;
+        assert_eq!(config.value(Axis::Throttle, &state), 1.0);
+
+        state.throttle = 130;
+        assert_eq!(config.value(Axis::Throttle, &state), 0.5);
+    }
+
+    #[test]
+    fn test_calibrated_centered_axis() {
+        let calibration = AxisCalibration {
+            min: 20,
+            max: 220,
+            rest: 120,
+        };
+
+        assert_eq!(calibration.normalize(120, false), 0.0);
+        assert_eq!(calibration.normalize(20, false), -1.0);
+        assert_eq!(calibration.normalize(170, false), 0.5);
+        assert_eq!(calibration.normalize(255, false), 1.0);
+    }
+
+    #[test]
+    fn test_calibrated_steering() {
+        let mut config = AxisConfig::default();
+        config.settings_mut(Axis::Steering).calibration = Some(AxisCalibration {
+            min: 30000,
+            max: 35000,
+            rest: 39321,
+        });
+
+        // min and max do not scale the steering
+        let mut state = get_test_state();
+        state.steering = 0x80;
+        state.steering_fine = 0x00;
+        assert!((config.calibrated(Axis::Steering, &state) + 0.2).abs() < 0.001);
+
+        state.steering = 0x00;
+        assert_eq!(config.calibrated(Axis::Steering, &state), -1.0);
+    }
+
+    #[test]
+    fn test_observe() {
+        let mut config = AxisConfig::default();
+        let mut state = get_test_state();
+        state.brake = 240;
+        config.observe(&state);
+
+        state.brake = 5;
+        config.observe(&state);
+
+        assert_eq!(
+            config.settings(Axis::Brake).calibration,
+            Some(AxisCalibration {
+                min: 5,
+                max: 240,
+                rest: 240
+            })
+        );
+        assert_eq!(config.value(Axis::Brake, &state), 1.0);
+        assert_eq!(config.settings(Axis::CombinedPedals).calibration, None);
+        assert_eq!(config.settings(Axis::Steering).calibration, None);
+    }
+
+    #[test]
+    fn test_curve() {
+        let mut config = AxisConfig::default();
+        config
+            .set_curve(Axis::Brake, ResponseCurve::Gamma(2.0))
+            .unwrap();
+        config
+            .set_curve(Axis::Steering, ResponseCurve::Gamma(2.0))
+            .unwrap();
+        assert!(config
+            .set_curve(Axis::Clutch, ResponseCurve::Gamma(-1.0))
+            .is_err());
+
+        let mut state = get_test_state();
+        state.brake = 255 - 51;
+        state.steering = 0;
+        state.steering_fine = 0;
+
+        assert!((config.value(Axis::Brake, &state) - 0.04).abs() < 0.0001);
+        assert_eq!(config.value(Axis::Steering, &state), -1.0);
+    }
+
+    #[test]
+    fn test_inverted() {
+        let mut config = AxisConfig::default();
+        config.set_inverted(Axis::Throttle, true);
+        config.set_inverted(Axis::ShifterX, true);
+
+        let mut state = get_test_state();
+        state.shifter_x = 255;
+
+        assert_eq!(config.value(Axis::Throttle, &state), 1.0);
+        assert_eq!(config.value(Axis::ShifterX, &state), -1.0);
+        assert_eq!(config.value(Axis::ShifterY, &state), -1.0);
+    }
+}
@@ -0,0 +1,368 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Sender},
+    thread::{self, JoinHandle},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::Frame;
+
+// pcapng block types
+const SECTION_HEADER_BLOCK: u32 = 0x0a0d_0d0a;
+const INTERFACE_DESCRIPTION_BLOCK: u32 = 0x0000_0001;
+const ENHANCED_PACKET_BLOCK: u32 = 0x0000_0006;
+const BYTE_ORDER_MAGIC: u32 = 0x1a2b_3c4d;
+
+// USB packets captured by the Linux usbmon interface, with the 64 byte memory mapped header
+const LINKTYPE_USB_LINUX_MMAPPED: u16 = 220;
+
+// pcapng options
+const OPTION_END: u16 = 0;
+const OPTION_EPB_FLAGS: u16 = 2;
+const FLAGS_INBOUND: u32 = 0b01;
+const FLAGS_OUTBOUND: u32 = 0b10;
+
+// usbmon fields
+const URB_SUBMIT: u8 = b'S';
+const URB_COMPLETE: u8 = b'C';
+const TRANSFER_INTERRUPT: u8 = 1;
+const ENDPOINT_IN: u8 = 0x81;
+const ENDPOINT_OUT: u8 = 0x01;
+const NO_SETUP: u8 = b'-';
+// The bus and device numbers are not available through hidapi
+const BUS_NUMBER: u16 = 1;
+const DEVICE_NUMBER: u8 = 1;
+
+///
+/// CaptureSettings
+///
+/// How a USB traffic capture is written
+/// - max_file_size: `Option<u64>` - Start a new file once a file reaches this many bytes (default: `None`)
+///
+/// When rolling over, the first file uses the given path and later files add a number to its name:
+/// `wheel.pcapng`, `wheel_1.pcapng`, `wheel_2.pcapng` and so on.
+///
+#[derive(Debug, PartialEq, Copy, Clone, Eq, Hash, Default)]
+pub struct CaptureSettings {
+    pub max_file_size: Option<u64>,
+}
+
+#[derive(Debug, Copy, Clone)]
+enum Packet {
+    Input { time: SystemTime, frame: Frame },
+    Output { time: SystemTime, command: [u8; 7] },
+}
+
+///
+/// Capture
+///
+/// Writes input reports and output commands to pcapng files that Wireshark can open,
+/// as interrupt transfers on the wheel's IN and OUT endpoints.
+///
+#[derive(Debug)]
+pub(crate) struct Capture {
+    sender: Sender<Packet>,
+    writer_handle: JoinHandle<io::Result<()>>,
+}
+
+impl Capture {
+    /// Create the first capture file.
+    pub(crate) fn start(path: &Path, settings: CaptureSettings) -> io::Result<Capture> {
+        let mut file = CaptureFile::create(path.to_path_buf())?;
+        let (sender, receiver) = mpsc::channel::<Packet>();
+
+        let path = path.to_path_buf();
+        let writer_handle = thread::spawn(move || {
+            let mut file_number = 0;
+
+            for (id, packet) in receiver.into_iter().enumerate() {
+                let block = enhanced_packet_block(id as u64, &packet);
+
+                let full = settings
+                    .max_file_size
+                    .is_some_and(|max| file.written > 0 && file.size + block.len() as u64 > max);
+
+                if full {
+                    file.writer.flush()?;
+                    file_number += 1;
+                    file = CaptureFile::create(numbered_path(&path, file_number))?;
+                }
+
+                file.write(&block)?;
+            }
+
+            file.writer.flush()
+        });
+
+        Ok(Capture {
+            sender,
+            writer_handle,
+        })
+    }
+
+    /// Queue a frame read from the wheel.
+    pub(crate) fn input(&self, frame: &Frame) {
+        let _ = self.sender.send(Packet::Input {
+            time: SystemTime::now(),
+            frame: *frame,
+        });
+    }
+
+    /// Queue a command written to the wheel.
+    pub(crate) fn output(&self, command: &[u8; 7]) {
+        let _ = self.sender.send(Packet::Output {
+            time: SystemTime::now(),
+            command: *command,
+        });
+    }
+
+    /// Write every queued packet and close the file.
+    pub(crate) fn finish(self) -> io::Result<()> {
+        drop(self.sender);
+
+        self.writer_handle
+            .join()
+            .unwrap_or_else(|_| Err(io::Error::other("capture -> Capture writer panicked")))
+    }
+}
+
+struct CaptureFile {
+    writer: BufWriter<File>,
+    // bytes in the file, including the headers
+    size: u64,
+    // packets in the file
+    written: u64,
+}
+
+impl CaptureFile {
+    fn create(path: PathBuf) -> io::Result<CaptureFile> {
+        let mut file = CaptureFile {
+            writer: BufWriter::new(File::create(path)?),
+            size: 0,
+            written: 0,
+        };
+
+        // section header: byte order, version 1.0, unknown section length
+        let mut body = Vec::new();
+        body.extend(BYTE_ORDER_MAGIC.to_le_bytes());
+        body.extend(1u16.to_le_bytes());
+        body.extend(0u16.to_le_bytes());
+        body.extend((-1i64).to_le_bytes());
+        file.write_block(SECTION_HEADER_BLOCK, &body)?;
+
+        // interface description: link type, reserved, no snapshot length limit
+        let mut body = Vec::new();
+        body.extend(LINKTYPE_USB_LINUX_MMAPPED.to_le_bytes());
+        body.extend(0u16.to_le_bytes());
+        body.extend(0u32.to_le_bytes());
+        file.write_block(INTERFACE_DESCRIPTION_BLOCK, &body)?;
+
+        Ok(file)
+    }
+
+    fn write_block(&mut self, block_type: u32, body: &[u8]) -> io::Result<()> {
+        let block = block(block_type, body);
+        self.writer.write_all(&block)?;
+        self.size += block.len() as u64;
+
+        Ok(())
+    }
+
+    fn write(&mut self, block: &[u8]) -> io::Result<()> {
+        self.writer.write_all(block)?;
+        self.size += block.len() as u64;
+        self.written += 1;
+
+        Ok(())
+    }
+}
+
+// wrap a block body with its type and lengths, padding the body to 32 bits
+fn block(block_type: u32, body: &[u8]) -> Vec<u8> {
+    let padding = (4 - body.len() % 4) % 4;
+    let length = (12 + body.len() + padding) as u32;
+
+    let mut block = Vec::with_capacity(length as usize);
+    block.extend(block_type.to_le_bytes());
+    block.extend(length.to_le_bytes());
+    block.extend(body);
+    block.extend(std::iter::repeat_n(0, padding));
+    block.extend(length.to_le_bytes());
+
+    block
+}
+
+fn enhanced_packet_block(id: u64, packet: &Packet) -> Vec<u8> {
+    let (time, kind, endpoint, data, flags): (_, _, _, &[u8], _) = match packet {
+        Packet::Input { time, frame } => (time, URB_COMPLETE, ENDPOINT_IN, frame, FLAGS_INBOUND),
+        Packet::Output { time, command } => {
+            (time, URB_SUBMIT, ENDPOINT_OUT, command, FLAGS_OUTBOUND)
+        }
+    };
+
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let micros = since_epoch.as_micros() as u64;
+
+    // usbmon memory mapped header
+    let mut usb = Vec::with_capacity(64 + data.len());
+    usb.extend(id.to_le_bytes());
+    usb.push(kind);
+    usb.push(TRANSFER_INTERRUPT);
+    usb.push(endpoint);
+    usb.push(DEVICE_NUMBER);
+    usb.extend(BUS_NUMBER.to_le_bytes());
+    usb.push(NO_SETUP);
+    // data present
+    usb.push(0);
+    usb.extend((since_epoch.as_secs() as i64).to_le_bytes());
+    usb.extend((since_epoch.subsec_micros() as i32).to_le_bytes());
+    // status
+    usb.extend(0i32.to_le_bytes());
+    // length and captured length
+    usb.extend((data.len() as u32).to_le_bytes());
+    usb.extend((data.len() as u32).to_le_bytes());
+    // setup
+    usb.extend([0u8; 8]);
+    // interval, start frame, transfer flags, iso descriptors
+    usb.extend(1i32.to_le_bytes());
+    usb.extend(0i32.to_le_bytes());
+    usb.extend(0u32.to_le_bytes());
+    usb.extend(0u32.to_le_bytes());
+    usb.extend(data);
+
+    // enhanced packet: interface 0, microsecond timestamp, lengths, packet, direction flags
+    let mut body = Vec::with_capacity(32 + usb.len());
+    body.extend(0u32.to_le_bytes());
+    body.extend(((micros >> 32) as u32).to_le_bytes());
+    body.extend((micros as u32).to_le_bytes());
+    body.extend((usb.len() as u32).to_le_bytes());
+    body.extend((usb.len() as u32).to_le_bytes());
+    body.extend(&usb);
+    body.extend(std::iter::repeat_n(0, (4 - usb.len() % 4) % 4));
+    body.extend(OPTION_EPB_FLAGS.to_le_bytes());
+    body.extend(4u16.to_le_bytes());
+    body.extend(flags.to_le_bytes());
+    body.extend(OPTION_END.to_le_bytes());
+    body.extend(0u16.to_le_bytes());
+
+    block(ENHANCED_PACKET_BLOCK, &body)
+}
+
+fn numbered_path(path: &Path, number: u32) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    let file_name = match path.extension() {
+        Some(extension) => format!("{}_{}.{}", stem, number, extension.to_string_lossy()),
+        None => format!("{}_{}", stem, number),
+    };
+
+    path.with_file_name(file_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::capture::{numbered_path, Capture, CaptureSettings};
+
+    // (block type, body) of every block in a pcapng file
+    fn read_blocks(path: &Path) -> Vec<(u32, Vec<u8>)> {
+        let data = std::fs::read(path).unwrap();
+        let mut blocks = Vec::new();
+        let mut offset = 0;
+
+        while offset < data.len() {
+            let word = |at: usize| u32::from_le_bytes(data[at..at + 4].try_into().unwrap());
+            let block_type = word(offset);
+            let length = word(offset + 4) as usize;
+
+            assert_eq!(word(offset + length - 4) as usize, length);
+            blocks.push((block_type, data[offset + 8..offset + length - 4].to_vec()));
+            offset += length;
+        }
+
+        blocks
+    }
+
+    #[test]
+    fn test_capture() {
+        let path = std::env::temp_dir().join(format!(
+            "lib-g29-capture-test-{}.pcapng",
+            std::process::id()
+        ));
+
+        let capture = Capture::start(&path, CaptureSettings::default()).unwrap();
+        capture.input(&[8, 0, 0, 0, 0, 128, 255, 255, 255, 128, 128, 0]);
+        capture.output(&[0xf8, 0x12, 0x1f, 0x00, 0x00, 0x00, 0x01]);
+        capture.finish().unwrap();
+
+        let blocks = read_blocks(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(blocks.len(), 4);
+        assert_eq!(blocks[0].0, 0x0a0d_0d0a);
+        assert_eq!(blocks[0].1[..4], 0x1a2b_3c4du32.to_le_bytes());
+        assert_eq!(blocks[1].0, 1);
+        assert_eq!(blocks[1].1[..2], 220u16.to_le_bytes());
+
+        // input: completed transfer on endpoint 0x81 with the 12 byte frame, inbound flag
+        let input = &blocks[2].1;
+        assert_eq!(blocks[2].0, 6);
+        assert_eq!(input[12..16], (64u32 + 12).to_le_bytes());
+        assert_eq!(input[20 + 8], b'C');
+        assert_eq!(input[20 + 10], 0x81);
+        assert_eq!(input[20 + 64], 8);
+        assert_eq!(input[input.len() - 8..input.len() - 4], 1u32.to_le_bytes());
+
+        // output: submitted transfer on endpoint 0x01 with the 7 byte command, outbound flag
+        let output = &blocks[3].1;
+        assert_eq!(output[20 + 8], b'S');
+        assert_eq!(output[20 + 10], 0x01);
+        assert_eq!(
+            output[20 + 64..20 + 71],
+            [0xf8, 0x12, 0x1f, 0x00, 0x00, 0x00, 0x01]
+        );
+        assert_eq!(
+            output[output.len() - 8..output.len() - 4],
+            2u32.to_le_bytes()
+        );
+    }
+
+    #[test]
+    fn test_capture_rollover() {
+        let dir =
+            std::env::temp_dir().join(format!("lib-g29-capture-rollover-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("wheel.pcapng");
+
+        // room for the headers and one packet per file
+        let capture = Capture::start(
+            &path,
+            CaptureSettings {
+                max_file_size: Some(200),
+            },
+        )
+        .unwrap();
+        for _ in 0..3 {
+            capture.input(&[0; 12]);
+        }
+        capture.finish().unwrap();
+
+        assert_eq!(numbered_path(&path, 2), dir.join("wheel_2.pcapng"));
+        for file in [
+            path.clone(),
+            numbered_path(&path, 1),
+            numbered_path(&path, 2),
+        ] {
+            assert_eq!(read_blocks(&file).len(), 3);
+        }
+        assert!(!numbered_path(&path, 3).exists());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,223 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Condvar, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+// The time the force threads run on. Wheels use the system clock. The simulator uses a virtual
+// clock that only moves when its script waits, and only returns once every thread on the clock
+// has run the ticks that were due
+#[derive(Debug, Clone, Default)]
+pub(crate) enum Clock {
+    #[default]
+    System,
+    Virtual(Arc<VirtualClock>),
+}
+
+impl Clock {
+    pub(crate) fn new_virtual() -> Clock {
+        Clock::Virtual(Arc::new(VirtualClock {
+            start: Instant::now(),
+            timeline: Mutex::new(Timeline::default()),
+            changed: Condvar::new(),
+        }))
+    }
+
+    pub(crate) fn now(&self) -> Instant {
+        match self {
+            Clock::System => Instant::now(),
+            Clock::Virtual(clock) => clock.start + clock.timeline.lock().unwrap().elapsed,
+        }
+    }
+
+    // Called before a thread using the clock is spawned, so `advance` waits for it to start
+    pub(crate) fn register(&self) -> Sleeper {
+        let id = match self {
+            Clock::System => 0,
+            Clock::Virtual(clock) => {
+                let mut timeline = clock.timeline.lock().unwrap();
+                timeline.next_id += 1;
+                timeline.threads += 1;
+                timeline.next_id
+            }
+        };
+
+        Sleeper {
+            clock: self.clone(),
+            id,
+        }
+    }
+
+    // Wake the sleeping threads to check if they were stopped
+    pub(crate) fn wake(&self) {
+        if let Clock::Virtual(clock) = self {
+            let _timeline = clock.timeline.lock().unwrap();
+            clock.changed.notify_all();
+        }
+    }
+
+    // Move a virtual clock forward, running every sleeping thread up to each of its deadlines
+    // in order. Does nothing for the system clock
+    pub(crate) fn advance(&self, duration: Duration) {
+        let Clock::Virtual(clock) = self else {
+            return;
+        };
+
+        let mut timeline = clock.timeline.lock().unwrap();
+        let target = timeline.elapsed + duration;
+
+        loop {
+            timeline = clock
+                .changed
+                .wait_while(timeline, |timeline| {
+                    timeline.sleeping.len() < timeline.threads
+                })
+                .unwrap();
+
+            let next = timeline
+                .sleeping
+                .iter()
+                .map(|(_, deadline)| *deadline)
+                .min()
+                .filter(|deadline| *deadline <= target);
+
+            let Some(next) = next else {
+                timeline.elapsed = target;
+                return;
+            };
+
+            // the woken threads count as running until they sleep again
+            timeline.elapsed = timeline.elapsed.max(next);
+            let elapsed = timeline.elapsed;
+            timeline
+                .sleeping
+                .retain(|(_, deadline)| *deadline > elapsed);
+            clock.changed.notify_all();
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct VirtualClock {
+    start: Instant,
+    timeline: Mutex<Timeline>,
+    changed: Condvar,
+}
+
+// The threads on a virtual clock and the deadlines of those that are asleep
+#[derive(Debug, Default)]
+struct Timeline {
+    elapsed: Duration,
+    threads: usize,
+    sleeping: Vec<(u64, Duration)>,
+    next_id: u64,
+}
+
+// A thread's place on the clock, dropped when the thread ends
+#[derive(Debug)]
+pub(crate) struct Sleeper {
+    clock: Clock,
+    id: u64,
+}
+
+impl Sleeper {
+    // Sleep until the deadline, or until `running` is cleared and the clock woken
+    pub(crate) fn sleep_until(&self, deadline: Instant, running: &AtomicBool) {
+        let clock = match &self.clock {
+            Clock::System => {
+                thread::sleep(deadline.saturating_duration_since(Instant::now()));
+                return;
+            }
+            Clock::Virtual(clock) => clock,
+        };
+
+        let deadline = deadline.saturating_duration_since(clock.start);
+        let mut timeline = clock.timeline.lock().unwrap();
+
+        if timeline.elapsed >= deadline {
+            return;
+        }
+
+        timeline.sleeping.push((self.id, deadline));
+        clock.changed.notify_all();
+
+        timeline = clock
+            .changed
+            .wait_while(timeline, |timeline| {
+                timeline.sleeping.iter().any(|(id, _)| *id == self.id)
+                    && running.load(Ordering::Acquire)
+            })
+            .unwrap();
+
+        timeline.sleeping.retain(|(id, _)| *id != self.id);
+    }
+}
+
+impl Drop for Sleeper {
+    fn drop(&mut self) {
+        if let Clock::Virtual(clock) = &self.clock {
+            let mut timeline = clock.timeline.lock().unwrap();
+            timeline.threads -= 1;
+            timeline.sleeping.retain(|(id, _)| *id != self.id);
+            clock.changed.notify_all();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            atomic::{AtomicBool, AtomicU32, Ordering},
+            Arc,
+        },
+        thread,
+        time::Duration,
+    };
+
+    use crate::clock::Clock;
+
+    #[test]
+    fn test_virtual_clock() {
+        let clock = Clock::new_virtual();
+        let start = clock.now();
+        let ticks = Arc::new(AtomicU32::new(0));
+        let running = Arc::new(AtomicBool::new(true));
+
+        let sleeper = clock.register();
+        let thread = {
+            let clock = clock.clone();
+            let ticks = ticks.clone();
+            let running = running.clone();
+
+            thread::spawn(move || {
+                let mut next = clock.now();
+
+                while running.load(Ordering::Acquire) {
+                    ticks.fetch_add(1, Ordering::AcqRel);
+                    next += Duration::from_millis(4);
+                    sleeper.sleep_until(next, &running);
+                }
+            })
+        };
+
+        // a tick at 0, 4 and 8 ms
+        clock.advance(Duration::from_millis(10));
+        assert_eq!(ticks.load(Ordering::Acquire), 3);
+        assert_eq!(clock.now() - start, Duration::from_millis(10));
+
+        clock.advance(Duration::from_millis(2));
+        assert_eq!(ticks.load(Ordering::Acquire), 4);
+
+        running.store(false, Ordering::Release);
+        clock.wake();
+        thread.join().unwrap();
+
+        // nothing left to wait for
+        clock.advance(Duration::from_millis(100));
+        assert_eq!(ticks.load(Ordering::Acquire), 4);
+    }
+}
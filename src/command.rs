@@ -0,0 +1,315 @@
+use crate::{effects::AutoCenter, Led};
+
+///
+/// Command
+///
+/// A 7 byte output report sent to the G29.
+/// Commands follow the Logitech classic force feedback protocol, as used by the Linux `hid-lg4ff` driver.
+///
+/// Force commands address effect slots with a mask: bit 0 is slot 1 and bit 3 is slot 4.
+///
+/// # Example
+///
+/// ```rust
+/// use lib_g29::{command::Command, Led};
+///
+/// let command = Command::SetLeds(Led::All);
+/// assert_eq!(command.encode(), [0xf8, 0x12, 0x1f, 0x00, 0x00, 0x00, 0x01]);
+/// assert_eq!(Command::decode(&command.encode()), command);
+///
+/// g29.send_command(Command::SetRange(540));
+/// ```
+///
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Command {
+    /// Stop the wheel from remembering its mode when it is reset. Sent before `SwitchMode`
+    RevertModeOnReset,
+    /// Switch the wheel to another compatibility mode and reattach it. `0x05` is native G29 mode
+    SwitchMode { mode: u8 },
+    /// Set the rev LEDs
+    SetLeds(Led),
+    /// Set the rotation range in degrees (40 - 900)
+    SetRange(u16),
+    /// Turn the built in auto-center spring on
+    AutoCenterOn,
+    /// Turn the built in auto-center spring off
+    AutoCenterOff,
+    /// Set the strength of the auto-center spring, see `G29::set_auto_center_force`
+    SetAutoCenterForce {
+        strength: u8,
+        turning_multiplier: u8,
+    },
+    /// Set the advanced auto-center spring, see `G29::set_auto_center_advanced`.
+    /// The two forces share a byte, clockwise in the high nibble
+    AutoCenterAdvanced(AutoCenter),
+    /// Download a friction effect to the slots and play it.
+    /// `left` and `right` are the raw coefficients, 0 - 255
+    Friction { slots: u8, left: u8, right: u8 },
+    /// Download a constant force to the slots and play it.
+    /// `level` is the raw force, `0x80` is no force
+    ConstantForce { slots: u8, level: u8 },
+    /// Download an effect to the slots and play it.
+    /// `params` are the effect type followed by its parameters
+    DownloadAndPlay { slots: u8, params: [u8; 6] },
+    /// Stop the effects in the slots, `0x0f` stops every slot
+    StopForces { slots: u8 },
+    /// Any other report
+    Unknown([u8; 7]),
+}
+
+impl Command {
+    /// Encode the command as the 7 bytes written to the wheel.
+    pub fn encode(&self) -> [u8; 7] {
+        match *self {
+            Command::RevertModeOnReset => [0xf8, 0x0a, 0x00, 0x00, 0x00, 0x00, 0x00],
+            Command::SwitchMode { mode } => [0xf8, 0x09, mode, 0x01, 0x01, 0x00, 0x00],
+            Command::SetLeds(leds) => [0xf8, 0x12, leds.as_u8(), 0x00, 0x00, 0x00, 0x01],
+            Command::SetRange(range) => {
+                let [low, high] = range.to_le_bytes();
+                [0xf8, 0x81, low, high, 0x00, 0x00, 0x00]
+            }
+            Command::AutoCenterOn => [0x14, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+            Command::AutoCenterOff => [0xf5, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+            Command::SetAutoCenterForce {
+                strength,
+                turning_multiplier,
+            } => [
+                0xfe,
+                0x0d,
+                strength,
+                strength,
+                turning_multiplier,
+                0x00,
+                0x00,
+            ],
+            Command::AutoCenterAdvanced(auto_center) => [
+                0xfc,
+                0x01,
+                auto_center.clockwise_angle,
+                auto_center.counter_clockwise_angle,
+                (auto_center.clockwise_force & 0x0f) << 4
+                    | (auto_center.counter_clockwise_force & 0x0f),
+                auto_center.reverse as u8,
+                auto_center.centering_force,
+            ],
+            Command::Friction { slots, left, right } => [
+                (slots & 0x0f) << 4 | 0x01,
+                0x02,
+                left,
+                0x00,
+                right,
+                0x00,
+                0x00,
+            ],
+            Command::ConstantForce { slots, level } => [
+                (slots & 0x0f) << 4 | 0x01,
+                0x08,
+                level,
+                0x80,
+                0x00,
+                0x00,
+                0x00,
+            ],
+            Command::DownloadAndPlay { slots, params } => {
+                let [a, b, c, d, e, f] = params;
+                [(slots & 0x0f) << 4 | 0x01, a, b, c, d, e, f]
+            }
+            Command::StopForces { slots } => [(slots & 0x0f) << 4 | 0x03, 0, 0, 0, 0, 0, 0],
+            Command::Unknown(data) => data,
+        }
+    }
+
+    /// Decode a report written to the wheel.
+    ///
+    /// An 8 byte report starting with `0x00` is treated as a 7 byte report with the report id
+    /// prepended, as written on Windows. Anything that is not a known command decodes to
+    /// `Command::Unknown`, zero padded or truncated to 7 bytes.
+    pub fn decode(data: &[u8]) -> Command {
+        let data = match data {
+            [0x00, rest @ ..] if rest.len() == 7 => rest,
+            _ => data,
+        };
+
+        let mut report = [0u8; 7];
+        let length = data.len().min(7);
+        report[..length].copy_from_slice(&data[..length]);
+
+        if data.len() != 7 {
+            return Command::Unknown(report);
+        }
+
+        let command = match report {
+            [0xf8, 0x0a, 0, 0, 0, 0, 0] => Command::RevertModeOnReset,
+            [0xf8, 0x09, mode, 0x01, 0x01, 0, 0] => Command::SwitchMode { mode },
+            [0xf8, 0x12, leds, 0, 0, 0, 0x01] => Command::SetLeds(Led::from_u8(leds)),
+            [0xf8, 0x81, low, high, 0, 0, 0] => Command::SetRange(u16::from_le_bytes([low, high])),
+            [0x14, 0, 0, 0, 0, 0, 0] => Command::AutoCenterOn,
+            [0xf5, 0, 0, 0, 0, 0, 0] => Command::AutoCenterOff,
+            [0xfe, 0x0d, strength, repeated, turning_multiplier, 0, 0] if strength == repeated => {
+                Command::SetAutoCenterForce {
+                    strength,
+                    turning_multiplier,
+                }
+            }
+            [0xfc, 0x01, clockwise_angle, counter_clockwise_angle, forces, reverse @ (0 | 1), centering_force] => {
+                Command::AutoCenterAdvanced(AutoCenter {
+                    clockwise_angle,
+                    counter_clockwise_angle,
+                    clockwise_force: forces >> 4,
+                    counter_clockwise_force: forces & 0x0f,
+                    reverse: reverse == 1,
+                    centering_force,
+                })
+            }
+            [first, 0x02, left, 0, right, 0, 0] if first & 0x0f == 0x01 && first >> 4 != 0 => {
+                Command::Friction {
+                    slots: first >> 4,
+                    left,
+                    right,
+                }
+            }
+            [first, 0x08, level, 0x80, 0, 0, 0] if first & 0x0f == 0x01 && first >> 4 != 0 => {
+                Command::ConstantForce {
+                    slots: first >> 4,
+                    level,
+                }
+            }
+            [first, a, b, c, d, e, f] if first & 0x0f == 0x01 && first >> 4 != 0 => {
+                Command::DownloadAndPlay {
+                    slots: first >> 4,
+                    params: [a, b, c, d, e, f],
+                }
+            }
+            [first, 0, 0, 0, 0, 0, 0] if first & 0x0f == 0x03 => {
+                Command::StopForces { slots: first >> 4 }
+            }
+            _ => Command::Unknown(report),
+        };
+
+        // only accept a decoding that encodes back to the same bytes
+        if command.encode() == report {
+            command
+        } else {
+            Command::Unknown(report)
+        }
+    }
+
+    /// A short name for the command, for logging.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Command::RevertModeOnReset => "revert_mode_on_reset",
+            Command::SwitchMode { .. } => "switch_mode",
+            Command::SetLeds(_) => "set_leds",
+            Command::SetRange(_) => "set_range",
+            Command::AutoCenterOn => "auto_center_on",
+            Command::AutoCenterOff => "auto_center_off",
+            Command::SetAutoCenterForce { .. } => "set_auto_center_force",
+            Command::AutoCenterAdvanced(_) => "set_auto_center_advanced",
+            Command::Friction { .. } => "friction",
+            Command::ConstantForce { .. } => "constant_force",
+            Command::DownloadAndPlay { .. } => "download_and_play",
+            Command::StopForces { .. } => "stop_forces",
+            Command::Unknown(_) => "unknown",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{command::Command, effects::AutoCenter, Led};
+
+    #[test]
+    fn test_round_trip() {
+        for command in [
+            Command::RevertModeOnReset,
+            Command::SwitchMode { mode: 0x05 },
+            Command::SetLeds(Led::None),
+            Command::SetLeds(Led::All),
+            Command::SetLeds(Led::Other(0x03)),
+            Command::SetRange(900),
+            Command::SetRange(40),
+            Command::AutoCenterOn,
+            Command::AutoCenterOff,
+            Command::SetAutoCenterForce {
+                strength: 0x07,
+                turning_multiplier: 0xff,
+            },
+            Command::AutoCenterAdvanced(AutoCenter::default()),
+            Command::AutoCenterAdvanced(AutoCenter {
+                clockwise_angle: 0x10,
+                counter_clockwise_angle: 0x20,
+                clockwise_force: 0x0f,
+                counter_clockwise_force: 0x03,
+                reverse: true,
+                centering_force: 0x80,
+            }),
+            Command::Friction {
+                slots: 0x02,
+                left: 49,
+                right: 14,
+            },
+            Command::ConstantForce {
+                slots: 0x01,
+                level: 0x20,
+            },
+            Command::DownloadAndPlay {
+                slots: 0x01,
+                params: [0x00, 0x80, 0x80, 0x80, 0x80, 0x00],
+            },
+            Command::StopForces { slots: 0x0f },
+            Command::StopForces { slots: 0x02 },
+            Command::Unknown([0xf8, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06]),
+        ] {
+            assert_eq!(Command::decode(&command.encode()), command);
+        }
+    }
+
+    #[test]
+    fn test_encode_matches_wheel_protocol() {
+        assert_eq!(
+            Command::SetRange(900).encode(),
+            [0xf8, 0x81, 0x84, 0x03, 0x00, 0x00, 0x00]
+        );
+        assert_eq!(
+            Command::StopForces { slots: 0x0f }.encode(),
+            [0xf3, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]
+        );
+        assert_eq!(
+            Command::ConstantForce {
+                slots: 0x01,
+                level: 0xc0
+            }
+            .encode(),
+            [0x11, 0x08, 0xc0, 0x80, 0x00, 0x00, 0x00]
+        );
+        assert_eq!(
+            Command::SwitchMode { mode: 0x05 }.encode(),
+            [0xf8, 0x09, 0x05, 0x01, 0x01, 0x00, 0x00]
+        );
+        assert_eq!(
+            Command::AutoCenterAdvanced(AutoCenter {
+                clockwise_force: 0x0a,
+                counter_clockwise_force: 0x05,
+                ..Default::default()
+            })
+            .encode(),
+            [0xfc, 0x01, 0x00, 0x00, 0xa5, 0x00, 0xff]
+        );
+    }
+
+    #[test]
+    fn test_decode_unknown_and_prefixed() {
+        assert_eq!(
+            Command::decode(&[0x00, 0xf8, 0x12, 0x1f, 0x00, 0x00, 0x00, 0x01]),
+            Command::SetLeds(Led::All)
+        );
+        assert_eq!(
+            Command::decode(&[0xf8, 0x12]),
+            Command::Unknown([0xf8, 0x12, 0x00, 0x00, 0x00, 0x00, 0x00])
+        );
+        assert_eq!(
+            Command::decode(&[0xfe, 0x0d, 0x01, 0x02, 0x03, 0x00, 0x00]),
+            Command::Unknown([0xfe, 0x0d, 0x01, 0x02, 0x03, 0x00, 0x00])
+        );
+    }
+}
@@ -0,0 +1,259 @@
+use std::{fmt, io, str::FromStr};
+
+///
+/// ResponseCurve
+///
+/// Shapes how a normalized axis value responds to input.
+///
+/// Curves map 0.0 - 1.0 onto 0.0 - 1.0. Centered axes such as steering apply the curve
+/// to the distance from center, so both directions respond the same way.
+///
+/// # Example
+///
+/// ```rust
+/// use lib_g29::{axis::Axis, curve::ResponseCurve};
+///
+/// // less sensitive around the top of the pedal travel
+/// g29.set_response_curve(Axis::Brake, ResponseCurve::Gamma(2.0))?;
+///
+/// // plot the curve
+/// for (input, output) in ResponseCurve::SCurve(2.0).sample(11) {
+///     println!("{:.1} -> {:.2}", input, output);
+/// }
+/// ```
+///
+#[derive(Debug, PartialEq, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ResponseCurve {
+    /// Output equals input
+    #[default]
+    Linear,
+    /// `input ^ gamma`, above 1.0 is less sensitive at the start of the travel, below 1.0 is more sensitive.
+    /// Must be above 0.0
+    Gamma(f32),
+    /// Less sensitive at both ends of the travel and more sensitive in the middle.
+    /// 1.0 is linear, higher values are steeper. Must be above 0.0
+    SCurve(f32),
+    /// `(input, output)` points, linearly interpolated. Points must be sorted by input
+    Lookup(Vec<(f32, f32)>),
+}
+
+impl ResponseCurve {
+    /// Check the parameter of a gamma or S-curve is a finite number above 0.0.
+    pub fn validate(&self) -> io::Result<()> {
+        match self {
+            ResponseCurve::Gamma(value) | ResponseCurve::SCurve(value)
+                if !value.is_finite() || *value <= 0.0 =>
+            {
+                Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("curve -> Parameter must be above 0: {}", value),
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Apply the curve to a value between 0.0 and 1.0.
+    /// A curve that does not `validate` is applied as `Linear`.
+    pub fn apply(&self, input: f32) -> f32 {
+        let input = input.clamp(0.0, 1.0);
+
+        let output = match self {
+            _ if self.validate().is_err() => input,
+            ResponseCurve::Linear => input,
+            ResponseCurve::Gamma(gamma) => input.powf(*gamma),
+            ResponseCurve::SCurve(steepness) => {
+                let rising = input.powf(*steepness);
+                let falling = (1.0 - input).powf(*steepness);
+
+                rising / (rising + falling)
+            }
+            ResponseCurve::Lookup(points) => interpolate(points, input),
+        };
+
+        output.clamp(0.0, 1.0)
+    }
+
+    /// Apply the curve to a value between -1.0 and 1.0, keeping its sign.
+    pub fn apply_centered(&self, input: f32) -> f32 {
+        self.apply(input.abs()).copysign(input)
+    }
+
+    /// Sample the curve at evenly spaced inputs from 0.0 to 1.0, returning `(input, output)` pairs.
+    pub fn sample(&self, points: usize) -> Vec<(f32, f32)> {
+        match points {
+            0 => Vec::new(),
+            1 => vec![(0.0, self.apply(0.0))],
+            _ => (0..points)
+                .map(|i| i as f32 / (points - 1) as f32)
+                .map(|input| (input, self.apply(input)))
+                .collect(),
+        }
+    }
+}
+
+fn interpolate(points: &[(f32, f32)], input: f32) -> f32 {
+    let (Some(first), Some(last)) = (points.first(), points.last()) else {
+        return input;
+    };
+
+    if input <= first.0 {
+        return first.1;
+    }
+
+    points
+        .windows(2)
+        .find(|pair| input <= pair[1].0)
+        .map(|pair| {
+            let ((x0, y0), (x1, y1)) = (pair[0], pair[1]);
+
+            if x1 == x0 {
+                y1
+            } else {
+                y0 + (y1 - y0) * (input - x0) / (x1 - x0)
+            }
+        })
+        .unwrap_or(last.1)
+}
+
+/// Curves are written as `linear`, `gamma 2.2`, `s_curve 2` or `lookup 0:0,0.5:0.25,1:1`.
+impl fmt::Display for ResponseCurve {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResponseCurve::Linear => write!(f, "linear"),
+            ResponseCurve::Gamma(gamma) => write!(f, "gamma {}", gamma),
+            ResponseCurve::SCurve(steepness) => write!(f, "s_curve {}", steepness),
+            ResponseCurve::Lookup(points) => {
+                let points: Vec<String> = points
+                    .iter()
+                    .map(|(input, output)| format!("{}:{}", input, output))
+                    .collect();
+
+                write!(f, "lookup {}", points.join(","))
+            }
+        }
+    }
+}
+
+impl FromStr for ResponseCurve {
+    type Err = io::Error;
+
+    fn from_str(text: &str) -> io::Result<ResponseCurve> {
+        let invalid = || {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("curve -> Invalid response curve: {}", text),
+            )
+        };
+
+        let (kind, value) = text
+            .trim()
+            .split_once(' ')
+            .map(|(kind, value)| (kind, value.trim()))
+            .unwrap_or((text.trim(), ""));
+
+        let curve = match kind {
+            "linear" => Ok(ResponseCurve::Linear),
+            "gamma" => Ok(ResponseCurve::Gamma(value.parse().map_err(|_| invalid())?)),
+            "s_curve" => Ok(ResponseCurve::SCurve(value.parse().map_err(|_| invalid())?)),
+            "lookup" => value
+                .split(',')
+                .map(|point| {
+                    let (input, output) = point.split_once(':')?;
+                    Some((input.trim().parse().ok()?, output.trim().parse().ok()?))
+                })
+                .collect::<Option<Vec<(f32, f32)>>>()
+                .map(ResponseCurve::Lookup)
+                .ok_or_else(invalid),
+            _ => Err(invalid()),
+        }?;
+
+        curve.validate().map_err(|_| invalid())?;
+
+        Ok(curve)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::curve::ResponseCurve;
+
+    #[test]
+    fn test_linear() {
+        assert_eq!(ResponseCurve::Linear.apply(0.25), 0.25);
+        assert_eq!(ResponseCurve::Linear.apply(1.5), 1.0);
+    }
+
+    #[test]
+    fn test_gamma() {
+        let curve = ResponseCurve::Gamma(2.0);
+
+        assert_eq!(curve.apply(0.5), 0.25);
+        assert_eq!(curve.apply(1.0), 1.0);
+        assert_eq!(curve.apply_centered(-0.5), -0.25);
+    }
+
+    #[test]
+    fn test_s_curve() {
+        let curve = ResponseCurve::SCurve(2.0);
+
+        assert_eq!(curve.apply(0.0), 0.0);
+        assert_eq!(curve.apply(0.5), 0.5);
+        assert_eq!(curve.apply(1.0), 1.0);
+        assert!(curve.apply(0.25) < 0.25);
+        assert!(curve.apply(0.75) > 0.75);
+    }
+
+    #[test]
+    fn test_lookup() {
+        let curve = ResponseCurve::Lookup(vec![(0.0, 0.0), (0.5, 0.2), (1.0, 1.0)]);
+
+        assert_eq!(curve.apply(0.25), 0.1);
+        assert_eq!(curve.apply(0.75), 0.6);
+        assert_eq!(curve.apply(1.0), 1.0);
+        assert_eq!(ResponseCurve::Lookup(vec![]).apply(0.3), 0.3);
+    }
+
+    #[test]
+    fn test_sample() {
+        let samples = ResponseCurve::Gamma(2.0).sample(3);
+
+        assert_eq!(samples, vec![(0.0, 0.0), (0.5, 0.25), (1.0, 1.0)]);
+        assert!(ResponseCurve::Linear.sample(0).is_empty());
+    }
+
+    #[test]
+    fn test_curve_round_trip() {
+        for curve in [
+            ResponseCurve::Linear,
+            ResponseCurve::Gamma(2.2),
+            ResponseCurve::SCurve(3.0),
+            ResponseCurve::Lookup(vec![(0.0, 0.0), (0.5, 0.25), (1.0, 1.0)]),
+        ] {
+            assert_eq!(curve.to_string().parse::<ResponseCurve>().unwrap(), curve);
+        }
+
+        assert!("cubic".parse::<ResponseCurve>().is_err());
+        assert!("gamma".parse::<ResponseCurve>().is_err());
+        assert!("lookup 0:0,1".parse::<ResponseCurve>().is_err());
+    }
+
+    #[test]
+    fn test_invalid_parameter() {
+        for curve in [
+            ResponseCurve::Gamma(0.0),
+            ResponseCurve::Gamma(-1.0),
+            ResponseCurve::SCurve(f32::NAN),
+            ResponseCurve::SCurve(f32::INFINITY),
+        ] {
+            assert!(curve.validate().is_err());
+            assert_eq!(curve.apply(0.25), 0.25);
+        }
+
+        assert!(ResponseCurve::Gamma(0.5).validate().is_ok());
+        assert!("gamma 0".parse::<ResponseCurve>().is_err());
+        assert!("s_curve NaN".parse::<ResponseCurve>().is_err());
+        assert!("s_curve -2".parse::<ResponseCurve>().is_err());
+    }
+}
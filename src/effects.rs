@@ -0,0 +1,853 @@
+use std::{fmt, io};
+
+use crate::{command::Command, G29};
+
+///
+/// Slot
+///
+/// One of the four force slots of the wheel. Each slot plays one effect at a time.
+///
+#[derive(Debug, PartialEq, Copy, Clone, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Slot {
+    One,
+    Two,
+    Three,
+    Four,
+}
+
+impl Slot {
+    /// Every slot, in order.
+    pub const ALL: [Slot; 4] = [Slot::One, Slot::Two, Slot::Three, Slot::Four];
+
+    /// The mask addressing every slot at once.
+    pub const ALL_MASK: u8 = 0x0f;
+
+    /// Get a slot from its number, 1 to 4.
+    pub fn from_number(number: u8) -> Option<Slot> {
+        Slot::ALL.get((number as usize).wrapping_sub(1)).copied()
+    }
+
+    /// The number of the slot, 1 to 4.
+    pub fn number(&self) -> u8 {
+        self.index() as u8 + 1
+    }
+
+    /// The mask addressing the slot in force commands.
+    pub fn mask(&self) -> u8 {
+        1 << self.index()
+    }
+
+    fn index(&self) -> usize {
+        match self {
+            Slot::One => 0,
+            Slot::Two => 1,
+            Slot::Three => 2,
+            Slot::Four => 3,
+        }
+    }
+}
+
+///
+/// EffectKind
+///
+/// The kinds of effects the wheel plays in its force slots
+///
+#[derive(Debug, PartialEq, Copy, Clone, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum EffectKind {
+    Constant,
+    Friction,
+    Spring,
+    Damper,
+}
+
+///
+/// Condition
+///
+/// The parameters of a spring or damper
+/// - left_coefficient: `f32` - The strength left of the deadband, -1.0 to 1.0 (default: `0.5`)
+/// - right_coefficient: `f32` - The strength right of the deadband, -1.0 to 1.0 (default: `0.5`)
+/// - saturation: `f32` - The maximum force, 0.0 to 1.0 (default: `1.0`)
+/// - center: `f32` - The steering position of the middle of the deadband, -1.0 (fully left)
+///   to 1.0 (fully right). Springs only (default: `0.0`)
+/// - deadband: `f32` - The width of the deadband with no force, 0.0 to 2.0 (the whole range).
+///   Springs only (default: `0.0`)
+///
+/// Negative coefficients push away from the center instead of towards it.
+/// Values are clamped to their range and sent with the precision of the wheel:
+/// coefficients have 15 steps in each direction, saturation has 255 steps and the deadband
+/// edges 2047 steps across the steering range.
+///
+#[derive(Debug, PartialEq, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Condition {
+    pub left_coefficient: f32,
+    pub right_coefficient: f32,
+    pub saturation: f32,
+    pub center: f32,
+    pub deadband: f32,
+}
+
+impl Default for Condition {
+    fn default() -> Self {
+        Condition {
+            left_coefficient: 0.5,
+            right_coefficient: 0.5,
+            saturation: 1.0,
+            center: 0.0,
+            deadband: 0.0,
+        }
+    }
+}
+
+impl Condition {
+    /// A condition with the same coefficient on both sides and full saturation.
+    pub fn new(coefficient: f32) -> Condition {
+        Condition {
+            left_coefficient: coefficient,
+            right_coefficient: coefficient,
+            ..Default::default()
+        }
+    }
+
+    /// The spring that stands in for the simple auto-center while the steering is trimmed.
+    ///
+    /// The wheel's `strength` (**0x00** to **0x0f**) is the force its auto-center settles at, and
+    /// becomes the saturation (`strength / 15`). The `turning_multiplier` (**0x00** to **0xff**) is
+    /// how fast the force rises as the wheel turns, and becomes the coefficient
+    /// (`turning_multiplier / 255`). Both keep their full range, but the wheel does not publish the
+    /// curve of its own spring, so the feel is close to the untrimmed auto-center, not the same.
+    pub fn auto_center(strength: u8, turning_multiplier: u8) -> Condition {
+        Condition {
+            saturation: strength as f32 / 15.0,
+            ..Condition::new(turning_multiplier as f32 / 255.0)
+        }
+    }
+
+    // 4-bit magnitude and sign
+    fn coefficients(&self) -> [(u8, u8); 2] {
+        [self.left_coefficient, self.right_coefficient].map(|coefficient| {
+            let coefficient = clamp(coefficient, -1.0, 1.0);
+            (
+                (coefficient.abs() * 15.0).round() as u8,
+                (coefficient < 0.0) as u8,
+            )
+        })
+    }
+
+    fn clip(&self) -> u8 {
+        (clamp(self.saturation, 0.0, 1.0) * 255.0).round() as u8
+    }
+
+    fn is_zero(&self) -> bool {
+        self.coefficients().iter().all(|(k, _)| *k == 0) || self.clip() == 0
+    }
+
+    // The deadband edges as 11-bit steering positions
+    fn deadband_edges(&self) -> (u16, u16) {
+        let center = clamp(self.center, -1.0, 1.0);
+        let half = clamp(self.deadband, 0.0, 2.0) / 2.0;
+        let position = |value: f32| ((clamp(value, -1.0, 1.0) + 1.0) / 2.0 * 2047.0).round() as u16;
+
+        (position(center - half), position(center + half))
+    }
+}
+
+///
+/// AutoCenter
+///
+/// The parameters of the advanced auto-center spring, see `G29::set_auto_center_advanced`
+/// - clockwise_angle: `u8` - How far the wheel turns clockwise before the spring starts (default: `0x00`)
+/// - counter_clockwise_angle: `u8` - How far the wheel turns counter-clockwise before the spring starts (default: `0x00`)
+/// - clockwise_force: `u8` - The strength of the spring when turned clockwise, **0x00** to **0x0f** (default: `0x07`)
+/// - counter_clockwise_force: `u8` - The strength of the spring when turned counter-clockwise, **0x00** to **0x0f** (default: `0x07`)
+/// - reverse: `bool` - Push the wheel away from the center instead of towards it (default: `false`)
+/// - centering_force: `u8` - The overall strength of the spring (default: `0xff`)
+///
+#[derive(Debug, PartialEq, Copy, Clone, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AutoCenter {
+    pub clockwise_angle: u8,
+    pub counter_clockwise_angle: u8,
+    pub clockwise_force: u8,
+    pub counter_clockwise_force: u8,
+    pub reverse: bool,
+    pub centering_force: u8,
+}
+
+impl Default for AutoCenter {
+    fn default() -> Self {
+        AutoCenter {
+            clockwise_angle: 0x00,
+            counter_clockwise_angle: 0x00,
+            clockwise_force: 0x07,
+            counter_clockwise_force: 0x07,
+            reverse: false,
+            centering_force: 0xff,
+        }
+    }
+}
+
+impl AutoCenter {
+    /// Check the parameters fit in the command.
+    pub fn validate(&self) -> io::Result<()> {
+        if self.clockwise_force > 0x0f || self.counter_clockwise_force > 0x0f {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "auto_center -> Forces must be 0x00 to 0x0f",
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// The spring that stands in for the advanced auto-center while the steering is trimmed.
+    ///
+    /// The direction forces set how hard each side pulls and become the coefficients
+    /// (`force / 15`, clockwise on the right), negated when `reverse` is set. The centering force
+    /// caps the pull and becomes the saturation (`centering_force / 255`). Both have the same
+    /// steps as the spring. A spring has one deadband around its center, so the angles are not used.
+    pub fn spring(&self) -> Condition {
+        let sign = if self.reverse { -1.0 } else { 1.0 };
+
+        Condition {
+            left_coefficient: sign * self.counter_clockwise_force as f32 / 15.0,
+            right_coefficient: sign * self.clockwise_force as f32 / 15.0,
+            saturation: self.centering_force as f32 / 255.0,
+            ..Default::default()
+        }
+    }
+}
+
+// NaN is treated as the lowest value
+fn clamp(value: f32, min: f32, max: f32) -> f32 {
+    if value.is_nan() {
+        min.max(0.0).min(max)
+    } else {
+        value.clamp(min, max)
+    }
+}
+
+///
+/// Effect
+///
+/// A force effect and its parameters
+///
+#[derive(Debug, PartialEq, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Effect {
+    /// A constant force between -1.0 (full force to the left) and 1.0 (full force to the right).
+    /// Values outside that range are clamped
+    Constant(f32),
+    /// Friction, resisting movement in each direction.
+    /// `left` and `right` are the raw coefficients, 0 - 255
+    Friction { left: u8, right: u8 },
+    /// A spring pulling the wheel towards the deadband, stronger the further it is turned
+    Spring(Condition),
+    /// A damper resisting the speed the wheel turns at
+    Damper(Condition),
+}
+
+impl Effect {
+    /// The kind of the effect.
+    pub fn kind(&self) -> EffectKind {
+        match self {
+            Effect::Constant(_) => EffectKind::Constant,
+            Effect::Friction { .. } => EffectKind::Friction,
+            Effect::Spring(_) => EffectKind::Spring,
+            Effect::Damper(_) => EffectKind::Damper,
+        }
+    }
+
+    /// The command playing the effect in the slots.
+    /// Effects with no force stop the slots instead.
+    pub fn command(&self, slots: u8) -> Command {
+        match *self {
+            Effect::Constant(level) => {
+                // 0x80 is no force, 0x01 and 0xff are full force
+                let level = clamp(level, -1.0, 1.0);
+                let level = (128.0 + level * 127.0).round() as u8;
+
+                if level == 0x80 {
+                    Command::StopForces { slots }
+                } else {
+                    Command::ConstantForce { slots, level }
+                }
+            }
+            Effect::Friction { left: 0, right: 0 } => Command::StopForces { slots },
+            Effect::Friction { left, right } => Command::Friction { slots, left, right },
+            Effect::Spring(condition) | Effect::Damper(condition) if condition.is_zero() => {
+                Command::StopForces { slots }
+            }
+            Effect::Spring(condition) => {
+                let [(k1, s1), (k2, s2)] = condition.coefficients();
+                let (d1, d2) = condition.deadband_edges();
+
+                Command::DownloadAndPlay {
+                    slots,
+                    params: [
+                        0x0b,
+                        (d1 >> 3) as u8,
+                        (d2 >> 3) as u8,
+                        k2 << 4 | k1,
+                        ((d2 & 7) << 5) as u8 | ((d1 & 7) << 1) as u8 | s2 << 4 | s1,
+                        condition.clip(),
+                    ],
+                }
+            }
+            Effect::Damper(condition) => {
+                let [(k1, s1), (k2, s2)] = condition.coefficients();
+
+                Command::DownloadAndPlay {
+                    slots,
+                    params: [0x0c, k1, s1, k2, s2, condition.clip()],
+                }
+            }
+        }
+    }
+}
+
+// Which effect plays in each slot. Every allocation gets a new generation, so a handle
+// from before `release_all_effects` cannot free a slot that was allocated again.
+// Effects played without a handle, such as `force_friction`, hold their fixed slot with
+// generation 0 while they play
+#[derive(Debug, Default)]
+pub(crate) struct SlotManager {
+    slots: [Option<(EffectKind, u64)>; 4],
+    generation: u64,
+}
+
+impl SlotManager {
+    pub(crate) fn allocate(&mut self, kind: EffectKind) -> Option<(Slot, u64)> {
+        let index = self.slots.iter().position(Option::is_none)?;
+
+        self.generation += 1;
+        self.slots[index] = Some((kind, self.generation));
+
+        Some((Slot::ALL[index], self.generation))
+    }
+
+    // Returns `false` if the slot was released since the handle allocated it
+    pub(crate) fn owns(&self, slot: Slot, generation: u64) -> bool {
+        matches!(self.slots[slot.index()], Some((_, g)) if g == generation)
+    }
+
+    pub(crate) fn set_kind(&mut self, slot: Slot, kind: EffectKind) {
+        if let Some((current, _)) = self.slots[slot.index()].as_mut() {
+            *current = kind;
+        }
+    }
+
+    pub(crate) fn free(&mut self, slot: Slot, generation: u64) -> bool {
+        let owned = self.owns(slot, generation);
+
+        if owned {
+            self.slots[slot.index()] = None;
+        }
+
+        owned
+    }
+
+    // Hold a slot for an effect without a handle, returns `false` if a handle owns it
+    pub(crate) fn claim(&mut self, slot: Slot, kind: EffectKind) -> bool {
+        let free = matches!(self.slots[slot.index()], None | Some((_, 0)));
+
+        if free {
+            self.slots[slot.index()] = Some((kind, 0));
+        }
+
+        free
+    }
+
+    // Give up the slot of a stopped effect without a handle, returns `false` if a handle owns it
+    pub(crate) fn unclaim(&mut self, slot: Slot) -> bool {
+        let free = matches!(self.slots[slot.index()], None | Some((_, 0)));
+
+        if free {
+            self.slots[slot.index()] = None;
+        }
+
+        free
+    }
+
+    // Give up every slot in the mask held by an effect without a handle
+    pub(crate) fn unclaim_all(&mut self, slots: u8) {
+        for slot in Slot::ALL {
+            if slots & slot.mask() != 0 {
+                self.unclaim(slot);
+            }
+        }
+    }
+
+    pub(crate) fn free_all(&mut self) {
+        self.slots = [None; 4];
+    }
+
+    pub(crate) fn active(&self) -> Vec<(Slot, EffectKind)> {
+        Slot::ALL
+            .iter()
+            .zip(self.slots)
+            .filter_map(|(slot, effect)| effect.map(|(kind, _)| (*slot, kind)))
+            .collect()
+    }
+}
+
+///
+/// EffectHandle
+///
+/// An effect playing in a force slot, returned by `G29::play_effect`.
+///
+/// The slot stays reserved until the handle is dropped, which stops the effect and frees the slot.
+///
+/// # Example
+///
+/// ```rust
+/// use lib_g29::effects::Effect;
+///
+/// let friction = g29.play_effect(Effect::Friction { left: 80, right: 80 })?;
+/// let mut torque = g29.play_effect(Effect::Constant(0.0))?;
+///
+/// torque.update(Effect::Constant(0.4));
+///
+/// // stops the friction and frees its slot
+/// drop(friction);
+/// ```
+///
+pub struct EffectHandle {
+    g29: G29,
+    slot: Slot,
+    generation: u64,
+}
+
+impl fmt::Debug for EffectHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EffectHandle")
+            .field("slot", &self.slot)
+            .field("generation", &self.generation)
+            .finish()
+    }
+}
+
+impl EffectHandle {
+    pub(crate) fn new(g29: G29, slot: Slot, generation: u64) -> EffectHandle {
+        EffectHandle {
+            g29,
+            slot,
+            generation,
+        }
+    }
+
+    /// The slot the effect plays in.
+    pub fn slot(&self) -> Slot {
+        self.slot
+    }
+
+    pub(crate) fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Returns `false` once the slot was freed by `G29::release_all_effects`.
+    pub fn is_active(&self) -> bool {
+        self.g29.owns_slot(self.slot, self.generation)
+    }
+
+    /// Replace the effect playing in the slot.
+    /// Returns a `NotFound` error, without sending anything, if the slot was released.
+    pub fn update(&mut self, effect: Effect) -> io::Result<()> {
+        if !self
+            .g29
+            .set_slot_effect(self.slot, self.generation, effect.kind())
+        {
+            return Err(released());
+        }
+
+        self.g29
+            .send_command(self.g29.effect_command(effect, self.slot.mask()));
+        Ok(())
+    }
+
+    // Replace the effect without refreshing the watchdog, for the mixer
+    pub(crate) fn stream(&mut self, effect: Effect) -> io::Result<()> {
+        if !self
+            .g29
+            .set_slot_effect(self.slot, self.generation, effect.kind())
+        {
+            return Err(released());
+        }
+
+        self.g29
+            .send_streamed(self.g29.effect_command(effect, self.slot.mask()));
+        Ok(())
+    }
+
+    /// Stop the effect, keeping the slot for a later `update`.
+    /// Returns a `NotFound` error, without sending anything, if the slot was released.
+    pub fn stop(&mut self) -> io::Result<()> {
+        if !self.is_active() {
+            return Err(released());
+        }
+
+        self.g29.send_command(Command::StopForces {
+            slots: self.slot.mask(),
+        });
+        Ok(())
+    }
+}
+
+impl Drop for EffectHandle {
+    fn drop(&mut self) {
+        if self.g29.free_slot(self.slot, self.generation) && self.g29.connected() {
+            self.g29.send_command(Command::StopForces {
+                slots: self.slot.mask(),
+            });
+        }
+    }
+}
+
+fn released() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        "effects -> The effect's slot was released",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        command::Command,
+        effects::{AutoCenter, Condition, Effect, EffectKind, Slot, SlotManager},
+        simulator::Simulator,
+        Options,
+    };
+
+    #[test]
+    fn test_slots() {
+        for slot in Slot::ALL {
+            assert_eq!(Slot::from_number(slot.number()), Some(slot));
+        }
+        assert_eq!(Slot::from_number(0), None);
+        assert_eq!(Slot::from_number(5), None);
+        assert_eq!(Slot::Three.mask(), 0x04);
+    }
+
+    #[test]
+    fn test_effect_commands() {
+        assert_eq!(
+            Effect::Constant(-0.5).command(0x01),
+            Command::ConstantForce {
+                slots: 0x01,
+                level: 0x41
+            }
+        );
+        assert_eq!(
+            Effect::Constant(f32::NAN).command(0x01),
+            Command::StopForces { slots: 0x01 }
+        );
+        assert_eq!(
+            Effect::Friction { left: 0, right: 0 }.command(0x02),
+            Command::StopForces { slots: 0x02 }
+        );
+    }
+
+    #[test]
+    fn test_auto_center_springs() {
+        assert_eq!(
+            Condition::auto_center(0x0f, 0xff),
+            Condition {
+                saturation: 1.0,
+                ..Condition::new(1.0)
+            }
+        );
+
+        let spring = AutoCenter {
+            clockwise_angle: 0x40,
+            clockwise_force: 0x0f,
+            counter_clockwise_force: 0x03,
+            reverse: true,
+            centering_force: 0x33,
+            ..Default::default()
+        }
+        .spring();
+        assert_eq!(spring.left_coefficient, -0.2);
+        assert_eq!(spring.right_coefficient, -1.0);
+        assert_eq!(spring.saturation, 0.2);
+        assert_eq!(spring.deadband, 0.0);
+    }
+
+    #[test]
+    fn test_condition_commands() {
+        let spring = Condition {
+            left_coefficient: 1.0,
+            right_coefficient: -0.4,
+            saturation: 0.5,
+            center: 0.0,
+            deadband: 0.2,
+        };
+
+        // deadband edges 921 and 1126: 921 >> 3 = 115, 1126 >> 3 = 140
+        assert_eq!(
+            Effect::Spring(spring).command(0x04),
+            Command::DownloadAndPlay {
+                slots: 0x04,
+                params: [0x0b, 115, 140, 0x6f, 0xd2, 128],
+            }
+        );
+        assert_eq!(
+            Effect::Damper(spring).command(0x08),
+            Command::DownloadAndPlay {
+                slots: 0x08,
+                params: [0x0c, 15, 0, 6, 1, 128],
+            }
+        );
+        assert_eq!(
+            Effect::Spring(Condition::new(0.0)).command(0x04),
+            Command::StopForces { slots: 0x04 }
+        );
+        assert_eq!(
+            Command::decode(&Effect::Spring(spring).command(0x04).encode()),
+            Effect::Spring(spring).command(0x04)
+        );
+    }
+
+    #[test]
+    fn test_slot_manager() {
+        let mut slots = SlotManager::default();
+
+        let allocated: Vec<_> = (0..4)
+            .map(|_| slots.allocate(EffectKind::Constant).unwrap())
+            .collect();
+        assert!(slots.allocate(EffectKind::Friction).is_none());
+
+        assert!(slots.free(allocated[1].0, allocated[1].1));
+        assert!(!slots.free(allocated[1].0, allocated[1].1));
+
+        let (slot, generation) = slots.allocate(EffectKind::Friction).unwrap();
+        assert_eq!(slot, Slot::Two);
+        assert!(!slots.owns(slot, allocated[1].1));
+        assert!(slots.owns(slot, generation));
+
+        slots.free_all();
+        assert!(slots.active().is_empty());
+        assert!(!slots.free(slot, generation));
+
+        // effects without a handle hold their slot until they stop
+        assert!(slots.claim(Slot::One, EffectKind::Constant));
+        assert_eq!(slots.allocate(EffectKind::Friction).unwrap().0, Slot::Two);
+        assert!(!slots.claim(Slot::Two, EffectKind::Friction));
+        assert!(!slots.unclaim(Slot::Two));
+        assert!(slots.unclaim(Slot::One));
+        assert_eq!(slots.allocate(EffectKind::Spring).unwrap().0, Slot::One);
+    }
+
+    #[test]
+    fn test_constant_force() {
+        let sim = Simulator::new(Options::default());
+        let g29 = sim.g29();
+
+        g29.set_constant_force(1.0, 1).unwrap();
+        g29.set_constant_force(-2.0, 2).unwrap();
+        assert!(g29.set_constant_force(0.5, 5).is_err());
+        assert_eq!(
+            g29.written_commands().last(),
+            Some(&[0x21, 0x08, 0x01, 0x80, 0x00, 0x00, 0x00])
+        );
+
+        // the forces hold slots 1 and 2 until they stop, and cannot take a handle's slot
+        assert_eq!(
+            g29.active_effects(),
+            vec![
+                (Slot::One, EffectKind::Constant),
+                (Slot::Two, EffectKind::Constant)
+            ]
+        );
+        let handle = g29.play_effect(Effect::Constant(0.5)).unwrap();
+        assert_eq!(handle.slot(), Slot::Three);
+        assert!(g29.set_constant_force(0.5, 3).is_err());
+
+        // a force too small to send stops the slot and frees it
+        g29.set_constant_force(0.001, 2).unwrap();
+        assert_eq!(
+            g29.written_commands().last(),
+            Some(&[0x23, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00])
+        );
+        assert_eq!(
+            g29.active_effects(),
+            vec![
+                (Slot::One, EffectKind::Constant),
+                (Slot::Three, EffectKind::Constant)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_force_friction() {
+        let sim = Simulator::new(Options::default());
+        let g29 = sim.g29();
+        g29.clear_written_commands();
+
+        assert!(g29.force_friction(0x08, 0x00).is_err());
+        assert!(g29.force_friction(0x07, 0xff).is_err());
+        assert!(g29.written_commands().is_empty());
+
+        g29.force_friction(0x07, 0x01).unwrap();
+        assert_eq!(
+            g29.written_commands(),
+            vec![[0x21, 0x02, 0x31, 0x00, 0x07, 0x00, 0x00]]
+        );
+        assert_eq!(
+            g29.active_effects(),
+            vec![(Slot::Two, EffectKind::Friction)]
+        );
+
+        // stopping the friction frees the slot, which a handle then keeps from it
+        g29.force_friction(0, 0).unwrap();
+        assert!(g29.active_effects().is_empty());
+        let first = g29.play_effect(Effect::Constant(0.5)).unwrap();
+        let second = g29.play_effect(Effect::Constant(0.5)).unwrap();
+        assert_eq!(second.slot(), Slot::Two);
+        assert!(g29.force_friction(0x01, 0x01).is_err());
+        drop((first, second));
+    }
+
+    #[test]
+    fn test_effect_handles() {
+        let sim = Simulator::new(Options::default());
+        let g29 = sim.g29();
+        g29.clear_written_commands();
+
+        let mut constant = g29.play_effect(Effect::Constant(0.5)).unwrap();
+        let friction = g29
+            .play_effect(Effect::Friction {
+                left: 70,
+                right: 70,
+            })
+            .unwrap();
+        let third = g29.play_effect(Effect::Constant(0.0)).unwrap();
+        let fourth = g29.play_effect(Effect::Constant(0.0)).unwrap();
+        assert!(g29.play_effect(Effect::Constant(1.0)).is_err());
+
+        assert_eq!(constant.slot(), Slot::One);
+        assert_eq!(friction.slot(), Slot::Two);
+        assert_eq!(g29.active_effects()[1], (Slot::Two, EffectKind::Friction));
+
+        constant.update(Effect::Constant(-1.0)).unwrap();
+        drop(friction);
+        assert_eq!(g29.active_effects().len(), 3);
+        assert_eq!(
+            g29.play_effect(Effect::Constant(0.0)).unwrap().slot(),
+            Slot::Two
+        );
+
+        g29.release_all_effects();
+        assert!(!third.is_active());
+        assert!(constant.update(Effect::Constant(0.2)).is_err());
+        drop((constant, third, fourth));
+
+        assert_eq!(
+            g29.written_commands(),
+            vec![
+                [0x11, 0x08, 0xc0, 0x80, 0x00, 0x00, 0x00],
+                [0x21, 0x02, 0x46, 0x00, 0x46, 0x00, 0x00],
+                [0x43, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+                [0x83, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+                [0x11, 0x08, 0x01, 0x80, 0x00, 0x00, 0x00],
+                [0x23, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+                // the second handle in slot 2, dropped straight away
+                [0x23, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+                [0x23, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+                [0xf3, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_spring_and_damper() {
+        let sim = Simulator::new(Options::default());
+        sim.g29().clear_written_commands();
+
+        sim.g29().force_spring(Condition::new(1.0)).unwrap();
+        sim.g29().force_damper(Condition::new(0.5)).unwrap();
+        assert_eq!(
+            sim.g29().active_effects(),
+            vec![
+                (Slot::Three, EffectKind::Spring),
+                (Slot::Four, EffectKind::Damper)
+            ]
+        );
+
+        // a condition with no force stops the effect and frees its slot
+        sim.g29().force_damper(Condition::new(0.0)).unwrap();
+        assert_eq!(
+            sim.g29().active_effects(),
+            vec![(Slot::Three, EffectKind::Spring)]
+        );
+
+        assert_eq!(
+            sim.g29().written_commands(),
+            vec![
+                [0x41, 0x0b, 0x80, 0x80, 0xff, 0x00, 0xff],
+                [0x81, 0x0c, 0x08, 0x00, 0x08, 0x00, 0xff],
+                [0x83, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_auto_center_advanced() {
+        let mut sim = Simulator::new(Options::default());
+        sim.g29().clear_written_commands();
+
+        let auto_center = AutoCenter {
+            clockwise_force: 0x10,
+            ..Default::default()
+        };
+        assert!(sim.g29_mut().set_auto_center_advanced(auto_center).is_err());
+        assert!(sim.g29().written_commands().is_empty());
+
+        let auto_center = AutoCenter {
+            clockwise_angle: 0x20,
+            clockwise_force: 0x0f,
+            counter_clockwise_force: 0x03,
+            ..Default::default()
+        };
+        sim.g29_mut().set_auto_center_advanced(auto_center).unwrap();
+        assert_eq!(sim.g29().auto_center_advanced(), Some(auto_center));
+
+        // set up again, as after switching to native mode
+        sim.g29().set_auto_center();
+        assert_eq!(
+            sim.g29().written_commands(),
+            vec![
+                [0xf5, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+                [0xfc, 0x01, 0x20, 0x00, 0xf3, 0x00, 0xff],
+                [0xf5, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+                [0xfc, 0x01, 0x20, 0x00, 0xf3, 0x00, 0xff],
+            ]
+        );
+
+        sim.g29().clear_written_commands();
+        sim.g29_mut().set_auto_center_force(0x07, 0xff);
+        assert_eq!(sim.g29().auto_center_advanced(), None);
+        assert_eq!(
+            sim.g29().written_commands(),
+            vec![
+                [0x14, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+                [0xfe, 0x0d, 0x07, 0x07, 0xff, 0x00, 0x00],
+            ]
+        );
+
+        // disconnecting turns the spring off but keeps the setting
+        sim.g29_mut().set_auto_center_advanced(auto_center).unwrap();
+        sim.g29_mut().disconnect();
+        assert_eq!(sim.g29().auto_center_advanced(), Some(auto_center));
+
+        // and connecting again with the options applies it to the new wheel
+        let sim = Simulator::new(sim.g29().options());
+        assert_eq!(sim.g29().auto_center_advanced(), Some(auto_center));
+        assert!(sim
+            .g29()
+            .written_commands()
+            .contains(&[0xfc, 0x01, 0x20, 0x00, 0xf3, 0x00, 0xff]));
+    }
+}
@@ -0,0 +1,269 @@
+use crate::{layout::Layout, Button, DpadPosition, Frame, G29State, GearSelector, FRAME_SIZE};
+
+// A frame with nothing pressed, the pedals released and the wheel and shifter centered
+pub(crate) const IDLE_FRAME: Frame = [8, 0, 0, 0, 0, 128, 255, 255, 255, 128, 128, 0];
+
+///
+/// FrameBuilder
+///
+/// Builds the 12 byte frames the G29 sends, the inverse of the getters on `G29`.
+///
+/// Values are raw, as the wheel reports them: pedals are 255 when released and 0 when fully
+/// pressed, steering is 16-bit with 32768 at center.
+/// The G29 has no seventh or eighth gear, so they are encoded as neutral.
+///
+/// # Example
+///
+/// ```rust
+/// use lib_g29::{encoder::FrameBuilder, Button, GearSelector};
+///
+/// let frame = FrameBuilder::new()
+///     .button(Button::X, true)
+///     .throttle(0)
+///     .gear_selector(GearSelector::Second)
+///     .build();
+///
+/// // send to an emulated wheel that uses report id 1
+/// uhid.write(&FrameBuilder::from_frame(frame).input_report(Some(1)));
+/// ```
+///
+#[derive(Debug, PartialEq, Copy, Clone, Eq, Hash)]
+pub struct FrameBuilder {
+    frame: Frame,
+}
+
+impl Default for FrameBuilder {
+    fn default() -> Self {
+        FrameBuilder { frame: IDLE_FRAME }
+    }
+}
+
+impl FrameBuilder {
+    /// Start from a frame with every control at rest.
+    pub fn new() -> FrameBuilder {
+        FrameBuilder::default()
+    }
+
+    /// Start from an existing frame.
+    pub fn from_frame(frame: [u8; FRAME_SIZE]) -> FrameBuilder {
+        FrameBuilder { frame }
+    }
+
+    /// Start from a state. The sequence and timestamp are not part of a frame.
+    pub fn from_state(state: &G29State) -> FrameBuilder {
+        let mut builder = FrameBuilder::new();
+        Layout::G29.encode(state, &mut builder.frame);
+
+        builder
+    }
+
+    /// Press or release a button.
+    pub fn button(mut self, button: Button, pressed: bool) -> FrameBuilder {
+        Layout::G29.set_button(&mut self.frame, button, pressed);
+        self
+    }
+
+    /// Set the Dpad position.
+    pub fn dpad(mut self, position: DpadPosition) -> FrameBuilder {
+        Layout::G29.set_dpad(&mut self.frame, position);
+        self
+    }
+
+    /// Set the 16-bit steering position. 65535 is fully right, 0 is fully left.
+    pub fn steering_raw(mut self, raw: u16) -> FrameBuilder {
+        Layout::G29.set_value(&mut self.frame, "steering_raw", raw as u32);
+        self
+    }
+
+    /// Set the raw throttle value.
+    pub fn throttle(mut self, value: u8) -> FrameBuilder {
+        Layout::G29.set_value(&mut self.frame, "throttle", value as u32);
+        self
+    }
+
+    /// Set the raw brake value.
+    pub fn brake(mut self, value: u8) -> FrameBuilder {
+        Layout::G29.set_value(&mut self.frame, "brake", value as u32);
+        self
+    }
+
+    /// Set the raw clutch value.
+    pub fn clutch(mut self, value: u8) -> FrameBuilder {
+        Layout::G29.set_value(&mut self.frame, "clutch", value as u32);
+        self
+    }
+
+    /// Set the gear selector position.
+    pub fn gear_selector(mut self, gear: GearSelector) -> FrameBuilder {
+        Layout::G29.set_gear_selector(&mut self.frame, gear);
+        self
+    }
+
+    /// Set the raw shifter stick position and whether it is pushed down.
+    pub fn shifter(mut self, x: u8, y: u8, pressed: bool) -> FrameBuilder {
+        Layout::G29.set_value(&mut self.frame, "shifter_x", x as u32);
+        Layout::G29.set_value(&mut self.frame, "shifter_y", y as u32);
+        Layout::G29.set_value(&mut self.frame, "shifter_pressed", pressed as u32);
+        self
+    }
+
+    /// Get the frame.
+    pub fn build(&self) -> [u8; FRAME_SIZE] {
+        self.frame
+    }
+
+    /// Get the frame as a HID input report, for emulation backends such as uhid.
+    /// The G29 sends its reports without a report id; pass one if the emulated descriptor uses one.
+    pub fn input_report(&self, report_id: Option<u8>) -> Vec<u8> {
+        report_id.into_iter().chain(self.frame).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{encoder::FrameBuilder, Button, DpadPosition, G29State, GearSelector};
+
+    // xorshift, so the property tests cover the same values on every run
+    fn random_values(count: usize) -> Vec<u64> {
+        let mut seed = 0x9e37_79b9_7f4a_7c15u64;
+
+        (0..count)
+            .map(|_| {
+                seed ^= seed << 13;
+                seed ^= seed >> 7;
+                seed ^= seed << 17;
+                seed
+            })
+            .collect()
+    }
+
+    fn round_trip(state: &G29State) -> G29State {
+        let frame = FrameBuilder::from_state(state).build();
+        G29State::from_frame(&frame, state.sequence, state.timestamp)
+    }
+
+    fn idle_state() -> G29State {
+        G29State::from_frame(&FrameBuilder::new().build(), 0, Duration::ZERO)
+    }
+
+    #[test]
+    fn test_idle_frame() {
+        let state = idle_state();
+
+        assert_eq!(state.dpad, DpadPosition::None);
+        assert_eq!(state.gear_selector, GearSelector::Neutral);
+        assert_eq!(state.throttle, 255);
+        assert!(Button::ALL.iter().all(|button| !state.button(*button)));
+    }
+
+    #[test]
+    fn test_round_trip_buttons() {
+        // every combination of pairs of buttons
+        for first in Button::ALL {
+            for second in Button::ALL {
+                let frame = FrameBuilder::new()
+                    .button(first, true)
+                    .button(second, true)
+                    .build();
+                let state = G29State::from_frame(&frame, 0, Duration::ZERO);
+
+                for button in Button::ALL {
+                    assert_eq!(state.button(button), button == first || button == second);
+                }
+                assert_eq!(round_trip(&state), state);
+            }
+        }
+    }
+
+    #[test]
+    fn test_round_trip_dpad_and_gears() {
+        let positions = [
+            DpadPosition::Up,
+            DpadPosition::TopRight,
+            DpadPosition::Right,
+            DpadPosition::BottomRight,
+            DpadPosition::Down,
+            DpadPosition::BottomLeft,
+            DpadPosition::Left,
+            DpadPosition::TopLeft,
+            DpadPosition::None,
+        ];
+        let gears = [
+            GearSelector::Neutral,
+            GearSelector::First,
+            GearSelector::Second,
+            GearSelector::Third,
+            GearSelector::Fourth,
+            GearSelector::Fifth,
+            GearSelector::Sixth,
+            GearSelector::Reverse,
+        ];
+
+        for dpad in positions {
+            for gear in gears {
+                let mut state = idle_state();
+                state.dpad = dpad;
+                state.gear_selector = gear;
+                state.x_button = true;
+                state.plus_button = true;
+
+                assert_eq!(round_trip(&state), state);
+            }
+        }
+    }
+
+    #[test]
+    fn test_round_trip_random_states() {
+        for value in random_values(1000) {
+            let bytes = value.to_le_bytes();
+            let mut state = idle_state();
+
+            state.steering = bytes[0];
+            state.steering_fine = bytes[1];
+            state.throttle = bytes[2];
+            state.brake = bytes[3];
+            state.clutch = bytes[4];
+            state.shifter_x = bytes[5];
+            state.shifter_y = bytes[6];
+            state.shifter_pressed = bytes[7] & 1 == 1;
+
+            for (i, button) in Button::ALL.into_iter().enumerate() {
+                let pressed = (value >> (i + 40)) & 1 == 1;
+
+                match button {
+                    Button::X => state.x_button = pressed,
+                    Button::Square => state.square_button = pressed,
+                    Button::Circle => state.circle_button = pressed,
+                    Button::Triangle => state.triangle_button = pressed,
+                    Button::RightShifter => state.right_shifter = pressed,
+                    Button::LeftShifter => state.left_shifter = pressed,
+                    Button::R2 => state.r2_button = pressed,
+                    Button::L2 => state.l2_button = pressed,
+                    Button::Share => state.share_button = pressed,
+                    Button::Options => state.options_button = pressed,
+                    Button::R3 => state.r3_button = pressed,
+                    Button::L3 => state.l3_button = pressed,
+                    Button::Plus => state.plus_button = pressed,
+                    Button::Minus => state.minus_button = pressed,
+                    Button::SpinnerRight => state.spinner_right = pressed,
+                    Button::SpinnerLeft => state.spinner_left = pressed,
+                    Button::SpinnerButton => state.spinner_button = pressed,
+                    Button::PlayStation => state.playstation_button = pressed,
+                }
+            }
+
+            assert_eq!(round_trip(&state), state);
+        }
+    }
+
+    #[test]
+    fn test_input_report() {
+        let builder = FrameBuilder::new().button(Button::X, true);
+
+        assert_eq!(builder.input_report(None), builder.build().to_vec());
+        assert_eq!(builder.input_report(Some(1))[0], 1);
+        assert_eq!(builder.input_report(Some(1))[1..], builder.build());
+    }
+}
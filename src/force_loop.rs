@@ -0,0 +1,276 @@
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use crate::{
+    clock::{Clock, Sleeper},
+    mixer::Mixer,
+    G29State, G29,
+};
+
+// The fastest rate of a loop, the wheel reports its state once a millisecond at best
+const MAX_RATE: u32 = 1000;
+
+///
+/// ForceLoopStats
+///
+/// How well a force loop kept to its rate
+/// - ticks: `u64` - The number of times the loop function was called
+/// - missed_deadlines: `u64` - The number of ticks that started a whole interval or more late.
+///   Missed ticks are skipped rather than run back to back
+/// - max_jitter: `Duration` - The latest a tick started
+/// - mean_jitter: `Duration` - The average time ticks started late
+///
+#[derive(Debug, PartialEq, Copy, Clone, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ForceLoopStats {
+    pub ticks: u64,
+    pub missed_deadlines: u64,
+    pub max_jitter: Duration,
+    pub mean_jitter: Duration,
+}
+
+impl ForceLoopStats {
+    // Record a tick that started `late` after its deadline
+    fn record(&mut self, late: Duration, interval: Duration) {
+        let total = self.mean_jitter * self.ticks as u32 + late;
+
+        self.ticks += 1;
+        self.mean_jitter = total / self.ticks as u32;
+        self.max_jitter = self.max_jitter.max(late);
+
+        if late >= interval {
+            self.missed_deadlines += 1;
+        }
+    }
+}
+
+///
+/// ForceLoop
+///
+/// A thread calling a function at a fixed rate and streaming the torque it returns to the wheel
+/// as a constant force, returned by `G29::start_force_loop`.
+///
+/// The torque is a source of the mixer, see `G29::set_ffb_strength`.
+/// Dropping the handle stops the loop.
+///
+pub struct ForceLoop {
+    mixer: Arc<Mutex<Mixer>>,
+    id: u64,
+    clock: Clock,
+    running: Arc<AtomicBool>,
+    stats: Arc<Mutex<ForceLoopStats>>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl fmt::Debug for ForceLoop {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ForceLoop")
+            .field("running", &self.is_running())
+            .field("stats", &self.stats())
+            .finish()
+    }
+}
+
+impl ForceLoop {
+    pub(crate) fn start<F>(g29: &G29, rate: u32, function: F) -> std::io::Result<ForceLoop>
+    where
+        F: FnMut(&G29State, Duration) -> f32 + Send + 'static,
+    {
+        if rate == 0 || rate > MAX_RATE {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "start_force_loop -> Rate must be 1 to 1000",
+            ));
+        }
+
+        let mixer = g29.mixer();
+        let id = mixer.lock().unwrap().register(g29)?;
+        let running = Arc::new(AtomicBool::new(true));
+        let stats = Arc::new(Mutex::new(ForceLoopStats::default()));
+        let clock = g29.clock();
+
+        let thread = {
+            let g29 = g29.clone();
+            let running = running.clone();
+            let stats = stats.clone();
+            let interval = Duration::from_secs(1) / rate;
+            let sleeper = clock.register();
+
+            thread::spawn(move || run(g29, id, interval, running, stats, sleeper, function))
+        };
+
+        Ok(ForceLoop {
+            mixer,
+            id,
+            clock,
+            running,
+            stats,
+            thread: Some(thread),
+        })
+    }
+
+    /// Returns `false` once the loop was stopped or the wheel disconnected.
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::Acquire)
+    }
+
+    /// Set the gain of the torque in the mix, **0.0** and up (default: `1.0`).
+    /// Returns `false` if the loop has stopped.
+    pub fn set_gain(&self, gain: f32) -> bool {
+        self.mixer.lock().unwrap().set_source_gain(self.id, gain)
+    }
+
+    /// Get how well the loop has kept to its rate so far.
+    pub fn stats(&self) -> ForceLoopStats {
+        *self.stats.lock().unwrap()
+    }
+
+    /// Stop the loop and wait for its thread, returning the final stats.
+    pub fn stop(mut self) -> ForceLoopStats {
+        self.join();
+        self.stats()
+    }
+
+    fn join(&mut self) {
+        self.running.store(false, Ordering::Release);
+        self.clock.wake();
+
+        if let Some(thread) = self.thread.take() {
+            thread.join().unwrap();
+        }
+    }
+}
+
+impl Drop for ForceLoop {
+    fn drop(&mut self) {
+        self.join();
+    }
+}
+
+fn run<F>(
+    g29: G29,
+    id: u64,
+    interval: Duration,
+    running: Arc<AtomicBool>,
+    stats: Arc<Mutex<ForceLoopStats>>,
+    sleeper: Sleeper,
+    mut function: F,
+) where
+    F: FnMut(&G29State, Duration) -> f32,
+{
+    // read frames straight from the shared sample, so the loop does not wait on the getters
+    let (data, layout, mixer) = {
+        let inner = g29.inner.read().unwrap();
+        (
+            inner.data.clone(),
+            inner.layout.clone(),
+            inner.mixer.clone(),
+        )
+    };
+
+    let clock = g29.clock();
+    let started = clock.now();
+    let mut deadline = started;
+    let mut last: Option<G29State> = None;
+
+    while running.load(Ordering::Acquire) && g29.connected() {
+        let now = clock.now();
+        let late = now.saturating_duration_since(deadline);
+        stats.lock().unwrap().record(late, interval);
+
+        let sample = *data.read().unwrap();
+        let state = match last {
+            Some(state) if state.sequence == sample.sequence => state,
+            _ => layout.decode(&sample.frame, sample.sequence, sample.timestamp),
+        };
+        last = Some(state);
+
+        let torque = function(&state, now.duration_since(started));
+        {
+            let mut mixer = mixer.lock().unwrap();
+
+            // the slot was released
+            if !mixer.is_active() || !mixer.set_level(id, torque) {
+                break;
+            }
+            mixer.output();
+        }
+
+        // skip the ticks that were missed instead of running them back to back
+        deadline += interval;
+        let now = clock.now();
+        if now > deadline + interval {
+            let behind = (now - deadline).as_nanos() / interval.as_nanos();
+            deadline += interval * behind as u32;
+        }
+        sleeper.sleep_until(deadline, &running);
+    }
+
+    mixer.lock().unwrap().unregister(id);
+    running.store(false, Ordering::Release);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{axis::Axis, force_loop::ForceLoopStats, simulator::Simulator, Options};
+
+    #[test]
+    fn test_stats() {
+        let ms = Duration::from_millis;
+        let mut stats = ForceLoopStats::default();
+
+        stats.record(ms(0), ms(2));
+        stats.record(ms(1), ms(2));
+        stats.record(ms(5), ms(2));
+
+        assert_eq!(
+            stats,
+            ForceLoopStats {
+                ticks: 3,
+                missed_deadlines: 1,
+                max_jitter: ms(5),
+                mean_jitter: ms(2),
+            }
+        );
+    }
+
+    #[test]
+    fn test_start_force_loop() {
+        let mut sim = Simulator::new(Options::default());
+        sim.set_axis(Axis::Throttle, 1.0);
+        sim.g29().clear_written_commands();
+
+        let force_loop = sim
+            .g29()
+            .start_force_loop(500, |state, _| if state.throttle == 0 { 0.5 } else { 0.0 })
+            .unwrap();
+        assert!(sim.g29().start_force_loop(0, |_, _| 0.0).is_err());
+        assert!(sim.g29().start_force_loop(1001, |_, _| 0.0).is_err());
+        assert!(sim.g29().start_force_loop(u32::MAX, |_, _| 0.0).is_err());
+
+        // a tick every 2 ms, on time
+        sim.wait(Duration::from_millis(51));
+        assert!(force_loop.is_running());
+
+        let stats = force_loop.stop();
+        assert_eq!(stats.ticks, 26);
+        assert_eq!(stats.max_jitter, Duration::ZERO);
+
+        let written = sim.g29().written_commands();
+        assert!(written.contains(&[0x11, 0x08, 0xc0, 0x80, 0x00, 0x00, 0x00]));
+        assert_eq!(
+            written.last(),
+            Some(&[0x13, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00])
+        );
+        assert!(sim.g29().active_effects().is_empty());
+    }
+}
@@ -0,0 +1,269 @@
+use std::io;
+
+///
+/// SequentialGear
+///
+/// A gear of the virtual sequential gearbox
+///
+#[derive(Debug, PartialEq, Copy, Clone, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SequentialGear {
+    Reverse,
+    Neutral,
+    /// A forward gear, starting at 1
+    Forward(u8),
+}
+
+///
+/// ShiftOutcome
+///
+/// The result of pulling a paddle
+///
+#[derive(Debug, PartialEq, Copy, Clone, Eq, Hash)]
+pub enum ShiftOutcome {
+    /// The gear changed
+    Changed(SequentialGear),
+    /// There is no gear in that direction
+    Missed,
+    /// The clutch is required but was not pressed, the gear did not change
+    WithoutClutch,
+}
+
+///
+/// GearboxSettings
+///
+/// The rules of the virtual sequential gearbox
+/// - gears: `u8` - The number of forward gears, at least 1 (default: `6`)
+/// - neutral: `bool` - Neutral sits between first and reverse (default: `true`)
+/// - reverse: `bool` - The gearbox has a reverse gear (default: `true`)
+/// - clutch_required: `bool` - Shifts only happen while the clutch is pressed (default: `false`)
+/// - clutch_threshold: `f32` - How far the clutch must be pressed to count, 0.0 - 1.0 (default: `0.5`)
+///
+/// Shifting down from first goes to neutral, then reverse. Shifting up from reverse goes to neutral, then first.
+///
+#[derive(Debug, PartialEq, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GearboxSettings {
+    pub gears: u8,
+    pub neutral: bool,
+    pub reverse: bool,
+    pub clutch_required: bool,
+    pub clutch_threshold: f32,
+}
+
+impl Default for GearboxSettings {
+    fn default() -> Self {
+        GearboxSettings {
+            gears: 6,
+            neutral: true,
+            reverse: true,
+            clutch_required: false,
+            clutch_threshold: 0.5,
+        }
+    }
+}
+
+impl GearboxSettings {
+    /// Check the gearbox has a forward gear.
+    pub fn validate(&self) -> io::Result<()> {
+        if self.gears == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "gearbox -> The gearbox needs at least one forward gear",
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+///
+/// SequentialGearbox
+///
+/// A virtual sequential gearbox. The right paddle shifts up and the left paddle shifts down.
+///
+/// # Example
+///
+/// ```rust
+/// use lib_g29::gearbox::{GearboxSettings, SequentialGearbox, ShiftOutcome};
+///
+/// let mut gearbox = SequentialGearbox::new(GearboxSettings::default())?;
+///
+/// if let ShiftOutcome::Changed(gear) = gearbox.shift_up(g29.axis(Axis::Clutch)) {
+///     println!("Gear: {:?}", gear);
+/// }
+/// ```
+///
+#[derive(Debug, PartialEq, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SequentialGearbox {
+    settings: GearboxSettings,
+    gear: SequentialGear,
+}
+
+impl SequentialGearbox {
+    /// Create a gearbox in neutral, or first if it has no neutral.
+    /// Returns an `InvalidInput` error if the settings do not `validate`.
+    pub fn new(settings: GearboxSettings) -> io::Result<SequentialGearbox> {
+        settings.validate()?;
+
+        Ok(SequentialGearbox {
+            settings,
+            gear: if settings.neutral {
+                SequentialGear::Neutral
+            } else {
+                SequentialGear::Forward(1)
+            },
+        })
+    }
+
+    /// Get the settings of the gearbox.
+    pub fn settings(&self) -> GearboxSettings {
+        self.settings
+    }
+
+    /// Get the current gear.
+    pub fn gear(&self) -> SequentialGear {
+        self.gear
+    }
+
+    /// Shift up one gear.
+    /// # Arguments
+    /// - `clutch` - How far the clutch is pressed (**0.0** to **1.0**)
+    pub fn shift_up(&mut self, clutch: f32) -> ShiftOutcome {
+        let next = match self.gear {
+            SequentialGear::Reverse if self.settings.neutral => Some(SequentialGear::Neutral),
+            SequentialGear::Reverse | SequentialGear::Neutral => Some(SequentialGear::Forward(1)),
+            SequentialGear::Forward(gear) if gear < self.settings.gears => {
+                Some(SequentialGear::Forward(gear + 1))
+            }
+            SequentialGear::Forward(_) => None,
+        };
+
+        self.shift(next, clutch)
+    }
+
+    /// Shift down one gear.
+    /// # Arguments
+    /// - `clutch` - How far the clutch is pressed (**0.0** to **1.0**)
+    pub fn shift_down(&mut self, clutch: f32) -> ShiftOutcome {
+        let next = match self.gear {
+            SequentialGear::Forward(gear) if gear > 1 => Some(SequentialGear::Forward(gear - 1)),
+            SequentialGear::Forward(_) if self.settings.neutral => Some(SequentialGear::Neutral),
+            SequentialGear::Forward(_) | SequentialGear::Neutral if self.settings.reverse => {
+                Some(SequentialGear::Reverse)
+            }
+            _ => None,
+        };
+
+        self.shift(next, clutch)
+    }
+
+    fn shift(&mut self, next: Option<SequentialGear>, clutch: f32) -> ShiftOutcome {
+        let Some(next) = next else {
+            return ShiftOutcome::Missed;
+        };
+
+        if self.settings.clutch_required && clutch < self.settings.clutch_threshold {
+            return ShiftOutcome::WithoutClutch;
+        }
+
+        self.gear = next;
+
+        ShiftOutcome::Changed(next)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::gearbox::{GearboxSettings, SequentialGear, SequentialGearbox, ShiftOutcome};
+
+    #[test]
+    fn test_shift_through_gears() {
+        let mut gearbox = SequentialGearbox::new(GearboxSettings {
+            gears: 2,
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(gearbox.gear(), SequentialGear::Neutral);
+
+        assert_eq!(
+            gearbox.shift_up(0.0),
+            ShiftOutcome::Changed(SequentialGear::Forward(1))
+        );
+        assert_eq!(
+            gearbox.shift_up(0.0),
+            ShiftOutcome::Changed(SequentialGear::Forward(2))
+        );
+        assert_eq!(gearbox.shift_up(0.0), ShiftOutcome::Missed);
+
+        gearbox.shift_down(0.0);
+        assert_eq!(
+            gearbox.shift_down(0.0),
+            ShiftOutcome::Changed(SequentialGear::Neutral)
+        );
+        assert_eq!(
+            gearbox.shift_down(0.0),
+            ShiftOutcome::Changed(SequentialGear::Reverse)
+        );
+        assert_eq!(gearbox.shift_down(0.0), ShiftOutcome::Missed);
+        assert_eq!(
+            gearbox.shift_up(0.0),
+            ShiftOutcome::Changed(SequentialGear::Neutral)
+        );
+    }
+
+    #[test]
+    fn test_no_neutral_or_reverse() {
+        let mut gearbox = SequentialGearbox::new(GearboxSettings {
+            neutral: false,
+            reverse: false,
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(gearbox.gear(), SequentialGear::Forward(1));
+        assert_eq!(gearbox.shift_down(0.0), ShiftOutcome::Missed);
+
+        let mut gearbox = SequentialGearbox::new(GearboxSettings {
+            neutral: false,
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(
+            gearbox.shift_down(0.0),
+            ShiftOutcome::Changed(SequentialGear::Reverse)
+        );
+        assert_eq!(
+            gearbox.shift_up(0.0),
+            ShiftOutcome::Changed(SequentialGear::Forward(1))
+        );
+    }
+
+    #[test]
+    fn test_clutch_required() {
+        let mut gearbox = SequentialGearbox::new(GearboxSettings {
+            clutch_required: true,
+            ..Default::default()
+        })
+        .unwrap();
+
+        assert_eq!(gearbox.shift_up(0.2), ShiftOutcome::WithoutClutch);
+        assert_eq!(gearbox.gear(), SequentialGear::Neutral);
+
+        assert_eq!(
+            gearbox.shift_up(0.9),
+            ShiftOutcome::Changed(SequentialGear::Forward(1))
+        );
+    }
+
+    #[test]
+    fn test_no_gears() {
+        let settings = GearboxSettings {
+            gears: 0,
+            ..Default::default()
+        };
+
+        assert!(settings.validate().is_err());
+        assert!(SequentialGearbox::new(settings).is_err());
+    }
+}
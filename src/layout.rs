@@ -0,0 +1,628 @@
+use std::{borrow::Cow, io, time::Duration};
+
+use crate::{
+    axis::Axis,
+    descriptor::{
+        parse_report_descriptor, ReportField, BUTTON, GENERIC_DESKTOP, HAT_SWITCH, RZ, VENDOR, X,
+        Y, Z,
+    },
+    events::Event,
+    state, Button, DpadPosition, G29State, GearSelector, FRAME_SIZE,
+};
+
+pub use crate::state::Field;
+
+///
+/// ControlKind
+///
+/// How the value of a control is read and which events it fires
+///
+#[derive(Debug, PartialEq, Copy, Clone, Eq, Hash)]
+pub enum ControlKind {
+    /// On while the value is 1. Fires the first event when turned on and the second when turned off
+    Button,
+    /// A hat switch, 0 - 7 clockwise from up, anything else is centered.
+    /// Fires the pressed and released events of each position
+    Dpad,
+    /// One bit per gear, first to sixth then reverse. Fires the first event when it changes
+    GearSelector,
+    /// An analog value. Fires the first event when it changes, and with an `axis` only when its
+    /// value after calibration, inversion and curve changes
+    Axis,
+}
+
+///
+/// Control
+///
+/// One row of a `Layout`
+/// - name: `&'static str` - The name of the control, the same as its getter and `G29State` field
+/// - kind: `ControlKind` - How the value is read
+/// - byte: `usize` - The first byte of the value in a frame
+/// - mask: `u16` - The bits of the value, in `byte` and the byte after it, little-endian
+/// - bits: `u32` - The size the value is scaled to when decoded, 16 for `steering_raw` and 8 for other axes
+/// - events: `(Option<Event>, Option<Event>)` - The events fired when the value changes, see `ControlKind`
+/// - usage: `Option<Usage>` - Where the control is in a report descriptor, `None` if it is not looked up
+/// - axis: `Option<Axis>` - The axis whose settings filter the events of an analog control
+/// - field: `Option<Field>` - The `G29State` field the value is decoded into, found once from the
+///   name with `Field::from_name`. A control without one is not decoded and fires no events
+///
+#[derive(Debug, PartialEq, Copy, Clone, Eq, Hash)]
+pub struct Control {
+    pub name: &'static str,
+    pub kind: ControlKind,
+    pub byte: usize,
+    pub mask: u16,
+    pub bits: u32,
+    pub events: (Option<Event>, Option<Event>),
+    pub usage: Option<Usage>,
+    pub axis: Option<Axis>,
+    pub field: Option<Field>,
+}
+
+///
+/// Usage
+///
+/// Where a control is in a HID report descriptor, see `Layout::from_descriptor`
+/// - page: `u16` - The usage page, such as `0x01` generic desktop or `0x09` buttons
+/// - id: `u16` - The usage within the page
+/// - index: `usize` - Which of the fields with this usage, in report order (default: `0`)
+/// - span: `usize` - The number of consecutive one-bit usages the control covers, such as the
+///   seven gears (default: `1`)
+/// - part: `UsagePart` - The bits of the field the control reads (default: `UsagePart::All`)
+///
+#[derive(Debug, PartialEq, Copy, Clone, Eq, Hash)]
+pub struct Usage {
+    pub page: u16,
+    pub id: u16,
+    pub index: usize,
+    pub span: usize,
+    pub part: UsagePart,
+}
+
+///
+/// UsagePart
+///
+/// The bits of a report field a control reads
+///
+#[derive(Debug, PartialEq, Copy, Clone, Eq, Hash)]
+pub enum UsagePart {
+    /// The whole field
+    All,
+    /// The most significant bits, or the whole field if it is no larger
+    High(usize),
+    /// The least significant bits, or the whole field if it is no larger
+    Low(usize),
+}
+
+impl Usage {
+    /// The first field with a usage, read whole.
+    pub const fn new(page: u16, id: u16) -> Usage {
+        Usage {
+            page,
+            id,
+            index: 0,
+            span: 1,
+            part: UsagePart::All,
+        }
+    }
+
+    /// Use the field at `index` among the fields with this usage.
+    pub const fn index(self, index: usize) -> Usage {
+        Usage { index, ..self }
+    }
+
+    /// Cover `span` consecutive one-bit usages, starting at this one.
+    pub const fn span(self, span: usize) -> Usage {
+        Usage { span, ..self }
+    }
+
+    /// Read only part of the field.
+    pub const fn part(self, part: UsagePart) -> Usage {
+        Usage { part, ..self }
+    }
+}
+
+impl Control {
+    const fn new(
+        name: &'static str,
+        kind: ControlKind,
+        byte: usize,
+        mask: u16,
+        bits: u32,
+        events: (Option<Event>, Option<Event>),
+    ) -> Control {
+        Control {
+            name,
+            kind,
+            byte,
+            mask,
+            bits,
+            events,
+            usage: None,
+            axis: None,
+            field: Field::from_name(name),
+        }
+    }
+
+    const fn usage(self, usage: Usage) -> Control {
+        Control {
+            usage: Some(usage),
+            ..self
+        }
+    }
+
+    const fn button(name: &'static str, byte: usize, mask: u16, on: Event, off: Event) -> Control {
+        Control::new(
+            name,
+            ControlKind::Button,
+            byte,
+            mask,
+            1,
+            (Some(on), Some(off)),
+        )
+    }
+
+    const fn axis(
+        name: &'static str,
+        axis: Axis,
+        byte: usize,
+        mask: u16,
+        bits: u32,
+        event: Event,
+    ) -> Control {
+        Control {
+            axis: Some(axis),
+            ..Control::new(
+                name,
+                ControlKind::Axis,
+                byte,
+                mask,
+                bits,
+                (Some(event), None),
+            )
+        }
+    }
+
+    /// The bytes of a frame the control is in.
+    pub fn bytes(&self) -> impl Iterator<Item = usize> {
+        let last = if self.mask > 0xff { 1 } else { 0 };
+        self.byte..=self.byte + last
+    }
+
+    /// Read the value of the control from a frame, scaled to `bits`.
+    pub fn read(&self, data: &[u8]) -> u32 {
+        let low = data.get(self.byte).copied().unwrap_or(0);
+        let high = data.get(self.byte + 1).copied().unwrap_or(0);
+        let raw = (u16::from_le_bytes([low, high]) & self.mask) >> self.mask.trailing_zeros();
+
+        match self.kind {
+            ControlKind::Axis => scale(raw as u32, self.mask.count_ones(), self.bits),
+            _ => raw as u32,
+        }
+    }
+
+    /// Write a value of `bits` into a frame, keeping the other controls in the same bytes.
+    pub fn write(&self, data: &mut [u8], value: u32) {
+        let value = match self.kind {
+            ControlKind::Axis => scale(value, self.bits, self.mask.count_ones()),
+            _ => value,
+        };
+        let shifted = ((value << self.mask.trailing_zeros()) as u16) & self.mask;
+
+        for (byte, (mask, value)) in self.bytes().zip(
+            self.mask
+                .to_le_bytes()
+                .into_iter()
+                .zip(shifted.to_le_bytes()),
+        ) {
+            if let Some(byte) = data.get_mut(byte) {
+                *byte = (*byte & !mask) | value;
+            }
+        }
+    }
+}
+
+// scale a value from one size in bits to another, keeping the most significant bits
+fn scale(value: u32, from: u32, to: u32) -> u32 {
+    if from >= to {
+        value >> (from - to)
+    } else {
+        value << (to - from)
+    }
+}
+
+// The G29 (046d:c24f) in PS3 mode. Buttons 13 - 19 are the gears
+#[rustfmt::skip]
+const G29_CONTROLS: [Control; 29] = [
+    Control::new("dpad", ControlKind::Dpad, 0, 0x0f, 4, (None, None))
+        .usage(Usage::new(GENERIC_DESKTOP, HAT_SWITCH)),
+    Control::button("x_button", 0, 0x10, Event::XButtonPressed, Event::XButtonReleased)
+        .usage(Usage::new(BUTTON, 1)),
+    Control::button("square_button", 0, 0x20, Event::SquareButtonPressed, Event::SquareButtonReleased)
+        .usage(Usage::new(BUTTON, 2)),
+    Control::button("circle_button", 0, 0x40, Event::CircleButtonPressed, Event::CircleButtonReleased)
+        .usage(Usage::new(BUTTON, 3)),
+    Control::button("triangle_button", 0, 0x80, Event::TriangleButtonPressed, Event::TriangleButtonReleased)
+        .usage(Usage::new(BUTTON, 4)),
+    Control::button("right_shifter", 1, 0x01, Event::RightShifterPressed, Event::RightShifterReleased)
+        .usage(Usage::new(BUTTON, 5)),
+    Control::button("left_shifter", 1, 0x02, Event::LeftShifterPressed, Event::LeftShifterReleased)
+        .usage(Usage::new(BUTTON, 6)),
+    Control::button("r2_button", 1, 0x04, Event::R2ButtonPressed, Event::R2ButtonReleased)
+        .usage(Usage::new(BUTTON, 7)),
+    Control::button("l2_button", 1, 0x08, Event::L2ButtonPressed, Event::L2ButtonReleased)
+        .usage(Usage::new(BUTTON, 8)),
+    Control::button("share_button", 1, 0x10, Event::ShareButtonPressed, Event::ShareButtonReleased)
+        .usage(Usage::new(BUTTON, 9)),
+    Control::button("options_button", 1, 0x20, Event::OptionsButtonPressed, Event::OptionsButtonReleased)
+        .usage(Usage::new(BUTTON, 10)),
+    Control::button("r3_button", 1, 0x40, Event::R3ButtonPressed, Event::R3ButtonReleased)
+        .usage(Usage::new(BUTTON, 11)),
+    Control::button("l3_button", 1, 0x80, Event::L3ButtonPressed, Event::L3ButtonReleased)
+        .usage(Usage::new(BUTTON, 12)),
+    Control::new("gear_selector", ControlKind::GearSelector, 2, 0x7f, 7, (Some(Event::GearChanged), None))
+        .usage(Usage::new(BUTTON, 13).span(7)),
+    Control::button("plus_button", 2, 0x80, Event::PlusButtonPressed, Event::PlusButtonReleased)
+        .usage(Usage::new(BUTTON, 20)),
+    Control::button("minus_button", 3, 0x01, Event::MinusButtonPressed, Event::MinusButtonReleased)
+        .usage(Usage::new(BUTTON, 21)),
+    // the spinner only fires when it clicks
+    Control::new("spinner_right", ControlKind::Button, 3, 0x02, 1, (Some(Event::SpinnerRight), None))
+        .usage(Usage::new(BUTTON, 22)),
+    Control::new("spinner_left", ControlKind::Button, 3, 0x04, 1, (Some(Event::SpinnerLeft), None))
+        .usage(Usage::new(BUTTON, 23)),
+    Control::button("spinner_button", 3, 0x08, Event::SpinnerButtonPressed, Event::SpinnerButtonReleased)
+        .usage(Usage::new(BUTTON, 24)),
+    Control::button("playstation_button", 3, 0x10, Event::PlaystationButtonPressed, Event::PlaystationButtonReleased)
+        .usage(Usage::new(BUTTON, 25)),
+    Control::axis("steering", Axis::Steering, 5, 0xff, 8, Event::Steering)
+        .usage(Usage::new(GENERIC_DESKTOP, X).part(UsagePart::High(8))),
+    Control::axis("steering_fine", Axis::Steering, 4, 0xff, 8, Event::SteeringFine)
+        .usage(Usage::new(GENERIC_DESKTOP, X).part(UsagePart::Low(8))),
+    Control::axis("steering_raw", Axis::Steering, 4, 0xffff, 16, Event::SteeringRaw)
+        .usage(Usage::new(GENERIC_DESKTOP, X)),
+    Control::axis("throttle", Axis::Throttle, 6, 0xff, 8, Event::Throttle)
+        .usage(Usage::new(GENERIC_DESKTOP, Y)),
+    Control::axis("brake", Axis::Brake, 7, 0xff, 8, Event::Brake)
+        .usage(Usage::new(GENERIC_DESKTOP, Z)),
+    Control::axis("clutch", Axis::Clutch, 8, 0xff, 8, Event::Clutch)
+        .usage(Usage::new(GENERIC_DESKTOP, RZ)),
+    Control::axis("shifter_x", Axis::ShifterX, 9, 0xff, 8, Event::ShifterX)
+        .usage(Usage::new(VENDOR, SHIFTER)),
+    Control::axis("shifter_y", Axis::ShifterY, 10, 0xff, 8, Event::ShifterY)
+        .usage(Usage::new(VENDOR, SHIFTER).index(1)),
+    // the whole byte is 1 when the stick is pushed down
+    Control::button("shifter_pressed", 11, 0xff, Event::ShifterPressed, Event::ShifterReleased)
+        .usage(Usage::new(VENDOR, SHIFTER).index(2)),
+];
+
+// the vendor usage of the shifter x, y and pressed bytes. Not confirmed against a descriptor
+// captured from a wheel, see `Layout::from_descriptor`
+const SHIFTER: u16 = 0x04;
+
+///
+/// Layout
+///
+/// The table of every control in a frame: its kind, where it is and the events it fires.
+///
+/// Snapshots, getters, events and the frame encoder are all driven by the layout, so a control
+/// is moved by editing its row. A new control also needs its field in `G29State` and `Field`.
+///
+/// `Layout::G29` is the G29 in PS3 mode. Other models can use their own table with `Layout::new`,
+/// or have the G29 controls found in their report descriptor with `Layout::from_descriptor`.
+///
+/// # Example
+///
+/// ```rust
+/// use lib_g29::layout::Layout;
+///
+/// for control in Layout::G29.controls() {
+///     println!("{} is in byte {} ({:#06x})", control.name, control.byte, control.mask);
+/// }
+///
+/// let state = Layout::G29.decode(&frame, 0, Duration::ZERO);
+/// ```
+///
+#[derive(Debug, PartialEq, Clone, Eq, Hash)]
+pub struct Layout {
+    controls: Cow<'static, [Control]>,
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Layout::G29
+    }
+}
+
+impl Layout {
+    /// The layout of the G29 in PS3 mode, matching the accessors in `state.rs`.
+    pub const G29: Layout = Layout {
+        controls: Cow::Borrowed(&G29_CONTROLS),
+    };
+
+    /// Create a layout from a table of controls.
+    /// Controls are decoded in order, so a later control overrides an earlier one with the same name.
+    pub fn new(controls: Vec<Control>) -> Layout {
+        Layout {
+            controls: Cow::Owned(controls),
+        }
+    }
+
+    /// Find the controls of `Layout::G29` in a HID report descriptor, using the `usage` of each row.
+    ///
+    /// The hat switch is the Dpad, buttons 1 - 12 are the face, paddle and shoulder buttons,
+    /// 13 - 19 are the gears, 20 - 25 are the remaining buttons, X is steering, Y, Z and Rz are the
+    /// pedals and the three vendor `0x04` fields are the shifter. The shifter usage has not been
+    /// checked against a descriptor captured from a wheel, so without it the shifter keeps its
+    /// place in the G29 frame.
+    /// Returns an `InvalidData` error if any other control is missing or does not fit in a frame.
+    pub fn from_descriptor(descriptor: &[u8]) -> io::Result<Layout> {
+        let fields = parse_report_descriptor(descriptor)?;
+
+        // frames from devices with report ids start with the id
+        let report_id = fields.first().map(|field| field.report_id).unwrap_or(0);
+        let id_bits = if report_id == 0 { 0 } else { 8 };
+
+        let fields: Vec<ReportField> = fields
+            .into_iter()
+            .filter(|field| field.report_id == report_id)
+            .collect();
+
+        let find = |page: u16, usage: u16, nth: usize| {
+            fields
+                .iter()
+                .filter(|field| field.usage_page == page && field.usage == usage)
+                .nth(nth)
+                .map(|field| (field.bit_offset + id_bits, field.bit_size))
+        };
+
+        let mut controls = Vec::new();
+
+        for control in G29_CONTROLS {
+            let Some(usage) = control.usage else {
+                continue;
+            };
+
+            let missing = || invalid(&format!("Missing field: {}", control.name));
+            let Some((offset, size)) = find(usage.page, usage.id, usage.index) else {
+                // the vendor usage of the shifter is not confirmed by a captured descriptor, so
+                // the shifter keeps its place in the G29 frame when the usage is not found
+                if usage.page == VENDOR {
+                    controls.push(control);
+                    continue;
+                }
+                return Err(missing());
+            };
+
+            let (offset, size) = match usage.part {
+                // the usages of a span must be consecutive bits
+                _ if usage.span > 1 => {
+                    let last_id = usage.id + usage.span as u16 - 1;
+                    let (last, _) = find(usage.page, last_id, usage.index).ok_or_else(missing)?;
+                    if last != offset + usage.span - 1 {
+                        return Err(invalid(&format!(
+                            "Usages are not consecutive: {}",
+                            control.name
+                        )));
+                    }
+                    (offset, usage.span)
+                }
+                UsagePart::High(bits) if size > bits => (offset + size - bits, bits),
+                UsagePart::Low(bits) => (offset, size.min(bits)),
+                _ => (offset, size),
+            };
+
+            // masks cover up to two bytes
+            if size == 0 || offset % 8 + size > 16 || (offset + size).div_ceil(8) > FRAME_SIZE {
+                return Err(invalid(&format!("Field does not fit: {}", control.name)));
+            }
+
+            controls.push(Control {
+                byte: offset / 8,
+                mask: (((1u32 << size) - 1) << (offset % 8)) as u16,
+                ..control
+            });
+        }
+
+        Ok(Layout::new(controls))
+    }
+
+    /// Every control in the layout.
+    pub fn controls(&self) -> &[Control] {
+        &self.controls
+    }
+
+    /// Find a control by name.
+    pub fn control(&self, name: &str) -> Option<&Control> {
+        self.controls.iter().find(|control| control.name == name)
+    }
+
+    /// Decode every control in a frame.
+    pub fn decode(&self, data: &[u8], sequence: u64, timestamp: Duration) -> G29State {
+        // controls missing from the layout stay at rest
+        let mut state = G29State::from_frame(&crate::encoder::IDLE_FRAME, sequence, timestamp);
+
+        for control in self.controls.iter() {
+            if let Some(field) = control.field {
+                field.set(&mut state, control.read(data));
+            }
+        }
+
+        state
+    }
+
+    /// Encode every control of a state into a frame.
+    pub fn encode(&self, state: &G29State, data: &mut [u8]) {
+        for control in self.controls.iter() {
+            if let Some(field) = control.field {
+                control.write(data, field.get(state));
+            }
+        }
+    }
+
+    /// Set the value of a control in a frame. Returns `false` if the layout has no such control.
+    pub fn set_value(&self, data: &mut [u8], name: &str, value: u32) -> bool {
+        self.control(name)
+            .map(|control| control.write(data, value))
+            .is_some()
+    }
+
+    /// Press or release a button in a frame.
+    pub fn set_button(&self, data: &mut [u8], button: Button, pressed: bool) {
+        self.set_value(data, button.name(), pressed as u32);
+    }
+
+    /// Set the Dpad position in a frame.
+    pub fn set_dpad(&self, data: &mut [u8], position: DpadPosition) {
+        self.set_value(data, "dpad", state::dpad_value(position));
+    }
+
+    /// Set the gear selector position in a frame.
+    /// The G29 has no seventh or eighth gear, so they are encoded as neutral.
+    pub fn set_gear_selector(&self, data: &mut [u8], gear: GearSelector) {
+        self.set_value(data, "gear_selector", state::gear_value(gear));
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("layout -> {}", message))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{
+        descriptor::G29_DESCRIPTOR,
+        layout::{Control, ControlKind, Field, Layout},
+        Button, G29State,
+    };
+
+    // xorshift, so the same frames are checked on every run
+    fn random_frames(count: usize) -> Vec<[u8; 12]> {
+        let mut seed = 0x2545_f491_4f6c_dd1du64;
+
+        (0..count)
+            .map(|_| {
+                let mut frame = [0u8; 12];
+                for chunk in frame.chunks_mut(4) {
+                    seed ^= seed << 13;
+                    seed ^= seed >> 7;
+                    seed ^= seed << 17;
+                    chunk.copy_from_slice(&seed.to_le_bytes()[..4]);
+                }
+                frame
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_g29_matches_accessors() {
+        for frame in random_frames(1000) {
+            assert_eq!(
+                Layout::G29.decode(&frame, 1, Duration::ZERO),
+                G29State::from_frame(&frame, 1, Duration::ZERO)
+            );
+        }
+    }
+
+    #[test]
+    fn test_descriptor_matches_g29() {
+        assert_eq!(
+            Layout::from_descriptor(G29_DESCRIPTOR).unwrap(),
+            Layout::G29
+        );
+
+        // another vendor usage for the shifter leaves it in place
+        let mut descriptor = G29_DESCRIPTOR.to_vec();
+        let shifter = descriptor
+            .windows(5)
+            .position(|item| item == [0x06, 0x00, 0xff, 0x09, 0x04])
+            .unwrap();
+        descriptor[shifter + 4] = 0x05;
+        assert_eq!(Layout::from_descriptor(&descriptor).unwrap(), Layout::G29);
+    }
+
+    #[test]
+    fn test_encode_round_trip() {
+        for frame in random_frames(100) {
+            let state = Layout::G29.decode(&frame, 0, Duration::ZERO);
+            let mut encoded = [0u8; 12];
+            Layout::G29.encode(&state, &mut encoded);
+
+            assert_eq!(Layout::G29.decode(&encoded, 0, Duration::ZERO), state);
+        }
+    }
+
+    #[test]
+    fn test_every_field_is_listed_once() {
+        let state = G29State::from_frame(&[0; 12], 0, Duration::ZERO);
+
+        for control in Layout::G29.controls() {
+            let count = Layout::G29
+                .controls()
+                .iter()
+                .filter(|other| other.name == control.name)
+                .count();
+
+            assert_eq!(count, 1, "{}", control.name);
+            assert!(state.value(control.name).is_some(), "{}", control.name);
+            assert_eq!(control.field.map(|field| field.name()), Some(control.name));
+        }
+
+        // every field has a control
+        for field in Field::ALL {
+            assert!(
+                Layout::G29.control(field.name()).is_some(),
+                "{}",
+                field.name()
+            );
+        }
+        assert_eq!(Field::from_name("steering_raw"), Some(Field::SteeringRaw));
+        assert_eq!(Field::from_name("steering_ra"), None);
+    }
+
+    #[test]
+    fn test_custom_layout() {
+        // a wheel with 10-bit pedals and the X button in byte 1
+        let mut controls = Layout::G29.controls().to_vec();
+        for control in controls.iter_mut() {
+            match control.name {
+                "x_button" => {
+                    control.byte = 1;
+                    control.mask = 0x01;
+                }
+                "throttle" => control.mask = 0x03ff,
+                _ => {}
+            }
+        }
+        controls.retain(|control| control.name != "right_shifter");
+        controls.push(Control {
+            name: "square_button",
+            kind: ControlKind::Button,
+            byte: 11,
+            mask: 0x80,
+            bits: 1,
+            events: (None, None),
+            usage: None,
+            axis: None,
+            field: Field::from_name("square_button"),
+        });
+        let layout = Layout::new(controls);
+
+        let mut frame = [0u8; 12];
+        frame[1] = 0x01;
+        [frame[6], frame[7]] = 0x0300u16.to_le_bytes();
+        frame[11] = 0x80;
+
+        let state = layout.decode(&frame, 0, Duration::ZERO);
+        assert!(state.x_button);
+        assert!(!state.right_shifter);
+        assert!(state.square_button);
+        assert_eq!(state.throttle, 0xc0);
+
+        layout.set_button(&mut frame, Button::X, false);
+        assert_eq!(frame[1], 0x00);
+        assert!(layout.control("right_shifter").is_none());
+    }
+}
@@ -1,18 +1,62 @@
+use axis::{Axis, AxisConfig};
+use capture::{Capture, CaptureSettings};
+use clock::Clock;
+use command::Command;
+use curve::ResponseCurve;
+use effects::{AutoCenter, Condition, Effect, EffectHandle, EffectKind, Slot, SlotManager};
 use events::{Event, EventHandler, EventMap, HandlerFn};
+use force_loop::ForceLoop;
+use gearbox::{GearboxSettings, SequentialGear, SequentialGearbox, ShiftOutcome};
 use hidapi::{DeviceInfo, HidApi};
+use layout::Layout;
+use mixer::{ClippingStats, Mixer};
+use periodic::{Periodic, PeriodicHandle, PeriodicPlayer};
+use profile::Profile;
+use recording::{DeviceDetails, Recorder, Recording};
+use replay::{Playback, ReplaySpeed};
+use safety::{Safety, SafetySettings};
+use shifter::{HShifter, ShifterGear, ShifterSettings};
+use soft_lock::SoftLock;
 
 use std::{
     env::consts::OS,
+    io,
     ops::BitOr,
+    path::{Path, PathBuf},
     process::exit,
-    sync::{atomic::AtomicBool, Arc, Mutex, RwLock},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, RwLock,
+    },
     thread::{self, sleep},
-    time::Duration,
+    time::{Duration, Instant},
 };
 
+pub mod axis;
+pub mod capture;
+mod clock;
+pub mod command;
+pub mod curve;
+pub mod descriptor;
+pub mod effects;
+pub mod encoder;
 pub mod events;
+pub mod force_loop;
+pub mod gearbox;
+pub mod layout;
+pub mod mixer;
+pub mod periodic;
+pub mod profile;
+pub mod recording;
+pub mod replay;
+pub mod safety;
+pub mod shifter;
+pub mod simulator;
+pub mod soft_lock;
 // pub mod state;
 mod state;
+
+pub use state::G29State;
 // The size of the data frame that the G29 sends
 const FRAME_SIZE: usize = 12;
 
@@ -20,7 +64,8 @@ const FRAME_SIZE: usize = 12;
 /// DpadPosition
 ///
 /// Represents the position of the Dpad on the G29
-#[derive(Debug, PartialEq, Clone, Eq, Hash)]
+#[derive(Debug, PartialEq, Copy, Clone, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DpadPosition {
     Up,
     TopRight,
@@ -33,11 +78,6 @@ pub enum DpadPosition {
     None,
 }
 
-///
-/// Event
-/// Events that can be triggered by the G29
-///
-
 type Frame = [u8; FRAME_SIZE];
 
 ///
@@ -45,7 +85,8 @@ type Frame = [u8; FRAME_SIZE];
 ///
 /// Represents the gear selected on the G29
 ///
-#[derive(Debug, PartialEq, Clone, Eq, Hash)]
+#[derive(Debug, PartialEq, Copy, Clone, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum GearSelector {
     Neutral = 0,
     First = 1,
@@ -57,6 +98,82 @@ pub enum GearSelector {
     Reverse = 64,
 }
 
+///
+/// Button
+///
+/// A button on the G29
+///
+#[derive(Debug, PartialEq, Copy, Clone, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Button {
+    X,
+    Square,
+    Circle,
+    Triangle,
+    RightShifter,
+    LeftShifter,
+    R2,
+    L2,
+    Share,
+    Options,
+    R3,
+    L3,
+    Plus,
+    Minus,
+    SpinnerRight,
+    SpinnerLeft,
+    SpinnerButton,
+    PlayStation,
+}
+
+impl Button {
+    /// Every button, in declaration order.
+    pub const ALL: [Button; 18] = [
+        Button::X,
+        Button::Square,
+        Button::Circle,
+        Button::Triangle,
+        Button::RightShifter,
+        Button::LeftShifter,
+        Button::R2,
+        Button::L2,
+        Button::Share,
+        Button::Options,
+        Button::R3,
+        Button::L3,
+        Button::Plus,
+        Button::Minus,
+        Button::SpinnerRight,
+        Button::SpinnerLeft,
+        Button::SpinnerButton,
+        Button::PlayStation,
+    ];
+
+    /// The name of the button's control in a `Layout`, the same as its getter.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Button::X => "x_button",
+            Button::Square => "square_button",
+            Button::Circle => "circle_button",
+            Button::Triangle => "triangle_button",
+            Button::RightShifter => "right_shifter",
+            Button::LeftShifter => "left_shifter",
+            Button::R2 => "r2_button",
+            Button::L2 => "l2_button",
+            Button::Share => "share_button",
+            Button::Options => "options_button",
+            Button::R3 => "r3_button",
+            Button::L3 => "l3_button",
+            Button::Plus => "plus_button",
+            Button::Minus => "minus_button",
+            Button::SpinnerRight => "spinner_right",
+            Button::SpinnerLeft => "spinner_left",
+            Button::SpinnerButton => "spinner_button",
+            Button::PlayStation => "playstation_button",
+        }
+    }
+}
+
 ///
 /// Led
 ///
@@ -88,6 +205,19 @@ impl Led {
             Led::Other(val) => *val,
         }
     }
+
+    fn from_u8(value: u8) -> Led {
+        match value {
+            0x0 => Led::None,
+            0x01 => Led::GreenOne,
+            0x02 => Led::GreenTwo,
+            0x04 => Led::OrangeOne,
+            0x08 => Led::OrangeTwo,
+            0x10 => Led::Red,
+            0x1F => Led::All,
+            val => Led::Other(val),
+        }
+    }
 }
 
 impl BitOr for Led {
@@ -102,8 +232,6 @@ impl BitOr for Led {
     }
 }
 
-static CONNECTED: AtomicBool = AtomicBool::new(false);
-
 ///
 /// G29
 /// Establishes a connection to the Logitech G29 Racing Wheel and provides methods to interact with it.
@@ -130,7 +258,6 @@ static CONNECTED: AtomicBool = AtomicBool::new(false);
 ///
 #[derive(Debug, Clone)]
 pub struct G29 {
-    options: Options,
     prepend_write: bool,
     calibrated: bool,
     inner: Arc<RwLock<InnerG29>>,
@@ -138,10 +265,85 @@ pub struct G29 {
 
 #[derive(Debug)]
 struct InnerG29 {
-    data: Arc<RwLock<Frame>>,
+    options: Options,
+    // locked on its own so learning on the reader thread does not block the getters
+    axes: RwLock<AxisConfig>,
+    learning_axes: bool,
+    shifter: Option<HShifter>,
+    gearbox: Option<SequentialGearbox>,
+    layout: Layout,
+    // the report descriptor read when connecting, empty for virtual wheels
+    descriptor: Vec<u8>,
+    effects: SlotManager,
+    periodic: Arc<Mutex<PeriodicPlayer>>,
+    mixer: Arc<Mutex<Mixer>>,
+    safety: Arc<Mutex<Safety>>,
+    soft_lock: Option<(SoftLock, ForceLoop)>,
+    // degrees added to the steering
+    steering_trim: f32,
+    // plays the auto-center while the steering is trimmed
+    auto_center_spring: Option<EffectHandle>,
+    device: DeviceDetails,
+    recorder: Option<Recorder>,
+    capture: Option<Capture>,
+    playback: Option<Playback>,
+    data: Arc<RwLock<Sample>>,
+    // the time of the force threads, virtual for the simulator
+    clock: Clock,
+    connected: AtomicBool,
+    connected_at: Instant,
     reader_handle: Option<thread::JoinHandle<()>>,
     event_handlers: EventMap,
-    wheel: Option<Mutex<hidapi::HidDevice>>,
+    wheel: Option<Wheel>,
+}
+
+impl InnerG29 {
+    fn steering_offset(&self) -> i32 {
+        (self.steering_trim * 65535.0 / self.options.range.max(1) as f32).round() as i32
+    }
+
+    // The frame with the steering trim applied, stopping at full lock
+    fn trimmed(&self, mut frame: Frame) -> Frame {
+        let offset = self.steering_offset();
+        if offset == 0 {
+            return frame;
+        }
+
+        if let Some(control) = self.layout.control("steering_raw") {
+            let max = (1i64 << control.bits) - 1;
+            let value = (control.read(&frame) as i64 + offset as i64).clamp(0, max);
+            control.write(&mut frame, value as u32);
+        }
+
+        frame
+    }
+}
+
+// Where frames come from and commands go to
+#[derive(Debug)]
+enum Wheel {
+    Hid(Mutex<hidapi::HidDevice>),
+    // Commands are kept instead of being sent
+    Virtual(Mutex<Vec<[u8; 7]>>),
+}
+
+impl Wheel {
+    fn hid(&self) -> &Mutex<hidapi::HidDevice> {
+        match self {
+            Wheel::Hid(device) => device,
+            Wheel::Virtual(_) => panic!("hid -> Virtual wheels have no HID device"),
+        }
+    }
+}
+
+// The latest frame read from the wheel, along with when it arrived.
+// `frame` has the steering trim applied, `raw` is the frame as read
+#[derive(Debug, Default, Copy, Clone)]
+struct Sample {
+    frame: Frame,
+    raw: Frame,
+    sequence: u64,
+    timestamp: Duration,
 }
 
 ///
@@ -150,6 +352,8 @@ struct InnerG29 {
 /// - range: `u16` - The range of the wheel (40 - 900) (default: `900`)
 /// - auto_center: `[u8; 2]` - The auto center force and turning multiplier (default: `[0x07, 0xff]`)
 /// - auto_center_enabled: `bool` - Enable auto centering (default: `true`)
+/// - auto_center_advanced: `Option<AutoCenter>` - The advanced auto-center spring, replacing
+///   `auto_center` when set, ignored if it does not `validate` (default: `None`)
 ///
 /// # Example
 ///
@@ -169,6 +373,7 @@ pub struct Options {
     pub range: u16,
     pub auto_center: [u8; 2],
     pub auto_center_enabled: bool,
+    pub auto_center_advanced: Option<AutoCenter>,
 }
 
 impl Default for Options {
@@ -178,6 +383,7 @@ impl Default for Options {
             debug: false,
             range: 900,
             auto_center_enabled: true,
+            auto_center_advanced: None,
         }
     }
 }
@@ -198,6 +404,45 @@ fn get_wheel_info(api: &HidApi) -> DeviceInfo {
         .clone()
 }
 
+// The spring playing the auto-center of the options while the steering is trimmed
+fn trimmed_auto_center(options: &Options) -> Condition {
+    match options.auto_center_advanced {
+        Some(auto_center) => auto_center.spring(),
+        None => Condition::auto_center(options.auto_center[0], options.auto_center[1]),
+    }
+}
+
+// Read the report descriptor of the wheel, empty if the wheel does not report one
+fn read_descriptor(wheel: &hidapi::HidDevice, debug: bool) -> Vec<u8> {
+    let mut descriptor = [0u8; hidapi::MAX_REPORT_DESCRIPTOR_SIZE];
+
+    match wheel.get_report_descriptor(&mut descriptor) {
+        Ok(size) => descriptor[..size].to_vec(),
+        Err(error) => {
+            if debug {
+                println!(
+                    "readDescriptor -> Could not read the report descriptor: {}",
+                    error
+                );
+            }
+            Vec::new()
+        }
+    }
+}
+
+// Find the controls in the report descriptor of the wheel, falling back to the G29 layout
+fn read_layout(descriptor: &[u8], debug: bool) -> Layout {
+    match Layout::from_descriptor(descriptor) {
+        Ok(layout) => layout,
+        Err(error) => {
+            if debug {
+                println!("readLayout -> Using the default G29 layout: {}", error);
+            }
+            Layout::G29
+        }
+    }
+}
+
 impl G29 {
     ///
     /// Connect to the G29 Racing Wheel
@@ -220,29 +465,229 @@ impl G29 {
             println!("findWheel -> Found G29 Wheel at {:?}", wheel_info.path());
         }
 
+        let device = DeviceDetails {
+            vendor_id: wheel_info.vendor_id(),
+            product_id: wheel_info.product_id(),
+            serial_number: wheel_info.serial_number().map(str::to_string),
+            product: wheel_info.product_string().map(str::to_string),
+        };
         let wheel = wheel_info.open_device(&api).expect("Failed to open device");
         wheel
             .set_blocking_mode(false)
             .expect("Failed to set non-blocking mode");
 
         let prepend_write: bool = { matches!(OS, "windows") };
+        let descriptor = read_descriptor(&wheel, options.debug);
+        let layout = read_layout(&descriptor, options.debug);
+
+        let mut g29 = G29::with_wheel(options, device, Wheel::Hid(Mutex::new(wheel)));
+        g29.prepend_write = prepend_write;
+        {
+            let mut inner = g29.inner.write().unwrap();
+            inner.layout = layout;
+            inner.descriptor = descriptor;
+        }
+
+        g29.initialize();
+
+        g29
+    }
+
+    ///
+    /// Replay a recorded session as a virtual wheel.
+    ///
+    /// The returned handle behaves like a connected wheel: frames from the recording go through the
+    /// same getters and event handlers. Commands are kept for `written_commands` instead of being sent,
+    /// starting with the range and auto-center commands a real wheel receives when it connects.
+    ///
+    /// # Arguments
+    /// - `path` - The recording to replay
+    /// - `speed` - How fast to replay the recording
+    ///
+    /// # Example
+    /// ```rust
+    /// let mut g29 = G29::replay(Path::new("session.g29rec"), ReplaySpeed::Stepped)?;
+    ///
+    /// g29.register_event_handler(Event::XButtonPressed, |g29| {
+    ///     g29.set_leds(Led::All);
+    /// });
+    ///
+    /// while g29.step() {}
+    /// ```
+    pub fn replay(path: &Path, speed: ReplaySpeed) -> io::Result<G29> {
+        if let ReplaySpeed::Accelerated(factor) = speed {
+            if factor.is_nan() || factor <= 0.0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "replay -> Speed must be greater than zero",
+                ));
+            }
+        }
+
+        let recording = Recording::read(path)?;
+
+        let g29 = G29::with_wheel(
+            recording.header.options,
+            recording.header.device.clone(),
+            Wheel::Virtual(Mutex::new(Vec::new())),
+        );
+        g29.inner.write().unwrap().playback = Some(Playback::new(&recording));
 
-        let mut g29 = G29 {
-            options,
-            prepend_write,
+        g29.send_range();
+        g29.set_auto_center();
+
+        if let Some(factor) = speed.factor() {
+            let mut g29_clone = g29.clone();
+            let thread_handle = thread::spawn(move || g29_clone.play(factor));
+            g29.inner.write().unwrap().reader_handle = Some(thread_handle);
+        }
+
+        Ok(g29)
+    }
+
+    fn with_wheel(mut options: Options, device: DeviceDetails, wheel: Wheel) -> G29 {
+        if let Some(Err(error)) = options.auto_center_advanced.map(|a| a.validate()) {
+            if options.debug {
+                println!(
+                    "userOptions -> Ignoring the advanced auto-center: {}",
+                    error
+                );
+            }
+            options.auto_center_advanced = None;
+        }
+
+        G29 {
+            prepend_write: false,
             calibrated: false,
             inner: Arc::new(RwLock::new(InnerG29 {
-                wheel: Some(Mutex::new(wheel)),
-                data: Arc::new(RwLock::new([0; FRAME_SIZE])),
+                options,
+                axes: RwLock::new(AxisConfig::default()),
+                learning_axes: false,
+                shifter: None,
+                gearbox: None,
+                layout: Layout::G29,
+                descriptor: Vec::new(),
+                effects: SlotManager::default(),
+                periodic: Arc::new(Mutex::new(PeriodicPlayer::default())),
+                mixer: Arc::new(Mutex::new(Mixer::default())),
+                safety: Arc::new(Mutex::new(Safety::default())),
+                soft_lock: None,
+                steering_trim: 0.0,
+                auto_center_spring: None,
+                device,
+                recorder: None,
+                capture: None,
+                playback: None,
+                wheel: Some(wheel),
+                data: Arc::new(RwLock::new(Sample::default())),
+                clock: Clock::System,
+                connected: AtomicBool::new(true),
+                connected_at: Instant::now(),
                 reader_handle: None,
                 event_handlers: EventMap::new(),
             })),
-        };
-        CONNECTED.store(true, std::sync::atomic::Ordering::Release);
+        }
+    }
 
-        g29.initialize();
+    // Feed the recorded frames on their original schedule, divided by `factor`
+    fn play(&mut self, factor: f64) {
+        let started = Instant::now();
 
-        g29
+        while self.connected() {
+            let next = self
+                .inner
+                .write()
+                .unwrap()
+                .playback
+                .as_mut()
+                .and_then(Playback::next);
+
+            let Some((timestamp, frame)) = next else {
+                return;
+            };
+
+            let due = Duration::from_secs_f64(timestamp.as_secs_f64() / factor);
+
+            // sleep in short slices so disconnect does not wait for a long gap in the recording
+            loop {
+                let remaining = due.saturating_sub(started.elapsed());
+
+                if remaining.is_zero() || !self.connected() {
+                    break;
+                }
+
+                sleep(remaining.min(Duration::from_millis(10)));
+            }
+
+            self.process_frame(frame, timestamp);
+            self.frame_processed();
+        }
+    }
+
+    // Count a replayed frame towards `replay_finished` once its snapshot and events are done
+    fn frame_processed(&self) {
+        if let Some(playback) = self.inner.write().unwrap().playback.as_mut() {
+            playback.processed();
+        }
+    }
+
+    /// Feed the next frame of a replay.
+    /// Returns `false` once every frame has been fed, or if the wheel is not a replay.
+    ///
+    /// Use this with `ReplaySpeed::Stepped` to control exactly when each frame arrives.
+    pub fn step(&mut self) -> bool {
+        let next = self
+            .inner
+            .write()
+            .unwrap()
+            .playback
+            .as_mut()
+            .and_then(Playback::next);
+
+        match next {
+            Some((timestamp, frame)) => {
+                self.process_frame(frame, timestamp);
+                self.frame_processed();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns `true` once a replay has fed every frame and the last one has been processed.
+    /// Always `false` for a real wheel.
+    pub fn replay_finished(&self) -> bool {
+        self.inner
+            .read()
+            .unwrap()
+            .playback
+            .as_ref()
+            .is_some_and(Playback::finished)
+    }
+
+    /// Get the commands written to a virtual wheel, oldest first.
+    /// Always empty for a real wheel.
+    ///
+    /// # Example
+    /// ```rust
+    /// g29.set_leds(Led::All);
+    /// assert_eq!(
+    ///     g29.written_commands().last(),
+    ///     Some(&[0xf8, 0x12, 0x1f, 0x00, 0x00, 0x00, 0x01])
+    /// );
+    /// ```
+    pub fn written_commands(&self) -> Vec<[u8; 7]> {
+        match self.inner.read().unwrap().wheel.as_ref() {
+            Some(Wheel::Virtual(written)) => written.lock().unwrap().clone(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Forget the commands written to a virtual wheel so far.
+    pub fn clear_written_commands(&self) {
+        if let Some(Wheel::Virtual(written)) = self.inner.read().unwrap().wheel.as_ref() {
+            written.lock().unwrap().clear();
+        }
     }
 
     fn initialize(&mut self) {
@@ -252,6 +697,7 @@ impl G29 {
             .wheel
             .as_ref()
             .unwrap()
+            .hid()
             .lock()
             .unwrap()
             .set_blocking_mode(false)
@@ -265,20 +711,21 @@ impl G29 {
             .wheel
             .as_ref()
             .unwrap()
+            .hid()
             .lock()
             .unwrap()
             .read(&mut data)
             .expect("connect -> Error reading from device.");
 
-        self.force_off(0xf3);
+        self.force_off(Slot::ALL_MASK);
 
         if data_size == FRAME_SIZE || self.calibrated {
-            if self.options.debug {
+            if self.options().debug {
                 println!("connect -> Wheel already in high precision mode.");
             }
             self.listen(true);
         } else {
-            if self.options.debug {
+            if self.options().debug {
                 println!("connect -> Initializing Wheel.");
             }
 
@@ -293,8 +740,8 @@ impl G29 {
 
     fn calibrate_wheel(&mut self) {
         // G29 Wheel init from - https://github.com/torvalds/linux/blob/master/drivers/hid/hid-lg4ff.c
-        self.relay_os([0xf8, 0x0a, 0x00, 0x00, 0x00, 0x00, 0x00], "init_1");
-        self.relay_os([0xf8, 0x09, 0x05, 0x01, 0x01, 0x00, 0x00], "init_2");
+        self.send(Command::RevertModeOnReset);
+        self.send(Command::SwitchMode { mode: 0x05 });
 
         sleep(Duration::from_secs(8));
     }
@@ -312,6 +759,7 @@ impl G29 {
                 .wheel
                 .as_ref()
                 .unwrap()
+                .hid()
                 .lock()
                 .unwrap() = new_wheel;
 
@@ -319,10 +767,10 @@ impl G29 {
             return;
         }
 
-        self.set_range();
+        self.send_range();
         self.set_auto_center();
 
-        if self.options.debug {
+        if self.options().debug {
             println!("listen -> Ready to listen for wheel events.");
         }
 
@@ -330,41 +778,31 @@ impl G29 {
         let mut g29_clone = self.clone();
         let local_self = self.inner.clone();
         let thread_handle = thread::spawn(move || {
-            while CONNECTED.load(std::sync::atomic::Ordering::Relaxed) {
+            while g29_clone.connected() {
                 let mut new_data = [0u8; FRAME_SIZE];
-                match local_self
+                let read_result = local_self
                     .read()
                     .unwrap()
                     .wheel
                     .as_ref()
                     .unwrap()
+                    .hid()
                     .lock()
                     .unwrap()
-                    .read(&mut new_data)
-                {
-                    Ok(size_read) if size_read == FRAME_SIZE => {
-                        let local_self_write = local_self.read().unwrap();
-                        let mut prev_data = local_self_write.data.write().unwrap();
-
-                        if new_data == *prev_data {
-                            continue;
-                        }
-
-                        local_self_write.event_handlers.trigger_events(
-                            &prev_data,
-                            &new_data,
-                            &mut g29_clone,
-                        );
+                    .read(&mut new_data);
 
-                        *prev_data = new_data;
+                match read_result {
+                    Ok(size_read) if size_read == FRAME_SIZE => {
+                        let timestamp = g29_clone.inner.read().unwrap().connected_at.elapsed();
+                        g29_clone.process_frame(new_data, timestamp);
                     }
                     Ok(_) => {
-                        if g29_clone.options.debug {
+                        if g29_clone.options().debug {
                             println!("listen -> Incomplete data read from device.");
                         }
                     }
                     Err(e) => {
-                        if g29_clone.options.debug {
+                        if g29_clone.options().debug {
                             println!("listen -> Error reading from device: {:?}", e);
                         }
                     }
@@ -374,88 +812,256 @@ impl G29 {
         self.inner.write().unwrap().reader_handle = Some(thread_handle);
     }
 
-    // fn auto_center_complex(
-    //     &self,
-    //     clockwise_angle: u8,
-    //     counter_clockwise_angle: u8,
-    //     clockwise_force: u8,
-    //     counter_clockwise_force: u8,
-    //     reverse: bool,
-    //     centering_force: u8,
-    // ) {
-    //     if !self.options.auto_center_enabled {
-    //         return;
-    //     }
-
-    //     self.force_off(0xf5);
-
-    //     // auto-center on
-    //     self.relay_os(
-    //         [
-    //             0xfc,
-    //             0x01,
-    //             clockwise_angle,
-    //             counter_clockwise_angle,
-    //             clockwise_force | counter_clockwise_force,
-    //             reverse as u8,
-    //             centering_force,
-    //         ],
-    //         "set_auto_center_complex",
-    //     );
-    // }
+    // Update the state derived from a new frame, then store the frame and trigger events.
+    // The previous and new frames are decoded once and the states passed down
+    fn process_frame(&mut self, raw: Frame, timestamp: Duration) {
+        let mut derived_events = Vec::new();
+
+        let (new_data, prev_data, prev_state, state, needs_update) = {
+            let inner = self.inner.read().unwrap();
+
+            if let Some(recorder) = inner.recorder.as_ref() {
+                recorder.input(&raw);
+            }
+
+            if let Some(capture) = inner.capture.as_ref() {
+                capture.input(&raw);
+            }
+
+            let new_data = inner.trimmed(raw);
+            let prev_data = inner.data.read().unwrap().frame;
+            let prev_state = inner.layout.decode(&prev_data, 0, Duration::ZERO);
+            let state = inner.layout.decode(&new_data, 0, Duration::ZERO);
+            let changed = prev_data != new_data;
+
+            if changed && inner.learning_axes {
+                inner.axes.write().unwrap().observe(&state);
+            }
+
+            let needs_update = changed && (inner.shifter.is_some() || inner.gearbox.is_some());
+            (new_data, prev_data, prev_state, state, needs_update)
+        };
+
+        if self.emergency_stop_pressed(&prev_state, &state) {
+            self.emergency_stop();
+        }
+
+        if needs_update {
+            let mut guard = self.inner.write().unwrap();
+            let inner = &mut *guard;
+
+            if let Some(shifter) = inner.shifter.as_mut() {
+                let prev_gear = shifter.gear();
+
+                if prev_gear
+                    != shifter.update(state.shifter_x, state.shifter_y, state.shifter_pressed)
+                {
+                    derived_events.push(Event::ShifterGearChanged);
+                }
+            }
+
+            if let Some(gearbox) = inner.gearbox.as_mut() {
+                let clutch = inner.axes.read().unwrap().value(Axis::Clutch, &state);
+                let mut outcomes = Vec::new();
+
+                if state.right_shifter && !prev_state.right_shifter {
+                    outcomes.push(gearbox.shift_up(clutch));
+                }
+
+                if state.left_shifter && !prev_state.left_shifter {
+                    outcomes.push(gearbox.shift_down(clutch));
+                }
+
+                derived_events.extend(outcomes.into_iter().map(|outcome| match outcome {
+                    ShiftOutcome::Changed(_) => Event::SequentialGearChanged,
+                    ShiftOutcome::Missed => Event::SequentialShiftMissed,
+                    ShiftOutcome::WithoutClutch => Event::SequentialShiftWithoutClutch,
+                }));
+            }
+        }
+
+        let inner = self.inner.clone();
+        let inner = inner.read().unwrap();
+        let mut sample = inner.data.write().unwrap();
+        sample.raw = raw;
+
+        if new_data == sample.frame {
+            return;
+        }
+
+        // only a new steering trim changes the stored frame in between, decode it again then
+        let prev_state = if sample.frame == prev_data {
+            prev_state
+        } else {
+            inner.layout.decode(&sample.frame, 0, Duration::ZERO)
+        };
+
+        inner.event_handlers.trigger_events(
+            &inner.layout,
+            &inner.axes.read().unwrap(),
+            &prev_state,
+            &state,
+            self,
+        );
+
+        for event in derived_events {
+            inner.event_handlers.trigger(event, self);
+        }
+
+        *sample = Sample {
+            frame: new_data,
+            raw,
+            sequence: sample.sequence + 1,
+            timestamp,
+        };
+    }
 
     fn set_auto_center(&self) {
         /*
             Set wheel autocentering based on existing options.
         */
-        if self.options.auto_center_enabled {
+        let options = self.options();
+        let advanced = options.auto_center_advanced;
+
+        // the wheel's auto-center cannot be moved, so a spring centered on the trim replaces it
+        if options.auto_center_enabled && self.steering_offset() != 0 {
+            if self.play_auto_center_spring(trimmed_auto_center(&options)) {
+                self.send(Command::AutoCenterOff);
+                return;
+            }
+        } else {
+            let spring = self.inner.write().unwrap().auto_center_spring.take();
+            drop(spring);
+        }
+
+        if let (true, Some(auto_center)) = (options.auto_center_enabled, advanced) {
+            // the advanced spring is set with auto-center off
+            self.send(Command::AutoCenterOff);
+            self.send(Command::AutoCenterAdvanced(auto_center));
+        } else if options.auto_center_enabled {
             // auto-center on
-            self.relay_os([0x14, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], "auto_center on");
-            self.relay_os(
-                [
-                    0xfe,
-                    0x0d,
-                    self.options.auto_center[0],
-                    self.options.auto_center[0],
-                    self.options.auto_center[1],
-                    0x00,
-                    0x00,
-                ],
-                "set_auto_center_force",
-            );
+            self.send(Command::AutoCenterOn);
+            self.send(Command::SetAutoCenterForce {
+                strength: options.auto_center[0],
+                turning_multiplier: options.auto_center[1],
+            });
         } else {
             // auto-center off
-            self.relay_os(
-                [0xf5, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
-                "auto_center off",
-            );
+            self.send(Command::AutoCenterOff);
         }
     }
 
-    fn set_range(&mut self) {
+    // Play the auto-center spring, returns `false` if there is no free slot for it
+    fn play_auto_center_spring(&self, spring: Condition) -> bool {
+        let handle = self.inner.write().unwrap().auto_center_spring.take();
+
+        let handle = match handle {
+            Some(mut handle) if handle.is_active() => {
+                let _ = handle.update(Effect::Spring(spring));
+                handle
+            }
+            _ => match self.play_effect(Effect::Spring(spring)) {
+                Ok(handle) => handle,
+                Err(_) => {
+                    if self.options().debug {
+                        println!("set_auto_center -> No free slot to center on the trim");
+                    }
+                    return false;
+                }
+            },
+        };
+
+        self.inner.write().unwrap().auto_center_spring = Some(handle);
+        true
+    }
+
+    // The steering trim in raw steering units, for the current range
+    fn steering_offset(&self) -> i32 {
+        self.inner.read().unwrap().steering_offset()
+    }
+
+    // The command playing an effect, with springs centered on the steering trim
+    pub(crate) fn effect_command(&self, effect: Effect, slots: u8) -> Command {
+        let effect = match effect {
+            Effect::Spring(mut spring) => {
+                spring.center -= self.steering_offset() as f32 / 32767.5;
+                Effect::Spring(spring)
+            }
+            effect => effect,
+        };
+
+        effect.command(slots)
+    }
+
+    fn send_range(&self) {
         /*
             Set wheel range.
         */
-        if self.options.range < 40 {
-            self.options.range = 40;
-        }
+        let range = {
+            let mut inner = self.inner.write().unwrap();
+            inner.options.range = inner.options.range.clamp(40, 900);
+            inner.options.range
+        };
+
+        self.send(Command::SetRange(range));
+    }
+
+    /// Get the options of the wheel, with the current range and auto-center.
+    /// They are kept after `disconnect`, so the wheel can be connected again with the same settings.
+    pub fn options(&self) -> Options {
+        self.inner.read().unwrap().options
+    }
+
+    fn force_off(&self, slots: u8) {
+        // turn off effects (except for auto-center)
+        self.inner.write().unwrap().effects.unclaim_all(slots);
+        self.send(Command::StopForces { slots });
+    }
+
+    // Play an effect without a handle in a fixed slot, holding the slot while it plays
+    fn play_in_slot(&self, effect: Effect, slot: Slot, operation: &str) -> io::Result<()> {
+        let command = self.effect_command(effect, slot.mask());
+
+        let free = {
+            let mut inner = self.inner.write().unwrap();
 
-        if self.options.range > 900 {
-            self.options.range = 900;
+            match command {
+                Command::StopForces { .. } => inner.effects.unclaim(slot),
+                _ => inner.effects.claim(slot, effect.kind()),
+            }
+        };
+
+        if !free {
+            return Err(io::Error::other(format!(
+                "{} -> Slot {} is in use by an effect handle",
+                operation,
+                slot.number()
+            )));
         }
 
-        let range1 = self.options.range & 0x00ff;
-        let range2 = (self.options.range & 0xff00) >> 8;
+        self.send(command);
 
-        self.relay_os(
-            [0xf8, 0x81, range1 as u8, range2 as u8, 0x00, 0x00, 0x00],
-            "set_range",
-        );
+        Ok(())
     }
 
-    fn force_off(&self, slot: u8) {
-        // turn off effects (except for auto-center)
-        self.relay_os([slot, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], "force_off");
+    // Every command goes through the safety limits, see `set_safety`
+    fn send(&self, command: Command) {
+        let safety = self.safety_state();
+        let mut safety = safety.lock().unwrap();
+
+        for command in safety.filter(command, self.clock().now()) {
+            self.relay_os(command.encode(), command.name());
+        }
+    }
+
+    // Send a force of the mixer, which does not refresh the watchdog
+    pub(crate) fn send_streamed(&self, command: Command) {
+        let safety = self.safety_state();
+        let mut safety = safety.lock().unwrap();
+
+        for command in safety.filter_streamed(command, self.clock().now()) {
+            self.relay_os(command.encode(), command.name());
+        }
     }
 
     fn relay_os(&self, data: [u8; 7], operation: &str) {
@@ -473,12 +1079,32 @@ impl G29 {
             ];
         }
 
-        self.inner
-            .read()
-            .unwrap()
-            .wheel
-            .as_ref()
-            .expect("relay_os -> Wheel not found")
+        let inner = self.inner.read().unwrap();
+
+        // background threads may still be finishing after a disconnect
+        if inner.wheel.is_none() && !inner.connected.load(Ordering::Acquire) {
+            return;
+        }
+
+        if let Some(recorder) = inner.recorder.as_ref() {
+            recorder.output(&data);
+        }
+
+        if let Some(capture) = inner.capture.as_ref() {
+            capture.output(&data);
+        }
+
+        let wheel = inner.wheel.as_ref().expect("relay_os -> Wheel not found");
+
+        let device = match wheel {
+            Wheel::Hid(device) => device,
+            Wheel::Virtual(written) => {
+                written.lock().unwrap().push(data);
+                return;
+            }
+        };
+
+        device
             .lock()
             .unwrap()
             .write(if self.prepend_write { &new_data } else { &data })
@@ -514,40 +1140,240 @@ impl G29 {
     /// ```
     ///
     pub fn set_auto_center_force(&mut self, strength: u8, turning_multiplier: u8) {
-        self.options.auto_center = [strength, turning_multiplier];
+        {
+            let mut inner = self.inner.write().unwrap();
+            inner.options.auto_center = [strength, turning_multiplier];
+            inner.options.auto_center_advanced = None;
+        }
 
         self.set_auto_center();
     }
 
-    /// Set the LED lights on the G29.
+    /// Set the rotation range of the wheel, from lock to lock. The wheel stops turning at the
+    /// lock and reports its full steering travel across the range.
+    ///
     /// # Arguments
-    /// - `leds` - The LED lights to set
+    /// - `degrees` - The range, clamped to **40** to **900**
+    ///
     /// # Example
     /// ```rust
-    /// use lib_g29{G29, Options, Led};
-    /// use std::time::Duration;
-    /// use std::thread::sleep;
+    /// g29.set_range(540);
+    /// assert_eq!(g29.range(), 540);
+    /// ```
+    pub fn set_range(&self, degrees: u16) {
+        self.inner.write().unwrap().options.range = degrees;
+
+        self.send_range();
+        // the trim is in degrees, so it moves the raw steering by a different amount
+        if self.steering_trim() != 0.0 {
+            self.apply_steering_trim();
+        }
+    }
+
+    /// Get the rotation range of the wheel in degrees.
+    pub fn range(&self) -> u16 {
+        self.options().range
+    }
+
+    /// Offset the steering by a number of degrees, positive to the right.
     ///
-    ///   let options = Options {
-    ///     ..Default::default()
-    ///   };
+    /// The trim applies to every steering reading and event, to the center of springs sent
+    /// after it changes and to auto-center.
     ///
-    ///   let g29 = G29::connect(options);
+    /// The wheel cannot move its own auto-center, so while the steering is trimmed and auto-center
+    /// is enabled, auto-center plays as a spring centered on the trim, see
+    /// `auto_center_spring`. The spring takes one of the 4 force slots, leaving one fewer for
+    /// `play_effect`, `set_constant_force` and the other forces until the trim is set back to 0.
+    /// If no slot is free, the wheel's own auto-center stays on at the untrimmed center.
+    /// `Condition::auto_center` and `AutoCenter::spring` describe how the auto-center settings
+    /// become the spring.
     ///
-    ///   loop {
-    ///     g29.set_leds(Led::All);
-    ///     sleep(Duration::from_secs(1));
-    ///     g29.set_leds(Led::Red | Led::GreenOne);
-    ///     sleep(Duration::from_secs(1));
+    /// # Example
+    /// ```rust
+    /// // the wheel reads 3 degrees left when the car drives straight
+    /// g29.set_steering_trim(3.0);
+    /// ```
+    pub fn set_steering_trim(&self, degrees: f32) {
+        let degrees = if degrees.is_finite() { degrees } else { 0.0 };
+        self.inner.write().unwrap().steering_trim = degrees;
+
+        self.apply_steering_trim();
+    }
+
+    // Trim the latest frame straight away and move auto-center to the trim
+    fn apply_steering_trim(&self) {
+        {
+            let inner = self.inner.read().unwrap();
+            // re-trim the frame stored under the same lock, so a newer frame is not overwritten
+            let mut data = inner.data.write().unwrap();
+
+            // nothing was read from the wheel yet
+            if data.sequence > 0 {
+                data.frame = inner.trimmed(data.raw);
+                data.sequence += 1;
+            }
+        }
+
+        if self.connected() {
+            self.set_auto_center();
+        }
+    }
+
+    /// Trim the steering so its current position reads as center, or as the rest position of its
+    /// calibration.
+    pub fn recenter_steering(&self) {
+        let (state, center, range) = {
+            let inner = self.inner.read().unwrap();
+            let raw = inner.data.read().unwrap().raw;
+            let center = match inner
+                .axes
+                .read()
+                .unwrap()
+                .settings(Axis::Steering)
+                .calibration
+            {
+                Some(calibration) => calibration.rest as f32,
+                None => 32767.5,
+            };
+
+            (
+                inner.layout.decode(&raw, 0, Duration::ZERO),
+                center,
+                inner.options.range,
+            )
+        };
+
+        let offset = center - state.steering_raw() as f32;
+        self.set_steering_trim(offset * range as f32 / 65535.0);
+    }
+
+    /// Get the steering trim in degrees.
+    pub fn steering_trim(&self) -> f32 {
+        self.inner.read().unwrap().steering_trim
+    }
+
+    /// Get the slot and the spring playing auto-center while the steering is trimmed,
+    /// or `None` while the wheel's own auto-center is used.
+    /// The spring's center is relative to the trim, like every other spring.
+    pub fn auto_center_spring(&self) -> Option<(Slot, Condition)> {
+        let inner = self.inner.read().unwrap();
+        let handle = inner.auto_center_spring.as_ref()?;
+
+        if !inner.effects.owns(handle.slot(), handle.generation()) {
+            return None;
+        }
+
+        Some((handle.slot(), trimmed_auto_center(&inner.options)))
+    }
+
+    /// Push the wheel back with a stiff, damped spring once it turns past a lock, computed in
+    /// software and mixed with the periodic effects and force loops. Replaces the soft lock
+    /// already enabled, if any.
+    ///
+    /// # Errors
+    /// Returns an error if nothing is being mixed and all 4 force slots are in use.
+    ///
+    /// # Example
+    /// ```rust
+    /// use lib_g29::soft_lock::SoftLock;
+    ///
+    /// // report the full 900 degrees, but lock at 540
+    /// g29.set_range(900);
+    /// g29.enable_soft_lock(SoftLock::new(540))?;
+    /// ```
+    pub fn enable_soft_lock(&self, soft_lock: SoftLock) -> io::Result<()> {
+        self.disable_soft_lock();
+
+        let force_loop = soft_lock::start(self, soft_lock)?;
+        self.inner.write().unwrap().soft_lock = Some((soft_lock, force_loop));
+
+        Ok(())
+    }
+
+    /// Stop the soft lock.
+    pub fn disable_soft_lock(&self) {
+        let soft_lock = self.inner.write().unwrap().soft_lock.take();
+
+        // stopping the loop waits for its thread, which reads the wheel
+        drop(soft_lock);
+    }
+
+    /// Get the soft lock, if it is enabled.
+    pub fn soft_lock(&self) -> Option<SoftLock> {
+        self.inner
+            .read()
+            .unwrap()
+            .soft_lock
+            .as_ref()
+            .map(|(soft_lock, _)| *soft_lock)
+    }
+
+    /// Set the advanced auto-center spring, with a separate angle and force for each direction.
+    /// It replaces the auto-center force until `set_auto_center_force` is called, and is sent
+    /// again whenever the wheel is set up, such as after switching to native mode.
+    /// It is kept in `options`, so `disconnect` turns it off on the wheel but keeps the setting,
+    /// and `G29::connect(g29.options())` applies it again on the reconnected wheel.
+    ///
+    /// Nothing is sent while auto-center is disabled in the options.
+    ///
+    /// # Errors
+    /// Returns an `InvalidInput` error if a direction's force is above **0x0f**.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use lib_g29::{G29, Options, effects::AutoCenter};
+    ///
+    ///   let mut g29 = G29::connect(Options::default());
+    ///
+    ///   g29.set_auto_center_advanced(AutoCenter {
+    ///     clockwise_force: 0x0f,
+    ///     counter_clockwise_force: 0x03,
+    ///     ..Default::default()
+    ///   })?;
+    /// ```
+    ///
+    pub fn set_auto_center_advanced(&mut self, auto_center: AutoCenter) -> io::Result<()> {
+        auto_center.validate()?;
+
+        self.inner.write().unwrap().options.auto_center_advanced = Some(auto_center);
+        self.set_auto_center();
+
+        Ok(())
+    }
+
+    /// Get the advanced auto-center spring, if one is set.
+    pub fn auto_center_advanced(&self) -> Option<AutoCenter> {
+        self.options().auto_center_advanced
+    }
+
+    /// Set the LED lights on the G29.
+    /// # Arguments
+    /// - `leds` - The LED lights to set
+    /// # Example
+    /// ```rust
+    /// use lib_g29{G29, Options, Led};
+    /// use std::time::Duration;
+    /// use std::thread::sleep;
+    ///
+    ///   let options = Options {
+    ///     ..Default::default()
+    ///   };
+    ///
+    ///   let g29 = G29::connect(options);
+    ///
+    ///   loop {
+    ///     g29.set_leds(Led::All);
+    ///     sleep(Duration::from_secs(1));
+    ///     g29.set_leds(Led::Red | Led::GreenOne);
+    ///     sleep(Duration::from_secs(1));
     ///   }
     /// ````
     pub fn set_leds(&self, leds: Led) {
         /*
             Set the LED lights on the G29.
         */
-        let data = [0xf8, 0x12, leds.as_u8(), 0x00, 0x00, 0x00, 0x01];
-
-        self.relay_os(data, "set_leds");
+        self.send(Command::SetLeds(leds));
     }
 
     /// Set the force feedback on the G29.
@@ -565,47 +1391,777 @@ impl G29 {
     ///
     ///   let mut g29 = G29::connect(options);
     ///
-    ///   g29.force_friction(0x07, 0x07);
+    ///   g29.force_friction(0x07, 0x07)?;
     ///
     ///   loop {}
     /// ```
-    pub fn force_friction(&self, mut left: u8, mut right: u8) {
-        if left | right == 0 {
-            self.force_off(2);
-            return;
+    ///
+    /// Friction plays in slot 2, which is held until it is stopped with `force_friction(0, 0)`.
+    /// Returns an `InvalidInput` error, without sending anything, if a strength is above **0x07**,
+    /// or an error if an effect handle owns the slot.
+    pub fn force_friction(&self, left: u8, right: u8) -> io::Result<()> {
+        if left > 0x07 || right > 0x07 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "force_friction -> Strength must be 0x00 to 0x07, got {:#04x} and {:#04x}",
+                    left, right
+                ),
+            ));
         }
 
-        left *= 7;
-        right *= 7;
+        let effect = Effect::Friction {
+            left: left * 7,
+            right: right * 7,
+        };
 
-        self.relay_os(
-            [0x21, 0x02, left, 0x00, right, 0x00, 0x00],
-            "force_friction",
-        );
+        self.play_in_slot(effect, Slot::Two, "force_friction")
+    }
+
+    /// Set a spring pulling the wheel towards a center point.
+    /// A condition with no coefficients or no saturation stops the spring.
+    ///
+    /// The spring plays in slot 3, which is held until the spring is stopped.
+    /// Returns an error, without sending anything, if an effect handle owns the slot.
+    ///
+    /// # Arguments
+    /// - `spring` - The coefficients, saturation, center and deadband, see [`Condition`]
+    ///
+    /// # Example
+    /// ```rust
+    /// use lib_g29::effects::Condition;
+    ///
+    /// // heavier steering to the right, with a little play around the center
+    /// g29.force_spring(Condition {
+    ///     left_coefficient: 0.4,
+    ///     right_coefficient: 0.6,
+    ///     deadband: 0.05,
+    ///     ..Default::default()
+    /// })?;
+    /// ```
+    pub fn force_spring(&self, spring: Condition) -> io::Result<()> {
+        self.play_in_slot(Effect::Spring(spring), Slot::Three, "force_spring")
+    }
+
+    /// Set a damper resisting how fast the wheel turns.
+    /// The center and deadband of the condition are not used.
+    /// A condition with no coefficients or no saturation stops the damper.
+    ///
+    /// The damper plays in slot 4, which is held until the damper is stopped.
+    /// Returns an error, without sending anything, if an effect handle owns the slot.
+    ///
+    /// # Arguments
+    /// - `damper` - The coefficients and saturation, see [`Condition`]
+    ///
+    /// # Example
+    /// ```rust
+    /// use lib_g29::effects::Condition;
+    ///
+    /// g29.force_damper(Condition::new(0.3))?;
+    /// ```
+    pub fn force_damper(&self, damper: Condition) -> io::Result<()> {
+        self.play_in_slot(Effect::Damper(damper), Slot::Four, "force_damper")
+    }
+
+    /// Play a constant force, the torque a game applies to the steering.
+    ///
+    /// # Arguments
+    /// - `level` - The force between -1.0 (full force to the left) and 1.0 (full force to the right).
+    ///   Values outside that range are clamped, and 0.0 stops the force
+    /// - `slot` - The force slot to play the effect in (**1** to **4**)
+    ///
+    /// The slot is held until the force is stopped with 0.0.
+    /// The force is not part of the effect mix, see `set_effect_gain`.
+    /// Returns an `InvalidInput` error, without sending anything, if the slot does not exist,
+    /// or an error if an effect handle owns it.
+    ///
+    /// # Example
+    /// ```rust
+    /// loop {
+    ///     // pull the wheel back towards the center
+    ///     let level = -g29.steering_normalized();
+    ///     g29.set_constant_force(level, 1)?;
+    ///     sleep(Duration::from_millis(2));
+    /// }
+    /// ```
+    pub fn set_constant_force(&self, level: f32, slot: u8) -> io::Result<()> {
+        let slot = Slot::from_number(slot).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("set_constant_force -> Slot must be 1 to 4, got {}", slot),
+            )
+        })?;
+
+        self.play_in_slot(Effect::Constant(level), slot, "set_constant_force")
+    }
+
+    /// Play an effect in a free force slot.
+    ///
+    /// The slot is reserved until the returned handle is dropped or `release_all_effects` is called.
+    /// Slots held by `set_constant_force`, `force_friction`, `force_spring` or `force_damper`
+    /// are not free until those effects are stopped.
+    /// Returns an error, without sending anything, if all 4 slots are in use.
+    /// The effect plays on its own, outside the effect mix, see `set_effect_gain`.
+    ///
+    /// # Example
+    /// ```rust
+    /// use lib_g29::effects::Effect;
+    ///
+    /// let mut torque = g29.play_effect(Effect::Constant(0.0))?;
+    ///
+    /// loop {
+    ///     torque.update(Effect::Constant(-g29.steering_normalized()))?;
+    ///     sleep(Duration::from_millis(2));
+    /// }
+    /// ```
+    pub fn play_effect(&self, effect: Effect) -> io::Result<EffectHandle> {
+        let (slot, generation) = self
+            .inner
+            .write()
+            .unwrap()
+            .effects
+            .allocate(effect.kind())
+            .ok_or_else(|| io::Error::other("play_effect -> All 4 force slots are in use"))?;
+
+        self.send(self.effect_command(effect, slot.mask()));
+
+        Ok(EffectHandle::new(self.clone(), slot, generation))
+    }
+
+    /// Stop every effect and free every slot. Existing handles become inactive.
+    pub fn release_all_effects(&self) {
+        self.inner.write().unwrap().effects.free_all();
+        self.force_off(Slot::ALL_MASK);
+    }
+
+    /// Play a periodic effect, computed in software and streamed to the wheel as a constant force.
+    ///
+    /// Periodic effects and force loops are mixed into one force, see `set_ffb_strength`.
+    /// It plays in a slot that is reserved when the first of them starts and freed once they
+    /// have all stopped. The effect stops when its duration runs out or its handle is dropped.
+    ///
+    /// # Errors
+    /// Returns an error if nothing is being mixed and all 4 force slots are in use.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use lib_g29::periodic::{Periodic, Waveform};
+    /// use std::time::Duration;
+    ///
+    /// let engine = g29.play_periodic(Periodic::new(Waveform::Sine, 0.1, Duration::from_millis(20)))?;
+    /// ```
+    ///
+    pub fn play_periodic(&self, effect: Periodic) -> io::Result<PeriodicHandle> {
+        let player = self.inner.read().unwrap().periodic.clone();
+
+        periodic::play(self, &player, &self.mixer(), effect)
+    }
+
+    /// Call a function at a fixed rate and stream the torque it returns to the wheel as a
+    /// constant force, -1.0 (full force to the left) to 1.0 (full force to the right).
+    ///
+    /// The function gets the latest state of the wheel and the time since the loop started.
+    /// The state is read without waiting on the getters, decoded with the layout in use when
+    /// the loop starts. The torque is mixed with the periodic effects, see `play_periodic`.
+    /// The loop keeps stats of late ticks, see `ForceLoop::stats`.
+    ///
+    /// # Arguments
+    /// - `rate` - How many times a second the function is called, **1** to **1000**, such as **500**
+    /// - `function` - Returns the torque to play
+    ///
+    /// # Errors
+    /// Returns an `InvalidInput` error if `rate` is 0 or above 1000, or an error if nothing is being mixed and
+    /// all 4 force slots are in use.
+    ///
+    /// # Example
+    /// ```rust
+    /// // a spring with damping, computed on the host
+    /// let mut previous = 0.0;
+    /// let force_loop = g29.start_force_loop(500, move |state, _elapsed| {
+    ///     let position = state.steering as f32 / 127.5 - 1.0;
+    ///     let velocity = position - previous;
+    ///     previous = position;
+    ///
+    ///     -0.8 * position - 20.0 * velocity
+    /// })?;
+    ///
+    /// sleep(Duration::from_secs(10));
+    /// println!("{:?}", force_loop.stop());
+    /// ```
+    pub fn start_force_loop<F>(&self, rate: u32, function: F) -> io::Result<ForceLoop>
+    where
+        F: FnMut(&G29State, Duration) -> f32 + Send + 'static,
+    {
+        ForceLoop::start(self, rate, function)
+    }
+
+    /// Set the master strength of every force sent to the wheel, **0.0** to **1.0**
+    /// (default: `1.0`). It applies to constant forces, friction, springs, dampers, auto-center
+    /// and the mix of periodic effects and force loops, on top of the `max_torque` of the safety
+    /// settings. The mix is scaled after it is clipped, so it can be turned down while effects
+    /// play without changing how they clip. Forces already playing are sent again.
+    ///
+    /// # Example
+    /// ```rust
+    /// g29.set_ffb_strength(0.6);
+    /// ```
+    pub fn set_ffb_strength(&self, strength: f32) {
+        let safety = self.safety_state();
+
+        {
+            let mut locked = safety.lock().unwrap();
+            for command in locked.set_strength(strength, self.clock().now()) {
+                self.relay_os(command.encode(), command.name());
+            }
+        }
+    }
+
+    /// Get the master strength of every force.
+    pub fn ffb_strength(&self) -> f32 {
+        self.safety_state().lock().unwrap().strength()
+    }
+
+    /// Set the gain applied to the sum of the periodic effects and force loops, **0.0** and up
+    /// (default: `1.0`). The sum is clipped to full force after the gain, see `clipping_stats`.
+    /// Each effect also has a gain of its own, see `PeriodicHandle::set_gain` and
+    /// `ForceLoop::set_gain`.
+    ///
+    /// Only those effects are mixed. Forces that play in a slot of their own are sent as they
+    /// are, with no gain and no clipping stats: `set_constant_force`, every effect started with
+    /// `play_effect`, `force_friction`, `force_spring`, `force_damper` and auto-center. The wheel
+    /// adds them to the mix itself. `set_ffb_strength` and the safety settings still scale them,
+    /// so use those to turn every force down, or play a game's constant force from a force loop
+    /// to have it mixed.
+    pub fn set_effect_gain(&self, gain: f32) {
+        self.mixer().lock().unwrap().set_gain(gain);
+    }
+
+    /// Get how often and how hard the mix of periodic effects and force loops was clipped.
+    /// Forces outside the mix, listed in `set_effect_gain`, are not counted.
+    ///
+    /// # Example
+    /// ```rust
+    /// let stats = g29.clipping_stats();
+    ///
+    /// if stats.clipped * 10 > stats.mixes {
+    ///     g29.set_effect_gain(0.8);
+    /// }
+    /// g29.reset_clipping_stats();
+    /// ```
+    pub fn clipping_stats(&self) -> ClippingStats {
+        self.mixer().lock().unwrap().stats()
+    }
+
+    /// Start counting clipping again.
+    pub fn reset_clipping_stats(&self) {
+        self.mixer().lock().unwrap().reset_stats();
+    }
+
+    pub(crate) fn clock(&self) -> Clock {
+        self.inner.read().unwrap().clock.clone()
+    }
+
+    fn mixer(&self) -> Arc<Mutex<Mixer>> {
+        self.inner.read().unwrap().mixer.clone()
+    }
+
+    /// Set the limits applied to every force sent to the wheel: a cap on torque, a limit on how
+    /// fast forces change, a watchdog ramping forces to zero and an emergency stop chord.
+    /// Forces already playing, including auto-center, are sent again within the new limits.
+    ///
+    /// The rate limit and the watchdog apply to the strengths of every force and of auto-center,
+    /// see `SafetySettings`. Only the application keeps the watchdog alive; periodic effects,
+    /// force loops and auto-center do not refresh it, see `refresh_watchdog`.
+    ///
+    /// # Example
+    /// ```rust
+    /// use lib_g29::{safety::SafetySettings, Button};
+    ///
+    /// g29.set_safety(SafetySettings {
+    ///     max_torque: 0.5,
+    ///     watchdog_timeout: Some(Duration::from_millis(100)),
+    ///     emergency_stop: vec![Button::L3, Button::R3],
+    ///     ..Default::default()
+    /// });
+    ///
+    /// loop {
+    ///     g29.refresh_watchdog();
+    ///     sleep(Duration::from_millis(20));
+    /// }
+    /// ```
+    pub fn set_safety(&self, settings: SafetySettings) {
+        let safety = self.safety_state();
+
+        {
+            let mut locked = safety.lock().unwrap();
+            for command in locked.set_settings(settings, self.clock().now()) {
+                self.relay_os(command.encode(), command.name());
+            }
+        }
+
+        safety::start(self, &safety);
+    }
+
+    /// Get the limits applied to every force.
+    pub fn safety(&self) -> SafetySettings {
+        self.safety_state().lock().unwrap().settings().clone()
+    }
+
+    /// Keep the watchdog from ramping forces down. Sending a force also refreshes it, but the
+    /// forces of running periodic effects and force loops do not.
+    pub fn refresh_watchdog(&self) {
+        let now = self.clock().now();
+        self.safety_state().lock().unwrap().refresh(now);
+    }
+
+    /// Returns `true` while the watchdog has timed out and forces are ramped down.
+    pub fn watchdog_tripped(&self) -> bool {
+        self.safety_state().lock().unwrap().tripped()
+    }
+
+    /// Stop every force slot at once and refuse new forces until `clear_emergency_stop`.
+    /// Also called when the emergency stop chord of the safety settings is pressed.
+    pub fn emergency_stop(&self) {
+        self.safety_state().lock().unwrap().stop();
+        self.force_off(Slot::ALL_MASK);
+    }
+
+    /// Accept forces again after an emergency stop.
+    pub fn clear_emergency_stop(&self) {
+        self.safety_state().lock().unwrap().clear_stop();
+    }
+
+    /// Returns `true` after an emergency stop, until it is cleared.
+    pub fn emergency_stopped(&self) -> bool {
+        self.safety_state().lock().unwrap().stopped()
+    }
+
+    fn safety_state(&self) -> Arc<Mutex<Safety>> {
+        self.inner.read().unwrap().safety.clone()
+    }
+
+    // Returns `true` when a frame completes the emergency stop chord
+    fn emergency_stop_pressed(&self, prev_state: &G29State, state: &G29State) -> bool {
+        let safety = self.safety_state();
+        let locked = safety.lock().unwrap();
+        let settings = locked.settings();
+
+        settings.emergency_stop_pressed(state) && !settings.emergency_stop_pressed(prev_state)
+    }
+
+    /// Get the slots reserved by effect handles and the kind of effect playing in each.
+    pub fn active_effects(&self) -> Vec<(Slot, EffectKind)> {
+        self.inner.read().unwrap().effects.active()
+    }
+
+    pub(crate) fn owns_slot(&self, slot: Slot, generation: u64) -> bool {
+        self.inner.read().unwrap().effects.owns(slot, generation)
+    }
+
+    // Record a new kind of effect for the slot, returns `false` if the slot was released
+    pub(crate) fn set_slot_effect(&self, slot: Slot, generation: u64, kind: EffectKind) -> bool {
+        let mut inner = self.inner.write().unwrap();
+        let owned = inner.effects.owns(slot, generation);
+
+        if owned {
+            inner.effects.set_kind(slot, kind);
+        }
+
+        owned
+    }
+
+    pub(crate) fn free_slot(&self, slot: Slot, generation: u64) -> bool {
+        self.inner.write().unwrap().effects.free(slot, generation)
+    }
+
+    /// Send a command to the wheel.
+    /// Use this for commands the rest of the API does not cover.
+    ///
+    /// # Example
+    /// ```rust
+    /// // play a constant force in slot 1
+    /// g29.send_command(Command::DownloadAndPlay {
+    ///     slots: 0x01,
+    ///     params: [0x00, 0x60, 0x60, 0x60, 0x60, 0x00],
+    /// });
+    /// ```
+    pub fn send_command(&self, command: Command) {
+        self.send(command);
+    }
+
+    /// Write a raw 7 byte report to the wheel, after checking that it is a known command.
+    /// Returns an `InvalidInput` error, without writing anything, if it is not.
+    pub fn write_raw(&self, data: &[u8]) -> io::Result<()> {
+        match Command::decode(data) {
+            Command::Unknown(_) => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("write_raw -> Unknown command: {:02x?}", data),
+            )),
+            command => {
+                self.send(command);
+                Ok(())
+            }
+        }
+    }
+
+    /// Get a snapshot of every control, decoded from a single frame.
+    ///
+    /// Unlike the individual getters, all values in the returned state come from the same frame.
+    ///
+    /// # Example
+    /// ```rust
+    /// let state = g29.snapshot();
+    ///
+    /// println!("Throttle: {} Brake: {}", state.throttle, state.brake);
+    /// ```
+    pub fn snapshot(&self) -> G29State {
+        let inner = self.inner.read().unwrap();
+        let sample = *inner.data.read().unwrap();
+
+        inner
+            .layout
+            .decode(&sample.frame, sample.sequence, sample.timestamp)
+    }
+
+    /// Get the layout used to decode frames, found in the report descriptor of the wheel when it
+    /// connected. `Layout::G29` if the descriptor could not be used.
+    pub fn layout(&self) -> Layout {
+        self.inner.read().unwrap().layout.clone()
+    }
+
+    /// Get the HID report descriptor read from the wheel when it connected, empty for replayed
+    /// and simulated wheels or if the wheel did not report one.
+    ///
+    /// # Example
+    /// ```rust
+    /// // save a capture to check `Layout::from_descriptor` against
+    /// std::fs::write("g29_ps3.rdesc", g29.report_descriptor())?;
+    /// ```
+    pub fn report_descriptor(&self) -> Vec<u8> {
+        self.inner.read().unwrap().descriptor.clone()
+    }
+
+    /// Use another layout to decode frames, for models that differ from the G29.
+    pub fn set_layout(&self, layout: Layout) {
+        self.inner.write().unwrap().layout = layout;
+    }
+
+    /// Get the value of a control by name, as listed in the layout.
+    /// Returns `None` if the layout has no such control.
+    ///
+    /// # Example
+    /// ```rust
+    /// for control in g29.layout().controls() {
+    ///     println!("{}: {:?}", control.name, g29.control_value(control.name));
+    /// }
+    /// ```
+    pub fn control_value(&self, name: &str) -> Option<u32> {
+        let inner = self.inner.read().unwrap();
+        let frame = inner.data.read().unwrap().frame;
+
+        inner
+            .layout
+            .control(name)
+            .map(|control| control.read(&frame))
     }
 
     /// Get the throttle value.
     ///  255 is depressed, 0 is fully pressed
     pub fn throttle(&self) -> u8 {
-        state::throttle(&self.inner.read().unwrap().data.read().unwrap())
+        self.snapshot().throttle
     }
 
     /// Get the brake value.
     ///  255 is depressed, 0 is fully pressed
     pub fn brake(&self) -> u8 {
-        state::brake(&self.inner.read().unwrap().data.read().unwrap())
+        self.snapshot().brake
     }
 
     /// Get the steering value.
     /// 255 is fully right, 0 is fully left
     pub fn steering(&self) -> u8 {
-        state::steering(&self.inner.read().unwrap().data.read().unwrap())
+        self.snapshot().steering
     }
 
     /// Get the fine steering value.
     /// 255 is fully right, 0 is fully left
     pub fn steering_fine(&self) -> u8 {
-        state::steering_fine(&self.inner.read().unwrap().data.read().unwrap())
+        self.snapshot().steering_fine
+    }
+
+    /// Get the 16-bit steering position, combining `steering` and `steering_fine`.
+    /// 65535 is fully right, 0 is fully left
+    pub fn steering_raw(&self) -> u16 {
+        self.snapshot().steering_raw()
+    }
+
+    /// Get the steering position between -1.0 (fully left) and 1.0 (fully right).
+    /// Uses the steering calibration if there is one, but not its inversion or curve.
+    pub fn steering_normalized(&self) -> f32 {
+        let state = self.snapshot();
+
+        self.inner
+            .read()
+            .unwrap()
+            .axes
+            .read()
+            .unwrap()
+            .calibrated(Axis::Steering, &state)
+    }
+
+    /// Get the steering angle in degrees using the configured range.
+    /// 0 is center, negative is left and ±`range / 2` is full lock.
+    /// Uses the rest position of the steering calibration as center, like `steering_normalized`
+    /// and the soft lock.
+    ///
+    /// # Example
+    /// ```rust
+    /// g29.register_event_handler(Event::SteeringRaw, |g29| {
+    ///     println!("Steering angle: {:.1}°", g29.steering_angle_degrees());
+    /// });
+    /// ```
+    pub fn steering_angle_degrees(&self) -> f32 {
+        self.steering_angle(&self.snapshot())
+    }
+
+    // The angle of the steering in a state, centered on the calibrated rest position
+    pub(crate) fn steering_angle(&self, state: &G29State) -> f32 {
+        let inner = self.inner.read().unwrap();
+        let normalized = inner.axes.read().unwrap().calibrated(Axis::Steering, state);
+
+        normalized * inner.options.range as f32 / 2.0
+    }
+
+    /// Get the normalized value of an axis, using the configured axis settings.
+    /// Pedals are 0.0 (released) to 1.0 (fully pressed), every other axis is -1.0 to 1.0.
+    ///
+    /// # Example
+    /// ```rust
+    /// use lib_g29::axis::Axis;
+    ///
+    /// g29.register_event_handler(Event::Throttle, |g29| {
+    ///     println!("Throttle: {:.0}%", g29.axis(Axis::Throttle) * 100.0);
+    /// });
+    /// ```
+    pub fn axis(&self, axis: Axis) -> f32 {
+        let state = self.snapshot();
+
+        self.inner
+            .read()
+            .unwrap()
+            .axes
+            .read()
+            .unwrap()
+            .value(axis, &state)
+    }
+
+    /// Flip the direction of an axis.
+    ///
+    /// # Example
+    /// ```rust
+    /// use lib_g29::axis::Axis;
+    ///
+    /// // report the throttle as 1.0 when released
+    /// g29.set_axis_inverted(Axis::Throttle, true);
+    /// ```
+    pub fn set_axis_inverted(&self, axis: Axis, inverted: bool) {
+        self.inner
+            .read()
+            .unwrap()
+            .axes
+            .write()
+            .unwrap()
+            .set_inverted(axis, inverted);
+    }
+
+    /// Set the response curve of an axis. Takes effect on the next reading.
+    /// Returns an `InvalidInput` error if the curve does not `validate`.
+    ///
+    /// # Example
+    /// ```rust
+    /// use lib_g29::{axis::Axis, curve::ResponseCurve};
+    ///
+    /// g29.set_response_curve(Axis::Throttle, ResponseCurve::SCurve(1.5))?;
+    /// ```
+    pub fn set_response_curve(&self, axis: Axis, curve: ResponseCurve) -> io::Result<()> {
+        self.inner
+            .read()
+            .unwrap()
+            .axes
+            .write()
+            .unwrap()
+            .set_curve(axis, curve)
+    }
+
+    /// Get the settings of every axis.
+    pub fn axis_config(&self) -> AxisConfig {
+        self.inner.read().unwrap().axes.read().unwrap().clone()
+    }
+
+    /// Replace the settings of every axis.
+    pub fn set_axis_config(&self, config: AxisConfig) {
+        *self.inner.read().unwrap().axes.write().unwrap() = config;
+    }
+
+    /// Learn the calibration of every axis while the wheel is used.
+    ///
+    /// While enabled, every frame extends the calibrated range of each axis but the steering,
+    /// see `AxisConfig::observe`. Axes without a calibration treat the first value seen as their
+    /// rest position.
+    pub fn set_axis_learning(&self, enabled: bool) {
+        self.inner.write().unwrap().learning_axes = enabled;
+    }
+
+    /// Calibrate every axis interactively.
+    ///
+    /// The current values are taken as the rest positions, so leave the pedals released when
+    /// calling this. Then press every pedal fully and move the shifter to its limits before
+    /// `duration` runs out. The steering calibration is removed and not learned again, its scale
+    /// is fixed by the range, see `recenter_steering` to move its center.
+    ///
+    /// # Example
+    /// ```rust
+    /// println!("Press every pedal fully within 10 seconds");
+    /// g29.calibrate_axes(Duration::from_secs(10));
+    /// g29.save_profile(Path::new("profiles")).unwrap();
+    /// ```
+    pub fn calibrate_axes(&self, duration: Duration) {
+        let state = self.snapshot();
+
+        {
+            let mut inner = self.inner.write().unwrap();
+            let axes = inner.axes.get_mut().unwrap();
+            axes.clear_calibration();
+            axes.observe(&state);
+            inner.learning_axes = true;
+        }
+
+        sleep(duration);
+
+        self.set_axis_learning(false);
+    }
+
+    /// Returns the serial number of the wheel, if it reports one.
+    pub fn serial_number(&self) -> Option<String> {
+        self.inner.read().unwrap().device.serial_number.clone()
+    }
+
+    /// Get the current settings of the wheel as a profile.
+    /// The serial number is empty if the wheel does not report one, and such a profile cannot be
+    /// saved or loaded.
+    pub fn profile(&self) -> Profile {
+        let inner = self.inner.read().unwrap();
+        let axes = inner.axes.read().unwrap().clone();
+
+        Profile {
+            serial: inner.device.serial_number.clone().unwrap_or_default(),
+            axes,
+            steering_trim: inner.steering_trim,
+        }
+    }
+
+    /// Save the current settings to the profile for this wheel in a directory.
+    /// Returns the path of the file written.
+    pub fn save_profile(&self, dir: &Path) -> io::Result<PathBuf> {
+        self.profile().save(dir)
+    }
+
+    /// Load and apply the profile for this wheel from a directory.
+    ///
+    /// # Example
+    /// ```rust
+    /// if g29.load_profile(Path::new("profiles")).is_err() {
+    ///     g29.calibrate_axes(Duration::from_secs(10));
+    /// }
+    /// ```
+    pub fn load_profile(&self, dir: &Path) -> io::Result<()> {
+        let profile = Profile::load(dir, &self.profile().serial)?;
+
+        self.set_axis_config(profile.axes);
+        self.set_steering_trim(profile.steering_trim);
+
+        Ok(())
+    }
+
+    /// Start recording every frame read from the wheel and every command written to it.
+    /// Stops any recording already in progress.
+    ///
+    /// Records are written on a separate thread, so recording does not slow down the reader.
+    /// Read the file back with `Recording::read`.
+    ///
+    /// # Example
+    /// ```rust
+    /// g29.start_recording(Path::new("session.g29rec")).unwrap();
+    /// sleep(Duration::from_secs(10));
+    /// g29.stop_recording().unwrap();
+    /// ```
+    pub fn start_recording(&self, path: &Path) -> io::Result<()> {
+        self.stop_recording()?;
+
+        let mut inner = self.inner.write().unwrap();
+        let recorder = Recorder::start(path, &inner.device, &inner.options)?;
+        inner.recorder = Some(recorder);
+
+        Ok(())
+    }
+
+    /// Stop recording, writing any queued records to the file.
+    /// Does nothing if no recording is in progress.
+    pub fn stop_recording(&self) -> io::Result<()> {
+        let recorder = self.inner.write().unwrap().recorder.take();
+
+        match recorder {
+            Some(recorder) => recorder.finish(),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns `true` if a recording is in progress.
+    pub fn is_recording(&self) -> bool {
+        self.inner.read().unwrap().recorder.is_some()
+    }
+
+    /// Start capturing the USB traffic of the wheel to a pcapng file that Wireshark can open.
+    /// Stops any capture already in progress.
+    ///
+    /// Every input report and every command written is captured as a USB interrupt transfer,
+    /// with its timestamp and direction.
+    ///
+    /// # Example
+    /// ```rust
+    /// // roll over into a new file every 10 MB
+    /// g29.start_capture(
+    ///     Path::new("wheel.pcapng"),
+    ///     CaptureSettings {
+    ///         max_file_size: Some(10_000_000),
+    ///     },
+    /// )
+    /// .unwrap();
+    /// ```
+    pub fn start_capture(&self, path: &Path, settings: CaptureSettings) -> io::Result<()> {
+        self.stop_capture()?;
+
+        let capture = Capture::start(path, settings)?;
+        self.inner.write().unwrap().capture = Some(capture);
+
+        Ok(())
+    }
+
+    /// Stop capturing, writing any queued packets to the file.
+    /// Does nothing if no capture is in progress.
+    pub fn stop_capture(&self) -> io::Result<()> {
+        let capture = self.inner.write().unwrap().capture.take();
+
+        match capture {
+            Some(capture) => capture.finish(),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns `true` if a capture is in progress.
+    pub fn is_capturing(&self) -> bool {
+        self.inner.read().unwrap().capture.is_some()
     }
 
     /// Get the Dpad position.
@@ -616,67 +2172,67 @@ impl G29 {
     /// }
     /// ````
     pub fn dpad(&self) -> DpadPosition {
-        state::dpad(&self.inner.read().unwrap().data.read().unwrap())
+        self.snapshot().dpad
     }
 
     /// Returns `true` if the x button is pressed.
     pub fn x_button(&self) -> bool {
-        state::x_button(&self.inner.read().unwrap().data.read().unwrap())
+        self.snapshot().x_button
     }
 
     /// Returns true if the square button is pressed.
     pub fn square_button(&self) -> bool {
-        state::square_button(&self.inner.read().unwrap().data.read().unwrap())
+        self.snapshot().square_button
     }
 
     /// Returns true if the circle button is pressed.
     pub fn circle_button(&self) -> bool {
-        state::circle_button(&self.inner.read().unwrap().data.read().unwrap())
+        self.snapshot().circle_button
     }
 
     /// Returns true if the triangle button is pressed.
     pub fn triangle_button(&self) -> bool {
-        state::triangle_button(&self.inner.read().unwrap().data.read().unwrap())
+        self.snapshot().triangle_button
     }
 
     /// returns true if the right shifter is pressed.
     pub fn right_shifter(&self) -> bool {
-        state::right_shifter(&self.inner.read().unwrap().data.read().unwrap())
+        self.snapshot().right_shifter
     }
 
     /// Returns true if the left shifter is pressed.
     pub fn left_shifter(&self) -> bool {
-        state::left_shifter(&self.inner.read().unwrap().data.read().unwrap())
+        self.snapshot().left_shifter
     }
 
     /// Returns true if the r2 button is pressed.
     pub fn r2_button(&self) -> bool {
-        state::r2_button(&self.inner.read().unwrap().data.read().unwrap())
+        self.snapshot().r2_button
     }
 
     /// Returns true if the l2 button is pressed.
     pub fn l2_button(&self) -> bool {
-        state::l2_button(&self.inner.read().unwrap().data.read().unwrap())
+        self.snapshot().l2_button
     }
 
     /// Returns true if the share button is pressed.
     pub fn share_button(&self) -> bool {
-        state::share_button(&self.inner.read().unwrap().data.read().unwrap())
+        self.snapshot().share_button
     }
 
     /// Returns true if the option button is pressed.
     pub fn option_button(&self) -> bool {
-        state::options_button(&self.inner.read().unwrap().data.read().unwrap())
+        self.snapshot().options_button
     }
 
     /// Returns true if the r3 button is pressed.
     pub fn r3_button(&self) -> bool {
-        state::r3_button(&self.inner.read().unwrap().data.read().unwrap())
+        self.snapshot().r3_button
     }
 
     /// Returns true if the l3 button is pressed.
     pub fn l3_button(&self) -> bool {
-        state::l3_button(&self.inner.read().unwrap().data.read().unwrap())
+        self.snapshot().l3_button
     }
 
     /// Get the gear selector position.
@@ -690,58 +2246,173 @@ impl G29 {
     /// ```
     ///
     pub fn gear_selector(&self) -> GearSelector {
-        state::gear_selector(&self.inner.read().unwrap().data.read().unwrap())
+        self.snapshot().gear_selector
+    }
+
+    /// Get the gear selected on the calibrated shifter.
+    /// Returns the gear selector position if the calibrated shifter is disabled.
+    ///
+    /// # Example
+    /// ```rust
+    /// g29.register_event_handler(Event::ShifterGearChanged, |g29| {
+    ///     println!("Gear: {:?}", g29.shifter_gear());
+    /// });
+    /// ```
+    pub fn shifter_gear(&self) -> ShifterGear {
+        match self.inner.read().unwrap().shifter {
+            Some(shifter) => shifter.gear(),
+            None => self.gear_selector().into(),
+        }
+    }
+
+    /// Classify gears from the raw shifter x and y axes instead of the gear selector.
+    /// Read the result with `shifter_gear`.
+    ///
+    /// # Example
+    /// ```rust
+    /// use lib_g29::shifter::{ShifterLayout, ShifterSettings};
+    ///
+    /// g29.enable_calibrated_shifter(ShifterSettings {
+    ///     layout: ShifterLayout::SevenSpeed,
+    ///     ..Default::default()
+    /// });
+    /// g29.calibrate_shifter(Duration::from_secs(10));
+    /// ```
+    pub fn enable_calibrated_shifter(&self, settings: ShifterSettings) {
+        self.inner.write().unwrap().shifter = Some(HShifter::new(settings));
+    }
+
+    /// Go back to reading gears from the gear selector.
+    pub fn disable_calibrated_shifter(&self) {
+        self.inner.write().unwrap().shifter = None;
+    }
+
+    /// Get the settings of the calibrated shifter, if it is enabled.
+    pub fn shifter_settings(&self) -> Option<ShifterSettings> {
+        self.inner
+            .read()
+            .unwrap()
+            .shifter
+            .map(|shifter| shifter.settings())
+    }
+
+    /// Learn the positions of the shifter gates.
+    ///
+    /// The current stick position is taken as neutral. Move the stick into every gate
+    /// before `duration` runs out. Enables the calibrated shifter with default settings
+    /// if it is not enabled yet.
+    pub fn calibrate_shifter(&self, duration: Duration) {
+        let state = self.snapshot();
+
+        self.inner
+            .write()
+            .unwrap()
+            .shifter
+            .get_or_insert_with(|| HShifter::new(ShifterSettings::default()))
+            .start_learning(state.shifter_x, state.shifter_y);
+
+        sleep(duration);
+
+        if let Some(shifter) = self.inner.write().unwrap().shifter.as_mut() {
+            shifter.stop_learning();
+        }
+    }
+
+    /// Get the gear of the virtual sequential gearbox, if it is enabled.
+    ///
+    /// # Example
+    /// ```rust
+    /// g29.register_event_handler(Event::SequentialGearChanged, |g29| {
+    ///     println!("Gear: {:?}", g29.sequential_gear());
+    /// });
+    /// ```
+    pub fn sequential_gear(&self) -> Option<SequentialGear> {
+        self.inner
+            .read()
+            .unwrap()
+            .gearbox
+            .map(|gearbox| gearbox.gear())
+    }
+
+    /// Drive a virtual sequential gearbox with the paddle shifters.
+    /// The right paddle shifts up and the left paddle shifts down.
+    /// Returns an `InvalidInput` error, keeping the current gearbox, if the settings do not `validate`.
+    ///
+    /// # Example
+    /// ```rust
+    /// use lib_g29::gearbox::GearboxSettings;
+    ///
+    /// g29.enable_sequential_gearbox(GearboxSettings {
+    ///     gears: 5,
+    ///     clutch_required: true,
+    ///     ..Default::default()
+    /// })?;
+    ///
+    /// g29.register_event_handler(Event::SequentialShiftWithoutClutch, |_| {
+    ///     println!("Press the clutch to shift");
+    /// });
+    /// ```
+    pub fn enable_sequential_gearbox(&self, settings: GearboxSettings) -> io::Result<()> {
+        let gearbox = SequentialGearbox::new(settings)?;
+        self.inner.write().unwrap().gearbox = Some(gearbox);
+
+        Ok(())
+    }
+
+    /// Remove the virtual sequential gearbox.
+    pub fn disable_sequential_gearbox(&self) {
+        self.inner.write().unwrap().gearbox = None;
     }
 
     /// Returns true if the plus button is pressed.
     pub fn plus_button(&self) -> bool {
-        state::plus_button(&self.inner.read().unwrap().data.read().unwrap())
+        self.snapshot().plus_button
     }
 
     /// Returns true if the minus button is pressed.
     pub fn minus_button(&self) -> bool {
-        state::minus_button(&self.inner.read().unwrap().data.read().unwrap())
+        self.snapshot().minus_button
     }
 
     /// Returns true if the spinner is rotating clockwise.
     pub fn spinner_right(&self) -> bool {
-        state::spinner_right(&self.inner.read().unwrap().data.read().unwrap())
+        self.snapshot().spinner_right
     }
 
     /// Returns true if the spinner is rotating counter-clockwise.
     pub fn spinner_left(&self) -> bool {
-        state::spinner_left(&self.inner.read().unwrap().data.read().unwrap())
+        self.snapshot().spinner_left
     }
 
     /// Returns true if the spinner button is pressed.
     pub fn spinner_button(&self) -> bool {
-        state::spinner_button(&self.inner.read().unwrap().data.read().unwrap())
+        self.snapshot().spinner_button
     }
 
     /// Returns true if the playstation button is pressed.
     pub fn playstation_button(&self) -> bool {
-        state::playstation_button(&self.inner.read().unwrap().data.read().unwrap())
+        self.snapshot().playstation_button
     }
 
     /// Returns the value of the clutch pedal.
     /// 255 is depressed, 0 is fully pressed
     pub fn clutch(&self) -> u8 {
-        state::clutch(&self.inner.read().unwrap().data.read().unwrap())
+        self.snapshot().clutch
     }
 
     /// Returns the value of the shifter x axis.
     pub fn shifter_x(&self) -> u8 {
-        state::shifter_x(&self.inner.read().unwrap().data.read().unwrap())
+        self.snapshot().shifter_x
     }
 
     /// Returns the value of the shifter y axis.
     pub fn shifter_y(&self) -> u8 {
-        state::shifter_y(&self.inner.read().unwrap().data.read().unwrap())
+        self.snapshot().shifter_y
     }
 
     /// Returns true if the shifter is pressed.
     pub fn shifter_pressed(&self) -> bool {
-        state::shifter_pressed(&self.inner.read().unwrap().data.read().unwrap())
+        self.snapshot().shifter_pressed
     }
 
     /// Disconnect from the G29.
@@ -766,25 +2437,48 @@ impl G29 {
             return;
         }
 
-        self.force_off(0xf3);
+        self.force_off(Slot::ALL_MASK);
         self.set_leds(Led::None);
-        self.force_friction(0, 0);
-        self.options.auto_center = [0x00, 0x00];
+        // force_off already stopped a friction handle, which would refuse this
+        let _ = self.force_friction(0, 0);
+        let options = {
+            let mut inner = self.inner.write().unwrap();
+            let options = inner.options;
+            inner.options.auto_center = [0x00, 0x00];
+            inner.options.auto_center_advanced = None;
+            options
+        };
         self.set_auto_center();
+        // turned off on the wheel, but kept in the options for connecting again
+        self.inner.write().unwrap().options = options;
 
         // set connected to false
 
-        CONNECTED.store(false, std::sync::atomic::Ordering::Release);
+        self.inner
+            .read()
+            .unwrap()
+            .connected
+            .store(false, Ordering::Release);
+        let player = self.inner.read().unwrap().periodic.clone();
+        periodic::stop(&player, &self.mixer());
+        safety::stop(&self.safety_state());
+        self.disable_soft_lock();
+        let spring = self.inner.write().unwrap().auto_center_spring.take();
+        drop(spring);
         self.inner.write().unwrap().wheel = None;
         // join all threads
         if let Some(handle) = self.inner.write().unwrap().reader_handle.take() {
             handle.join().unwrap();
         }
+
+        let _ = self.stop_recording();
+        let _ = self.stop_capture();
     }
 
     pub fn connected(&self) -> bool {
-        CONNECTED.load(std::sync::atomic::Ordering::Relaxed)
-            && self.inner.read().unwrap().wheel.is_some()
+        let inner = self.inner.read().unwrap();
+
+        inner.connected.load(Ordering::Relaxed) && inner.wheel.is_some()
     }
 
     ///
@@ -0,0 +1,305 @@
+use std::io;
+
+use crate::{
+    effects::{Effect, EffectHandle},
+    G29,
+};
+
+///
+/// ClippingStats
+///
+/// How often and how hard the mixed periodic effects and force loops went past full force.
+/// Forces that play in a slot of their own are not mixed, see `G29::set_effect_gain`.
+/// - mixes: `u64` - The number of times the effects were mixed
+/// - clipped: `u64` - The number of mixes that were clipped
+/// - max_excess: `f32` - The most a mix went past full force, where 1.0 is full force
+/// - mean_excess: `f32` - The average a clipped mix went past full force
+///
+#[derive(Debug, PartialEq, Copy, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ClippingStats {
+    pub mixes: u64,
+    pub clipped: u64,
+    pub max_excess: f32,
+    pub mean_excess: f32,
+}
+
+impl ClippingStats {
+    fn record(&mut self, level: f32) {
+        self.mixes += 1;
+
+        let excess = level.abs() - 1.0;
+        if excess > 0.0 {
+            let total = self.mean_excess * self.clipped as f32 + excess;
+
+            self.clipped += 1;
+            self.mean_excess = total / self.clipped as f32;
+            self.max_excess = self.max_excess.max(excess);
+        }
+    }
+}
+
+// Mixes the software effects, periodic effects and force loops, into one constant force.
+// The force plays in a slot that is reserved while any source is registered
+#[derive(Debug)]
+pub(crate) struct Mixer {
+    // id, gain and latest level of each source
+    sources: Vec<(u64, f32, f32)>,
+    next_id: u64,
+    gain: f32,
+    stats: ClippingStats,
+    slot: Option<EffectHandle>,
+    sent: Option<f32>,
+}
+
+impl Default for Mixer {
+    fn default() -> Self {
+        Mixer {
+            sources: Vec::new(),
+            next_id: 0,
+            gain: 1.0,
+            stats: ClippingStats::default(),
+            slot: None,
+            sent: None,
+        }
+    }
+}
+
+impl Mixer {
+    // Add a source, reserving the slot if it is the first
+    pub(crate) fn register(&mut self, g29: &G29) -> io::Result<u64> {
+        if self.slot.is_none() {
+            self.slot = Some(g29.play_effect(Effect::Constant(0.0))?);
+            self.sent = None;
+        }
+
+        self.next_id += 1;
+        self.sources.push((self.next_id, 1.0, 0.0));
+
+        Ok(self.next_id)
+    }
+
+    // Remove a source, freeing the slot if it was the last
+    pub(crate) fn unregister(&mut self, id: u64) {
+        self.sources.retain(|(i, ..)| *i != id);
+
+        if self.sources.is_empty() {
+            self.slot = None;
+        } else {
+            self.output();
+        }
+    }
+
+    // Returns `false` once the slot was released by `G29::release_all_effects`
+    pub(crate) fn is_active(&self) -> bool {
+        self.slot.as_ref().is_some_and(EffectHandle::is_active)
+    }
+
+    // Returns `false` if the source was unregistered
+    pub(crate) fn set_level(&mut self, id: u64, level: f32) -> bool {
+        match self.sources.iter_mut().find(|(i, ..)| *i == id) {
+            Some((_, _, current)) => {
+                *current = if level.is_nan() { 0.0 } else { level };
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Returns `false` if the source was unregistered
+    pub(crate) fn set_source_gain(&mut self, id: u64, gain: f32) -> bool {
+        match self.sources.iter_mut().find(|(i, ..)| *i == id) {
+            Some((_, current, _)) => {
+                *current = gain.max(0.0);
+                self.output();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub(crate) fn set_gain(&mut self, gain: f32) {
+        self.gain = gain.max(0.0);
+        self.output();
+    }
+
+    pub(crate) fn stats(&self) -> ClippingStats {
+        self.stats
+    }
+
+    pub(crate) fn reset_stats(&mut self) {
+        self.stats = ClippingStats::default();
+    }
+
+    // The sum of the sources, clipped to full force
+    pub(crate) fn mix(&mut self) -> f32 {
+        let level = self
+            .sources
+            .iter()
+            .map(|(_, gain, level)| gain * level)
+            .sum::<f32>()
+            * self.gain;
+
+        let level = if level.is_nan() { 0.0 } else { level };
+        self.stats.record(level);
+
+        level.clamp(-1.0, 1.0)
+    }
+
+    // Mix the sources and send the force if it changed
+    pub(crate) fn output(&mut self) {
+        if self.slot.is_none() {
+            return;
+        }
+
+        let level = self.mix();
+
+        if self.sent != Some(level) {
+            if let Some(Ok(())) = self
+                .slot
+                .as_mut()
+                .map(|slot| slot.stream(Effect::Constant(level)))
+            {
+                self.sent = Some(level);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{
+        effects::{EffectKind, Slot},
+        mixer::{ClippingStats, Mixer},
+        periodic::{Periodic, Waveform},
+        simulator::Simulator,
+        Options,
+    };
+
+    fn add(mixer: &mut Mixer, gain: f32, level: f32) -> u64 {
+        mixer.next_id += 1;
+        mixer.sources.push((mixer.next_id, gain, level));
+        mixer.next_id
+    }
+
+    #[test]
+    fn test_mix() {
+        let mut mixer = Mixer::default();
+        let first = add(&mut mixer, 1.0, 0.5);
+        add(&mut mixer, 0.5, -0.5);
+
+        assert_eq!(mixer.mix(), 0.25);
+
+        mixer.gain = 2.0;
+        assert_eq!(mixer.mix(), 0.5);
+
+        assert!(mixer.set_level(first, 0.75));
+        assert_eq!(mixer.mix(), 1.0);
+        assert!(!mixer.set_level(10, 1.0));
+    }
+
+    #[test]
+    fn test_clipping_stats() {
+        let mut mixer = Mixer::default();
+        let id = add(&mut mixer, 1.0, 0.5);
+        add(&mut mixer, 1.0, 1.0);
+
+        assert_eq!(mixer.mix(), 1.0);
+        mixer.set_level(id, -0.5);
+        assert_eq!(mixer.mix(), 0.5);
+        mixer.set_level(id, 1.0);
+        assert_eq!(mixer.mix(), 1.0);
+
+        assert_eq!(
+            mixer.stats(),
+            ClippingStats {
+                mixes: 3,
+                clipped: 2,
+                max_excess: 1.0,
+                mean_excess: 0.75,
+            }
+        );
+    }
+
+    #[test]
+    fn test_sources() {
+        let sim = Simulator::new(Options::default());
+        let mut mixer = Mixer::default();
+
+        // the first source reserves a slot and the last one frees it
+        let first = mixer.register(sim.g29()).unwrap();
+        let second = mixer.register(sim.g29()).unwrap();
+        assert!(mixer.is_active());
+        assert_eq!(
+            sim.g29().active_effects(),
+            vec![(Slot::One, EffectKind::Constant)]
+        );
+
+        assert!(mixer.set_level(first, 0.5));
+        assert!(mixer.set_level(second, -1.0));
+        assert!(mixer.set_source_gain(second, 0.5));
+        assert_eq!(mixer.mix(), 0.0);
+        assert!(mixer.set_source_gain(second, -1.0));
+        assert_eq!(mixer.mix(), 0.5);
+
+        mixer.unregister(first);
+        assert!(!mixer.set_level(first, 1.0));
+        assert!(!mixer.set_source_gain(first, 1.0));
+        assert!(mixer.is_active());
+
+        mixer.unregister(second);
+        assert!(!mixer.is_active());
+        assert!(sim.g29().active_effects().is_empty());
+    }
+
+    #[test]
+    fn test_mixed_effects() {
+        let mut sim = Simulator::new(Options::default());
+        let square =
+            |magnitude| Periodic::new(Waveform::Square, magnitude, Duration::from_secs(10));
+
+        let first = sim.g29().play_periodic(square(0.5)).unwrap();
+        let second = sim.g29().play_periodic(square(0.5)).unwrap();
+        let force_loop = sim.g29().start_force_loop(500, |_, _| 0.25).unwrap();
+        assert_eq!(
+            sim.g29().active_effects(),
+            vec![(Slot::One, EffectKind::Constant)]
+        );
+
+        sim.wait(Duration::from_millis(50));
+        assert!(sim.g29().clipping_stats().clipped > 0);
+        assert!((sim.g29().clipping_stats().max_excess - 0.25).abs() < 1e-4);
+
+        // 0.5 + 0.5 * 0.5, at half strength
+        first.set_gain(0.5);
+        force_loop.set_gain(0.0);
+        sim.g29().clear_written_commands();
+        sim.g29().set_ffb_strength(0.5);
+        sim.g29().reset_clipping_stats();
+        assert_eq!(sim.g29().ffb_strength(), 0.5);
+        sim.wait(Duration::from_millis(20));
+
+        // the strength also turns down the auto-center
+        assert_eq!(sim.g29().clipping_stats().clipped, 0);
+        assert_eq!(
+            sim.g29().written_commands(),
+            vec![
+                [0x11, 0x08, 0xb0, 0x80, 0x00, 0x00, 0x00],
+                [0xfe, 0x0d, 0x04, 0x04, 0xff, 0x00, 0x00],
+            ]
+        );
+
+        // every other force is turned down too
+        sim.g29().set_constant_force(1.0, 2).unwrap();
+        assert_eq!(
+            sim.g29().written_commands().last(),
+            Some(&[0x21, 0x08, 0xc0, 0x80, 0x00, 0x00, 0x00])
+        );
+        sim.g29().set_constant_force(0.0, 2).unwrap();
+
+        drop((first, second, force_loop));
+        assert!(sim.g29().active_effects().is_empty());
+    }
+}
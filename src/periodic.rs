@@ -0,0 +1,526 @@
+use std::{
+    f32::consts::TAU,
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    clock::{Clock, Sleeper},
+    mixer::Mixer,
+    G29,
+};
+
+// How often the levels of the periodic effects are sent to the mixer
+pub(crate) const STREAM_INTERVAL: Duration = Duration::from_millis(4);
+
+///
+/// Waveform
+///
+/// The shape of a periodic effect. Every waveform starts at zero and rises, except `Square`
+/// which starts at its high level.
+///
+#[derive(Debug, PartialEq, Copy, Clone, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Waveform {
+    Sine,
+    Square,
+    Triangle,
+    SawtoothUp,
+    SawtoothDown,
+}
+
+impl Waveform {
+    /// The value of the waveform, -1.0 to 1.0, at a position in its cycle, 0.0 to 1.0.
+    pub fn value(&self, position: f32) -> f32 {
+        let position = position.rem_euclid(1.0);
+
+        match self {
+            Waveform::Sine => (position * TAU).sin(),
+            Waveform::Square if position < 0.5 => 1.0,
+            Waveform::Square => -1.0,
+            Waveform::Triangle if position < 0.25 => position * 4.0,
+            Waveform::Triangle if position < 0.75 => 2.0 - position * 4.0,
+            Waveform::Triangle => position * 4.0 - 4.0,
+            Waveform::SawtoothUp if position < 0.5 => position * 2.0,
+            Waveform::SawtoothUp => position * 2.0 - 2.0,
+            Waveform::SawtoothDown => -Waveform::SawtoothUp.value(position),
+        }
+    }
+}
+
+///
+/// Envelope
+///
+/// How the magnitude of a periodic effect ramps in and out
+/// - attack_level: `f32` - The magnitude at the start, 0.0 to 1.0 (default: `0.0`)
+/// - attack_time: `Duration` - How long the magnitude ramps from `attack_level` (default: `0`)
+/// - fade_level: `f32` - The magnitude at the end, 0.0 to 1.0 (default: `0.0`)
+/// - fade_time: `Duration` - How long the magnitude ramps to `fade_level` before the effect
+///   ends. Only used by effects with a duration (default: `0`)
+///
+/// The default envelope plays the effect at its full magnitude throughout.
+///
+#[derive(Debug, PartialEq, Copy, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Envelope {
+    pub attack_level: f32,
+    pub attack_time: Duration,
+    pub fade_level: f32,
+    pub fade_time: Duration,
+}
+
+///
+/// Periodic
+///
+/// A force that repeats a waveform, computed in software and sent to the wheel as a constant force
+/// - waveform: `Waveform` - The shape of the force
+/// - magnitude: `f32` - The peak of the force, 0.0 to 1.0
+/// - offset: `f32` - Added to the waveform, -1.0 (left) to 1.0 (right) (default: `0.0`)
+/// - period: `Duration` - The length of one cycle
+/// - phase: `f32` - Where in its cycle the waveform starts, in degrees (default: `0.0`)
+/// - duration: `Option<Duration>` - How long the effect plays, `None` plays until it is stopped
+///   (default: `None`)
+/// - envelope: `Envelope` - How the magnitude ramps in and out (default: `Envelope::default()`)
+///
+/// The force is clamped to -1.0 to 1.0.
+///
+/// # Example
+///
+/// ```rust
+/// use lib_g29::periodic::{Periodic, Waveform};
+/// use std::time::Duration;
+///
+/// // a rumble strip
+/// let rumble = Periodic {
+///     duration: Some(Duration::from_millis(400)),
+///     ..Periodic::new(Waveform::Square, 0.3, Duration::from_millis(40))
+/// };
+///
+/// let handle = g29.play_periodic(rumble)?;
+/// ```
+///
+#[derive(Debug, PartialEq, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Periodic {
+    pub waveform: Waveform,
+    pub magnitude: f32,
+    pub offset: f32,
+    pub period: Duration,
+    pub phase: f32,
+    pub duration: Option<Duration>,
+    pub envelope: Envelope,
+}
+
+impl Periodic {
+    /// A periodic effect with no offset, phase or envelope, playing until it is stopped.
+    pub fn new(waveform: Waveform, magnitude: f32, period: Duration) -> Periodic {
+        Periodic {
+            waveform,
+            magnitude,
+            offset: 0.0,
+            period,
+            phase: 0.0,
+            duration: None,
+            envelope: Envelope::default(),
+        }
+    }
+
+    /// The force `elapsed` after the effect started, -1.0 to 1.0. Zero once it has finished.
+    pub fn level(&self, elapsed: Duration) -> f32 {
+        if self.finished(elapsed) {
+            return 0.0;
+        }
+
+        let position = if self.period.is_zero() {
+            0.0
+        } else {
+            elapsed.as_secs_f32() / self.period.as_secs_f32()
+        };
+        let wave = self.waveform.value(position + self.phase / 360.0);
+
+        let level = self.offset + self.magnitude(elapsed) * wave;
+        if level.is_nan() {
+            0.0
+        } else {
+            level.clamp(-1.0, 1.0)
+        }
+    }
+
+    /// Returns `true` once the effect has played for its duration.
+    pub fn finished(&self, elapsed: Duration) -> bool {
+        self.duration.is_some_and(|duration| elapsed >= duration)
+    }
+
+    // The magnitude after the envelope
+    fn magnitude(&self, elapsed: Duration) -> f32 {
+        let envelope = &self.envelope;
+        let magnitude = self.magnitude.clamp(0.0, 1.0);
+
+        if elapsed < envelope.attack_time {
+            let progress = elapsed.as_secs_f32() / envelope.attack_time.as_secs_f32();
+            let attack_level = envelope.attack_level.clamp(0.0, 1.0);
+            return attack_level + (magnitude - attack_level) * progress;
+        }
+
+        if let Some(remaining) = self.duration.and_then(|d| d.checked_sub(elapsed)) {
+            if remaining < envelope.fade_time {
+                let progress = 1.0 - remaining.as_secs_f32() / envelope.fade_time.as_secs_f32();
+                let fade_level = envelope.fade_level.clamp(0.0, 1.0);
+                return magnitude + (fade_level - magnitude) * progress;
+            }
+        }
+
+        magnitude
+    }
+}
+
+// The periodic effects playing on a wheel and the thread streaming them.
+// Each effect is a source of the mixer, identified by its source id
+#[derive(Debug, Default)]
+pub(crate) struct PeriodicPlayer {
+    effects: Vec<(u64, Periodic, Instant)>,
+    clock: Clock,
+    running: Arc<AtomicBool>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl PeriodicPlayer {
+    fn update(&mut self, id: u64, effect: Periodic) -> bool {
+        match self.effects.iter_mut().find(|(i, ..)| *i == id) {
+            Some((_, current, _)) => {
+                *current = effect;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn remove(&mut self, id: u64) -> bool {
+        let count = self.effects.len();
+        self.effects.retain(|(i, ..)| *i != id);
+        self.effects.len() != count
+    }
+
+    fn contains(&self, id: u64) -> bool {
+        self.effects.iter().any(|(i, ..)| *i == id)
+    }
+
+    // The level of every effect, and the effects that finished, which are removed
+    fn levels(&mut self, now: Instant) -> (Vec<(u64, f32)>, Vec<u64>) {
+        let (finished, playing) =
+            self.effects
+                .iter()
+                .partition::<Vec<_>, _>(|(_, effect, started)| {
+                    effect.finished(now.duration_since(*started))
+                });
+
+        let levels = playing
+            .iter()
+            .map(|(id, effect, started)| (*id, effect.level(now.duration_since(*started))))
+            .collect();
+        let finished: Vec<u64> = finished.iter().map(|(id, ..)| *id).collect();
+
+        self.effects.retain(|(id, ..)| !finished.contains(id));
+
+        (levels, finished)
+    }
+}
+
+// Start an effect, starting the thread that streams the effects if it is not running.
+// Fails if the mixer has no slot and every slot is in use
+pub(crate) fn play(
+    g29: &G29,
+    player: &Arc<Mutex<PeriodicPlayer>>,
+    mixer: &Arc<Mutex<Mixer>>,
+    effect: Periodic,
+) -> std::io::Result<PeriodicHandle> {
+    let mut locked = player.lock().unwrap();
+    let id = mixer.lock().unwrap().register(g29)?;
+
+    if !locked.running.load(Ordering::Acquire) {
+        // the previous thread has finished, or is about to
+        if let Some(previous) = locked.thread.take() {
+            drop(locked);
+            previous.join().unwrap();
+            locked = player.lock().unwrap();
+        }
+
+        let g29 = g29.clone();
+        let stream_player = player.clone();
+        let stream_mixer = mixer.clone();
+        let running = Arc::new(AtomicBool::new(true));
+        let sleeper = g29.clock().register();
+        locked.clock = g29.clock();
+        locked.running = running.clone();
+        locked.thread = Some(thread::spawn(move || {
+            stream(g29, stream_player, stream_mixer, running, sleeper)
+        }));
+    }
+
+    let now = locked.clock.now();
+    locked.effects.push((id, effect, now));
+
+    Ok(PeriodicHandle {
+        player: player.clone(),
+        mixer: mixer.clone(),
+        id,
+    })
+}
+
+// Stop every effect and wait for the thread, before the wheel is released
+pub(crate) fn stop(player: &Arc<Mutex<PeriodicPlayer>>, mixer: &Arc<Mutex<Mixer>>) {
+    let (effects, thread) = {
+        let mut locked = player.lock().unwrap();
+        locked.running.store(false, Ordering::Release);
+        locked.clock.wake();
+        (std::mem::take(&mut locked.effects), locked.thread.take())
+    };
+
+    let mut mixer = mixer.lock().unwrap();
+    for (id, ..) in effects {
+        mixer.unregister(id);
+    }
+    drop(mixer);
+
+    if let Some(thread) = thread {
+        thread.join().unwrap();
+    }
+}
+
+// Send the levels to the mixer at a fixed rate until every effect has finished
+fn stream(
+    g29: G29,
+    player: Arc<Mutex<PeriodicPlayer>>,
+    mixer: Arc<Mutex<Mixer>>,
+    running: Arc<AtomicBool>,
+    sleeper: Sleeper,
+) {
+    let clock = g29.clock();
+    let mut next = clock.now();
+
+    loop {
+        {
+            let mut locked = player.lock().unwrap();
+            let mut mixer = mixer.lock().unwrap();
+
+            // the wheel disconnected or the slot was released
+            if !running.load(Ordering::Acquire) || !g29.connected() || !mixer.is_active() {
+                for (id, ..) in locked.effects.drain(..) {
+                    mixer.unregister(id);
+                }
+            }
+
+            let (levels, finished) = locked.levels(clock.now());
+            for (id, level) in levels {
+                mixer.set_level(id, level);
+            }
+            for id in finished {
+                mixer.unregister(id);
+            }
+
+            if locked.effects.is_empty() {
+                running.store(false, Ordering::Release);
+                return;
+            }
+
+            mixer.output();
+        }
+
+        next += STREAM_INTERVAL;
+        sleeper.sleep_until(next, &running);
+    }
+}
+
+///
+/// PeriodicHandle
+///
+/// A periodic effect playing on the wheel, returned by `G29::play_periodic`.
+///
+/// Dropping the handle stops the effect.
+///
+pub struct PeriodicHandle {
+    player: Arc<Mutex<PeriodicPlayer>>,
+    mixer: Arc<Mutex<Mixer>>,
+    id: u64,
+}
+
+impl fmt::Debug for PeriodicHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PeriodicHandle")
+            .field("id", &self.id)
+            .finish()
+    }
+}
+
+impl PeriodicHandle {
+    /// Returns `false` once the effect has played for its duration or was stopped.
+    pub fn is_playing(&self) -> bool {
+        self.player.lock().unwrap().contains(self.id)
+    }
+
+    /// Replace the parameters of the effect, keeping its start time.
+    /// Returns `false` if the effect is no longer playing.
+    pub fn update(&self, effect: Periodic) -> bool {
+        self.player.lock().unwrap().update(self.id, effect)
+    }
+
+    /// Set the gain of the effect in the mix, **0.0** and up (default: `1.0`).
+    /// Returns `false` if the effect is no longer playing.
+    pub fn set_gain(&self, gain: f32) -> bool {
+        self.mixer.lock().unwrap().set_source_gain(self.id, gain)
+    }
+
+    /// Stop the effect.
+    pub fn stop(&self) {
+        if self.player.lock().unwrap().remove(self.id) {
+            self.mixer.lock().unwrap().unregister(self.id);
+        }
+    }
+}
+
+impl Drop for PeriodicHandle {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use crate::{
+        effects::{EffectKind, Slot},
+        periodic::{Envelope, Periodic, PeriodicPlayer, Waveform},
+        simulator::Simulator,
+        Options,
+    };
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-4
+    }
+
+    #[test]
+    fn test_waveforms() {
+        for (position, sine, square, triangle, sawtooth) in [
+            (0.0, 0.0, 1.0, 0.0, 0.0),
+            (0.25, 1.0, 1.0, 1.0, 0.5),
+            (0.5, 0.0, -1.0, 0.0, -1.0),
+            (0.75, -1.0, -1.0, -1.0, -0.5),
+            (1.25, 1.0, 1.0, 1.0, 0.5),
+        ] {
+            assert!(close(Waveform::Sine.value(position), sine));
+            assert!(close(Waveform::Square.value(position), square));
+            assert!(close(Waveform::Triangle.value(position), triangle));
+            assert!(close(Waveform::SawtoothUp.value(position), sawtooth));
+            assert!(close(Waveform::SawtoothDown.value(position), -sawtooth));
+        }
+    }
+
+    #[test]
+    fn test_level() {
+        let ms = Duration::from_millis;
+        let effect = Periodic {
+            offset: 0.25,
+            phase: 90.0,
+            duration: Some(ms(1000)),
+            ..Periodic::new(Waveform::Sine, 0.5, ms(100))
+        };
+
+        // a quarter cycle in from the phase
+        assert!(close(effect.level(ms(0)), 0.75));
+        assert!(close(effect.level(ms(50)), -0.25));
+        assert!(!effect.finished(ms(999)));
+        assert!(effect.finished(ms(1000)));
+        assert_eq!(effect.level(ms(1000)), 0.0);
+
+        let clipped = Periodic {
+            offset: 0.8,
+            ..Periodic::new(Waveform::Square, 1.0, ms(100))
+        };
+        assert_eq!(clipped.level(ms(0)), 1.0);
+    }
+
+    #[test]
+    fn test_envelope() {
+        let ms = Duration::from_millis;
+        let effect = Periodic {
+            duration: Some(ms(1000)),
+            envelope: Envelope {
+                attack_level: 0.0,
+                attack_time: ms(200),
+                fade_level: 0.2,
+                fade_time: ms(400),
+            },
+            ..Periodic::new(Waveform::Square, 1.0, ms(1))
+        };
+
+        assert!(close(effect.level(ms(0)), 0.0));
+        assert!(close(effect.level(ms(100)), 0.5));
+        assert!(close(effect.level(ms(400)), 1.0));
+        assert!(close(effect.level(ms(800)), 0.6));
+    }
+
+    #[test]
+    fn test_player_levels() {
+        let ms = Duration::from_millis;
+        let mut player = PeriodicPlayer::default();
+        let start = Instant::now();
+
+        let short = Periodic {
+            duration: Some(ms(100)),
+            ..Periodic::new(Waveform::Square, 0.5, ms(1000))
+        };
+        player.effects.push((1, short, start));
+        player
+            .effects
+            .push((2, Periodic::new(Waveform::Square, 0.75, ms(1000)), start));
+
+        assert_eq!(player.levels(start), (vec![(1, 0.5), (2, 0.75)], vec![]));
+        assert_eq!(player.levels(start + ms(200)), (vec![(2, 0.75)], vec![1]));
+        assert!(!player.contains(1));
+        assert!(player.contains(2));
+    }
+
+    #[test]
+    fn test_play_periodic() {
+        let mut sim = Simulator::new(Options::default());
+        let periodic = Periodic::new(Waveform::Square, 0.5, Duration::from_secs(10));
+
+        let handle = sim.g29().play_periodic(periodic).unwrap();
+        assert_eq!(
+            sim.g29().active_effects(),
+            vec![(Slot::One, EffectKind::Constant)]
+        );
+
+        sim.wait(Duration::from_millis(50));
+        assert!(handle.is_playing());
+        assert_eq!(
+            sim.g29().written_commands().last(),
+            Some(&[0x11, 0x08, 0xc0, 0x80, 0x00, 0x00, 0x00])
+        );
+
+        // an effect with a duration ends on the first tick after it, ticks are 4 ms apart from 0
+        let short = Periodic {
+            duration: Some(Duration::from_millis(20)),
+            ..Periodic::new(Waveform::Square, 0.25, Duration::from_secs(10))
+        };
+        let short = sim.g29().play_periodic(short).unwrap();
+        sim.wait(Duration::from_millis(20));
+        assert!(short.is_playing());
+        sim.wait(Duration::from_millis(4));
+        assert!(!short.is_playing());
+
+        drop(handle);
+        sim.wait(Duration::from_millis(50));
+        assert!(sim.g29().active_effects().is_empty());
+        assert_eq!(
+            sim.g29().written_commands().last(),
+            Some(&[0x13, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00])
+        );
+    }
+}
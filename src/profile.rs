@@ -0,0 +1,242 @@
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use crate::axis::{Axis, AxisCalibration, AxisConfig};
+
+// The extension of profile files
+const PROFILE_EXTENSION: &str = "g29profile";
+
+///
+/// Profile
+///
+/// Per-wheel settings that are saved to a file named after the wheel's serial number.
+/// - serial: `String` - The serial number of the wheel the profile belongs to
+/// - axes: `AxisConfig` - The orientation, calibration and response curve of every axis
+/// - steering_trim: `f32` - The steering offset in degrees, see `G29::set_steering_trim`
+///
+/// Profiles are stored as plain text, one `key = value` setting per line.
+///
+/// # Example
+///
+/// ```rust
+/// use lib_g29::profile::Profile;
+/// use std::path::Path;
+///
+/// let profile = Profile::load(Path::new("profiles"), "0123456789")?;
+/// g29.set_axis_config(profile.axes);
+/// ```
+///
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Profile {
+    pub serial: String,
+    pub axes: AxisConfig,
+    pub steering_trim: f32,
+}
+
+impl Profile {
+    /// Get the path of the profile for a serial number in a directory.
+    ///
+    /// # Errors
+    /// Returns an `InvalidInput` error if the serial number is empty, so wheels without one do not
+    /// share a profile.
+    pub fn path(dir: &Path, serial: &str) -> io::Result<PathBuf> {
+        if serial.trim().is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "profile -> The wheel has no serial number",
+            ));
+        }
+
+        let file_name: String = serial
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+
+        Ok(dir.join(file_name).with_extension(PROFILE_EXTENSION))
+    }
+
+    /// Load the profile for a serial number from a directory.
+    pub fn load(dir: &Path, serial: &str) -> io::Result<Profile> {
+        fs::read_to_string(Profile::path(dir, serial)?)?.parse()
+    }
+
+    /// Save the profile to a directory, returning the path of the file written.
+    pub fn save(&self, dir: &Path) -> io::Result<PathBuf> {
+        let path = Profile::path(dir, &self.serial)?;
+
+        fs::create_dir_all(dir)?;
+        fs::write(&path, self.to_string())?;
+
+        Ok(path)
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "# lib-g29 profile")?;
+        writeln!(f, "serial = {}", self.serial)?;
+        writeln!(f, "steering_trim = {}", self.steering_trim)?;
+
+        for axis in Axis::ALL {
+            let settings = self.axes.settings(axis);
+
+            writeln!(f, "{}.inverted = {}", axis.name(), settings.inverted)?;
+            writeln!(f, "{}.curve = {}", axis.name(), settings.curve)?;
+
+            if let Some(calibration) = settings.calibration {
+                writeln!(
+                    f,
+                    "{}.calibration = {},{},{}",
+                    axis.name(),
+                    calibration.min,
+                    calibration.max,
+                    calibration.rest
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Profile {
+    type Err = io::Error;
+
+    fn from_str(text: &str) -> io::Result<Profile> {
+        let mut profile = Profile::default();
+
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (key, value) = line
+                .split_once('=')
+                .map(|(key, value)| (key.trim(), value.trim()))
+                .ok_or_else(|| invalid(line))?;
+
+            if key == "serial" {
+                profile.serial = value.to_string();
+                continue;
+            }
+
+            if key == "steering_trim" {
+                profile.steering_trim = value.parse().map_err(|_| invalid(line))?;
+                continue;
+            }
+
+            let (axis, setting) = key
+                .split_once('.')
+                .and_then(|(axis, setting)| Some((Axis::from_name(axis)?, setting)))
+                .ok_or_else(|| invalid(line))?;
+
+            let settings = profile.axes.settings_mut(axis);
+
+            match setting {
+                "inverted" => settings.inverted = value.parse().map_err(|_| invalid(line))?,
+                "curve" => settings.curve = value.parse().map_err(|_| invalid(line))?,
+                "calibration" => {
+                    let values = value
+                        .split(',')
+                        .map(|v| v.trim().parse())
+                        .collect::<Result<Vec<u16>, _>>()
+                        .map_err(|_| invalid(line))?;
+
+                    let [min, max, rest] = values[..] else {
+                        return Err(invalid(line));
+                    };
+
+                    settings.calibration = Some(AxisCalibration { min, max, rest });
+                }
+                _ => return Err(invalid(line)),
+            }
+        }
+
+        Ok(profile)
+    }
+}
+
+fn invalid(line: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("profile -> Invalid setting: {}", line),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::axis::{Axis, AxisCalibration};
+    use crate::curve::ResponseCurve;
+    use crate::profile::Profile;
+
+    fn get_test_profile() -> Profile {
+        let mut profile = Profile {
+            serial: "G29-0001".to_string(),
+            steering_trim: -2.5,
+            ..Default::default()
+        };
+        profile.axes.set_inverted(Axis::ShifterY, true);
+        profile
+            .axes
+            .set_curve(Axis::Throttle, ResponseCurve::Gamma(1.5))
+            .unwrap();
+        profile
+            .axes
+            .set_curve(
+                Axis::Steering,
+                ResponseCurve::Lookup(vec![(0.0, 0.0), (0.5, 0.3), (1.0, 1.0)]),
+            )
+            .unwrap();
+        profile.axes.settings_mut(Axis::Brake).calibration = Some(AxisCalibration {
+            min: 12,
+            max: 251,
+            rest: 250,
+        });
+
+        profile
+    }
+
+    #[test]
+    fn test_profile_round_trip() {
+        let profile = get_test_profile();
+        let parsed: Profile = profile.to_string().parse().unwrap();
+
+        assert_eq!(parsed, profile);
+    }
+
+    #[test]
+    fn test_profile_invalid() {
+        assert!("throttle.inverted = maybe".parse::<Profile>().is_err());
+        assert!("pedal.inverted = true".parse::<Profile>().is_err());
+        assert!("brake.calibration = 1,2".parse::<Profile>().is_err());
+        assert!("brake.curve = cubic".parse::<Profile>().is_err());
+        assert!("brake.curve = gamma -1".parse::<Profile>().is_err());
+        assert!("serial".parse::<Profile>().is_err());
+        assert!("steering_trim = left".parse::<Profile>().is_err());
+    }
+
+    #[test]
+    fn test_profile_path() {
+        let dir = std::path::Path::new("profiles");
+        let path = Profile::path(dir, "AB 12/3").unwrap();
+
+        assert_eq!(path, std::path::Path::new("profiles/AB_12_3.g29profile"));
+        assert!(Profile::path(dir, "").is_err());
+        assert!(Profile::default().save(dir).is_err());
+    }
+
+    #[test]
+    fn test_profile_save_load() {
+        let dir = std::env::temp_dir().join(format!("lib-g29-profile-test-{}", std::process::id()));
+        let profile = get_test_profile();
+
+        let path = profile.save(&dir).unwrap();
+        assert_eq!(path, Profile::path(&dir, &profile.serial).unwrap());
+        assert_eq!(Profile::load(&dir, &profile.serial).unwrap(), profile);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,439 @@
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+    sync::mpsc::{self, Sender},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crate::{effects::AutoCenter, Frame, Options, FRAME_SIZE};
+
+// Identifies a recording file
+const MAGIC: &[u8; 4] = b"G29R";
+// The version of the recording format written by this crate
+const VERSION: u16 = 2;
+// Version 1 headers end before the advanced auto-center
+const VERSION_1: u16 = 1;
+
+const INPUT_RECORD: u8 = 0;
+const OUTPUT_RECORD: u8 = 1;
+
+///
+/// DeviceDetails
+///
+/// Identifies the wheel a session was recorded from
+/// - vendor_id: `u16` - The USB vendor id
+/// - product_id: `u16` - The USB product id
+/// - serial_number: `Option<String>` - The serial number, if the wheel reports one
+/// - product: `Option<String>` - The product name, if the wheel reports one
+///
+#[derive(Debug, PartialEq, Clone, Eq, Hash, Default)]
+pub struct DeviceDetails {
+    pub vendor_id: u16,
+    pub product_id: u16,
+    pub serial_number: Option<String>,
+    pub product: Option<String>,
+}
+
+///
+/// RecordingHeader
+///
+/// The start of every recording
+/// - version: `u16` - The version of the recording format
+/// - device: `DeviceDetails` - The wheel the session was recorded from
+/// - options: `Options` - The options the wheel was connected with
+///
+#[derive(Debug, PartialEq, Clone)]
+pub struct RecordingHeader {
+    pub version: u16,
+    pub device: DeviceDetails,
+    pub options: Options,
+}
+
+///
+/// Record
+///
+/// A single frame or command in a recording.
+/// Timestamps are measured from the start of the recording.
+///
+#[derive(Debug, PartialEq, Copy, Clone, Eq, Hash)]
+pub enum Record {
+    /// A frame read from the wheel
+    Input { timestamp: Duration, frame: Frame },
+    /// A command written to the wheel
+    Output {
+        timestamp: Duration,
+        command: [u8; 7],
+    },
+}
+
+impl Record {
+    /// When the record was captured, relative to the start of the recording.
+    pub fn timestamp(&self) -> Duration {
+        match self {
+            Record::Input { timestamp, .. } | Record::Output { timestamp, .. } => *timestamp,
+        }
+    }
+}
+
+///
+/// Recording
+///
+/// A session read back from a recording file.
+///
+/// The file starts with the magic bytes `G29R`, a little-endian `u16` version and the header.
+/// Version 1 files, written before the header held the advanced auto-center, are read without it.
+/// Every record after that is a kind byte (`0` input, `1` output), a little-endian `u64`
+/// timestamp in microseconds and the 12 byte frame or 7 byte command.
+///
+/// # Example
+///
+/// ```rust
+/// use lib_g29::recording::{Record, Recording};
+///
+/// let recording = Recording::read(Path::new("session.g29rec"))?;
+///
+/// for record in recording.records {
+///     if let Record::Input { timestamp, frame } = record {
+///         println!("{:?}: {:?}", timestamp, frame);
+///     }
+/// }
+/// ```
+///
+#[derive(Debug, PartialEq, Clone)]
+pub struct Recording {
+    pub header: RecordingHeader,
+    pub records: Vec<Record>,
+}
+
+impl Recording {
+    /// Read a recording from a file.
+    pub fn read(path: &Path) -> io::Result<Recording> {
+        let mut reader = BufReader::new(File::open(path)?);
+
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("not a recording"));
+        }
+
+        let version = read_u16(&mut reader)?;
+        if version != VERSION && version != VERSION_1 {
+            return Err(invalid("unsupported recording version"));
+        }
+
+        let header = RecordingHeader {
+            version,
+            device: DeviceDetails {
+                vendor_id: read_u16(&mut reader)?,
+                product_id: read_u16(&mut reader)?,
+                serial_number: read_string(&mut reader)?,
+                product: read_string(&mut reader)?,
+            },
+            options: Options {
+                debug: read_u8(&mut reader)? == 1,
+                range: read_u16(&mut reader)?,
+                auto_center: [read_u8(&mut reader)?, read_u8(&mut reader)?],
+                auto_center_enabled: read_u8(&mut reader)? == 1,
+                auto_center_advanced: match version {
+                    VERSION_1 => None,
+                    _ => read_auto_center(&mut reader)?,
+                },
+            },
+        };
+
+        let mut records = Vec::new();
+        let mut kind = [0u8; 1];
+
+        while reader.read(&mut kind)? == 1 {
+            let mut timestamp = [0u8; 8];
+            reader.read_exact(&mut timestamp)?;
+            let timestamp = Duration::from_micros(u64::from_le_bytes(timestamp));
+
+            records.push(match kind[0] {
+                INPUT_RECORD => {
+                    let mut frame = [0u8; FRAME_SIZE];
+                    reader.read_exact(&mut frame)?;
+                    Record::Input { timestamp, frame }
+                }
+                OUTPUT_RECORD => {
+                    let mut command = [0u8; 7];
+                    reader.read_exact(&mut command)?;
+                    Record::Output { timestamp, command }
+                }
+                _ => return Err(invalid("unknown record kind")),
+            });
+        }
+
+        Ok(Recording { header, records })
+    }
+}
+
+///
+/// Recorder
+///
+/// Writes frames and commands to a recording file on its own thread,
+/// so recording adds no file IO to the thread reading the wheel.
+///
+#[derive(Debug)]
+pub(crate) struct Recorder {
+    started: Instant,
+    sender: Sender<Record>,
+    writer_handle: JoinHandle<io::Result<()>>,
+}
+
+impl Recorder {
+    /// Create a recording file and write its header.
+    pub(crate) fn start(
+        path: &Path,
+        device: &DeviceDetails,
+        options: &Options,
+    ) -> io::Result<Recorder> {
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        writer.write_all(&device.vendor_id.to_le_bytes())?;
+        writer.write_all(&device.product_id.to_le_bytes())?;
+        write_string(&mut writer, device.serial_number.as_deref())?;
+        write_string(&mut writer, device.product.as_deref())?;
+        writer.write_all(&[options.debug as u8])?;
+        writer.write_all(&options.range.to_le_bytes())?;
+        writer.write_all(&options.auto_center)?;
+        writer.write_all(&[options.auto_center_enabled as u8])?;
+        write_auto_center(&mut writer, options.auto_center_advanced)?;
+
+        let (sender, receiver) = mpsc::channel::<Record>();
+
+        let writer_handle = thread::spawn(move || {
+            for record in receiver {
+                let timestamp = (record.timestamp().as_micros() as u64).to_le_bytes();
+
+                match record {
+                    Record::Input { frame, .. } => {
+                        writer.write_all(&[INPUT_RECORD])?;
+                        writer.write_all(&timestamp)?;
+                        writer.write_all(&frame)?;
+                    }
+                    Record::Output { command, .. } => {
+                        writer.write_all(&[OUTPUT_RECORD])?;
+                        writer.write_all(&timestamp)?;
+                        writer.write_all(&command)?;
+                    }
+                }
+            }
+
+            writer.flush()
+        });
+
+        Ok(Recorder {
+            started: Instant::now(),
+            sender,
+            writer_handle,
+        })
+    }
+
+    /// Queue a frame read from the wheel.
+    pub(crate) fn input(&self, frame: &Frame) {
+        let _ = self.sender.send(Record::Input {
+            timestamp: self.started.elapsed(),
+            frame: *frame,
+        });
+    }
+
+    /// Queue a command written to the wheel.
+    pub(crate) fn output(&self, command: &[u8; 7]) {
+        let _ = self.sender.send(Record::Output {
+            timestamp: self.started.elapsed(),
+            command: *command,
+        });
+    }
+
+    /// Write every queued record and close the file.
+    pub(crate) fn finish(self) -> io::Result<()> {
+        drop(self.sender);
+
+        self.writer_handle
+            .join()
+            .unwrap_or_else(|_| Err(invalid("recording writer panicked")))
+    }
+}
+
+fn read_u8(reader: &mut impl Read) -> io::Result<u8> {
+    let mut bytes = [0u8; 1];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes[0])
+}
+
+fn read_u16(reader: &mut impl Read) -> io::Result<u16> {
+    let mut bytes = [0u8; 2];
+    reader.read_exact(&mut bytes)?;
+    Ok(u16::from_le_bytes(bytes))
+}
+
+// strings are a u16 length followed by utf-8, with 0xffff meaning none
+fn read_string(reader: &mut impl Read) -> io::Result<Option<String>> {
+    let length = read_u16(reader)?;
+    if length == u16::MAX {
+        return Ok(None);
+    }
+
+    let mut bytes = vec![0u8; length as usize];
+    reader.read_exact(&mut bytes)?;
+
+    String::from_utf8(bytes)
+        .map(Some)
+        .map_err(|_| invalid("invalid string"))
+}
+
+// the advanced auto-center is a presence byte followed by one byte per field, in order
+fn read_auto_center(reader: &mut impl Read) -> io::Result<Option<AutoCenter>> {
+    if read_u8(reader)? == 0 {
+        return Ok(None);
+    }
+
+    Ok(Some(AutoCenter {
+        clockwise_angle: read_u8(reader)?,
+        counter_clockwise_angle: read_u8(reader)?,
+        clockwise_force: read_u8(reader)?,
+        counter_clockwise_force: read_u8(reader)?,
+        reverse: read_u8(reader)? == 1,
+        centering_force: read_u8(reader)?,
+    }))
+}
+
+fn write_auto_center(writer: &mut impl Write, value: Option<AutoCenter>) -> io::Result<()> {
+    match value {
+        Some(auto_center) => writer.write_all(&[
+            1,
+            auto_center.clockwise_angle,
+            auto_center.counter_clockwise_angle,
+            auto_center.clockwise_force,
+            auto_center.counter_clockwise_force,
+            auto_center.reverse as u8,
+            auto_center.centering_force,
+        ]),
+        None => writer.write_all(&[0]),
+    }
+}
+
+fn write_string(writer: &mut impl Write, value: Option<&str>) -> io::Result<()> {
+    match value {
+        Some(value) => {
+            // cut long strings after the last whole character, so they read back as UTF-8
+            let length = value
+                .char_indices()
+                .map(|(index, char)| index + char.len_utf8())
+                .take_while(|end| *end < u16::MAX as usize)
+                .last()
+                .unwrap_or(0);
+            let bytes = &value.as_bytes()[..length];
+            writer.write_all(&(bytes.len() as u16).to_le_bytes())?;
+            writer.write_all(bytes)
+        }
+        None => writer.write_all(&u16::MAX.to_le_bytes()),
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("recording -> {}", message),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::effects::AutoCenter;
+    use crate::recording::{read_string, write_string, DeviceDetails, Record, Recorder, Recording};
+    use crate::Options;
+
+    #[test]
+    fn test_record_and_read() {
+        let path = std::env::temp_dir().join(format!(
+            "lib-g29-recording-test-{}.g29rec",
+            std::process::id()
+        ));
+        let device = DeviceDetails {
+            vendor_id: 1133,
+            product_id: 49743,
+            serial_number: Some("0123".to_string()),
+            product: None,
+        };
+        let options = Options {
+            range: 540,
+            auto_center_advanced: Some(AutoCenter {
+                clockwise_force: 0x0f,
+                reverse: true,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let recorder = Recorder::start(&path, &device, &options).unwrap();
+        recorder.input(&[1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12]);
+        recorder.output(&[0xf8, 0x12, 0x1f, 0x00, 0x00, 0x00, 0x01]);
+        recorder.finish().unwrap();
+
+        let recording = Recording::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(recording.header.version, 2);
+        assert_eq!(recording.header.device, device);
+        assert_eq!(recording.header.options, options);
+        assert_eq!(recording.records.len(), 2);
+        assert!(matches!(
+            recording.records[0],
+            Record::Input { frame, .. } if frame == [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12]
+        ));
+        assert!(matches!(
+            recording.records[1],
+            Record::Output { command, .. } if command == [0xf8, 0x12, 0x1f, 0x00, 0x00, 0x00, 0x01]
+        ));
+        assert!(recording.records[0].timestamp() <= recording.records[1].timestamp());
+    }
+
+    #[test]
+    fn test_read_invalid() {
+        let path = std::env::temp_dir().join(format!(
+            "lib-g29-recording-invalid-{}.g29rec",
+            std::process::id()
+        ));
+        std::fs::write(&path, b"not a recording").unwrap();
+
+        assert!(Recording::read(&path).is_err());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_read_version_1() {
+        let path = std::env::temp_dir().join(format!(
+            "lib-g29-recording-version-1-{}.g29rec",
+            std::process::id()
+        ));
+        let mut bytes = b"G29R".to_vec();
+        bytes.extend([0x01, 0x00, 0x6d, 0x04, 0x4f, 0xc2, 0xff, 0xff, 0xff, 0xff]);
+        bytes.extend([0x00, 0x84, 0x03, 0x07, 0xff, 0x01]);
+        std::fs::write(&path, bytes).unwrap();
+
+        let recording = Recording::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(recording.header.version, 1);
+        assert_eq!(recording.header.options, Options::default());
+        assert!(recording.records.is_empty());
+    }
+
+    #[test]
+    fn test_long_string() {
+        // the last character would straddle the length limit
+        let value = format!("{}é", "a".repeat(u16::MAX as usize - 2));
+        let mut bytes = Vec::new();
+        write_string(&mut bytes, Some(&value)).unwrap();
+
+        let read = read_string(&mut bytes.as_slice()).unwrap().unwrap();
+        assert_eq!(read, "a".repeat(u16::MAX as usize - 2));
+    }
+}
@@ -0,0 +1,187 @@
+use std::time::Duration;
+
+use crate::{
+    recording::{Record, Recording},
+    Frame,
+};
+
+///
+/// ReplaySpeed
+///
+/// How fast `G29::replay` feeds the recorded frames
+///
+#[derive(Debug, PartialEq, Copy, Clone, Default)]
+pub enum ReplaySpeed {
+    /// Frames arrive with the same timing as when they were recorded
+    #[default]
+    RealTime,
+    /// Frames arrive this many times faster than they were recorded.
+    /// `f32::INFINITY` feeds every frame as fast as possible
+    Accelerated(f32),
+    /// Frames only arrive when `G29::step` is called
+    Stepped,
+}
+
+impl ReplaySpeed {
+    // How many times faster than recorded, or `None` when stepped
+    pub(crate) fn factor(&self) -> Option<f64> {
+        match self {
+            ReplaySpeed::RealTime => Some(1.0),
+            ReplaySpeed::Accelerated(factor) => Some(*factor as f64),
+            ReplaySpeed::Stepped => None,
+        }
+    }
+}
+
+// The input frames of a recording and how far the replay has got.
+// A frame is taken by `next` and only counts towards `finished` once it has been processed
+#[derive(Debug)]
+pub(crate) struct Playback {
+    frames: Vec<(Duration, Frame)>,
+    position: usize,
+    processed: usize,
+}
+
+impl Playback {
+    pub(crate) fn new(recording: &Recording) -> Playback {
+        let frames = recording
+            .records
+            .iter()
+            .filter_map(|record| match record {
+                Record::Input { timestamp, frame } => Some((*timestamp, *frame)),
+                Record::Output { .. } => None,
+            })
+            .collect();
+
+        Playback {
+            frames,
+            position: 0,
+            processed: 0,
+        }
+    }
+
+    pub(crate) fn next(&mut self) -> Option<(Duration, Frame)> {
+        let next = self.frames.get(self.position).copied();
+
+        if next.is_some() {
+            self.position += 1;
+        }
+
+        next
+    }
+
+    pub(crate) fn processed(&mut self) {
+        self.processed += 1;
+    }
+
+    pub(crate) fn finished(&self) -> bool {
+        self.processed >= self.frames.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        path::PathBuf,
+        thread::sleep,
+        time::{Duration, Instant},
+    };
+
+    use crate::{
+        recording::{DeviceDetails, Recorder},
+        replay::ReplaySpeed,
+        GearSelector, Led, Options, G29,
+    };
+
+    // neutral, nothing pressed, wheel centered
+    const IDLE: [u8; 12] = [8, 0, 0, 0, 0, 128, 255, 255, 255, 128, 128, 0];
+
+    fn record_test_session(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("{}-{}.g29rec", name, std::process::id()));
+        let recorder = Recorder::start(
+            &path,
+            &DeviceDetails::default(),
+            &Options {
+                range: 540,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let mut x_pressed = IDLE;
+        x_pressed[0] |= 0x10;
+        let mut first_gear = IDLE;
+        first_gear[2] = 0x01;
+
+        recorder.input(&IDLE);
+        recorder.output(&[0xf8, 0x12, 0x00, 0x00, 0x00, 0x00, 0x01]);
+        recorder.input(&x_pressed);
+        recorder.input(&first_gear);
+        recorder.finish().unwrap();
+
+        path
+    }
+
+    #[test]
+    fn test_replay_stepped() {
+        let path = record_test_session("lib-g29-replay-stepped");
+        let mut g29 = G29::replay(&path, ReplaySpeed::Stepped).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(g29.connected());
+        assert_eq!(g29.options().range, 540);
+
+        assert!(g29.step());
+        assert!(!g29.x_button());
+
+        assert!(g29.step());
+        assert!(g29.x_button());
+        assert_eq!(g29.snapshot().sequence, 2);
+
+        assert!(!g29.replay_finished());
+        assert!(g29.step());
+        assert!(!g29.x_button());
+        assert_eq!(g29.gear_selector(), GearSelector::First);
+
+        assert!(!g29.step());
+        assert!(g29.replay_finished());
+    }
+
+    #[test]
+    fn test_replay_captures_commands() {
+        let path = record_test_session("lib-g29-replay-commands");
+        let mut g29 = G29::replay(&path, ReplaySpeed::Stepped).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        // range and auto-center are set like on a real wheel
+        assert!(!g29.written_commands().is_empty());
+
+        g29.clear_written_commands();
+        g29.set_leds(Led::All);
+
+        assert_eq!(
+            g29.written_commands(),
+            vec![[0xf8, 0x12, 0x1f, 0x00, 0x00, 0x00, 0x01]]
+        );
+
+        g29.disconnect();
+        assert!(!g29.connected());
+    }
+
+    #[test]
+    fn test_replay_accelerated() {
+        let path = record_test_session("lib-g29-replay-accelerated");
+        let g29 = G29::replay(&path, ReplaySpeed::Accelerated(f32::INFINITY)).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let started = Instant::now();
+        while !g29.replay_finished() && started.elapsed() < Duration::from_secs(5) {
+            sleep(Duration::from_millis(1));
+        }
+
+        // finished means the last frame has been processed, not just taken
+        assert!(g29.replay_finished());
+        assert_eq!(g29.gear_selector(), GearSelector::First);
+        assert!(G29::replay(&path, ReplaySpeed::Accelerated(0.0)).is_err());
+    }
+}
@@ -0,0 +1,943 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{clock::Clock, command::Command, effects::Slot, Button, G29State, G29};
+
+// How often the watchdog ramps forces down and rate limited forces catch up
+pub(crate) const WATCHDOG_INTERVAL: Duration = Duration::from_millis(4);
+
+///
+/// SafetySettings
+///
+/// Limits applied to every force sent to the wheel
+/// - max_torque: `f32` - The strongest force allowed, 0.0 to 1.0 (full force). Every force,
+///   including auto-center, is scaled down by it (default: `1.0`)
+/// - max_torque_rate: `Option<f32>` - How fast a force may change, in full force per second.
+///   Faster changes are spread out over time. It limits constant forces, including periodic
+///   effects and force loops, the coefficients of friction, the coefficients and saturation of
+///   springs and dampers, and the auto-center forces. The center and deadband of springs and the
+///   auto-center turning multiplier and angles change at once (default: `None`)
+/// - watchdog_timeout: `Option<Duration>` - Ramp every force, including auto-center, to zero if
+///   the application sends no force and does not call `G29::refresh_watchdog` for this long.
+///   Periodic effects, force loops and auto-center do not refresh it (default: `None`)
+/// - ramp_down: `Duration` - How long the watchdog takes to ramp forces to zero
+///   (default: `500ms`)
+/// - emergency_stop: `Vec<Button>` - Buttons that stop every force when pressed together,
+///   see `G29::emergency_stop`. Empty for no chord (default: empty)
+///
+/// # Example
+///
+/// ```rust
+/// use lib_g29::{safety::SafetySettings, Button};
+/// use std::time::Duration;
+///
+/// g29.set_safety(SafetySettings {
+///     max_torque: 0.6,
+///     max_torque_rate: Some(4.0),
+///     watchdog_timeout: Some(Duration::from_millis(100)),
+///     emergency_stop: vec![Button::L3, Button::R3],
+///     ..Default::default()
+/// });
+/// ```
+///
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SafetySettings {
+    pub max_torque: f32,
+    pub max_torque_rate: Option<f32>,
+    pub watchdog_timeout: Option<Duration>,
+    pub ramp_down: Duration,
+    pub emergency_stop: Vec<Button>,
+}
+
+impl Default for SafetySettings {
+    fn default() -> Self {
+        SafetySettings {
+            max_torque: 1.0,
+            max_torque_rate: None,
+            watchdog_timeout: None,
+            ramp_down: Duration::from_millis(500),
+            emergency_stop: Vec::new(),
+        }
+    }
+}
+
+impl SafetySettings {
+    // The watchdog thread is only needed to ramp down or to catch up with rate limited forces
+    fn needs_thread(&self) -> bool {
+        self.watchdog_timeout.is_some() || self.max_torque_rate.is_some()
+    }
+
+    /// Returns `true` if the buttons of the emergency stop chord are all pressed.
+    pub fn emergency_stop_pressed(&self, state: &G29State) -> bool {
+        !self.emergency_stop.is_empty()
+            && self
+                .emergency_stop
+                .iter()
+                .all(|button| state.button(*button))
+    }
+}
+
+// Where the auto-center is kept, after the 4 slots
+const AUTO_CENTER: usize = 4;
+
+// Applies the limits and the strength to the commands sent to the wheel. Keeps the force last requested in each
+// slot and for auto-center, so the watchdog can ramp it down and rate limited forces can catch up
+#[derive(Debug)]
+pub(crate) struct Safety {
+    settings: SafetySettings,
+    // the master strength, see `G29::set_ffb_strength`
+    strength: f32,
+    requested: [Option<Command>; 5],
+    // the strengths last sent in each slot and when, for the rate limit
+    sent: [Option<([f32; 3], Instant)>; 5],
+    refreshed: Instant,
+    // 1.0 until the watchdog times out, then falls to 0.0
+    ramp: f32,
+    stopped: bool,
+    clock: Clock,
+    running: Arc<AtomicBool>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Default for Safety {
+    fn default() -> Self {
+        Safety {
+            settings: SafetySettings::default(),
+            strength: 1.0,
+            requested: [None; 5],
+            sent: [None; 5],
+            refreshed: Instant::now(),
+            ramp: 1.0,
+            stopped: false,
+            clock: Clock::default(),
+            running: Arc::new(AtomicBool::new(false)),
+            thread: None,
+        }
+    }
+}
+
+impl Safety {
+    pub(crate) fn settings(&self) -> &SafetySettings {
+        &self.settings
+    }
+
+    // Returns the commands resending the requested forces within the new limits
+    pub(crate) fn set_settings(&mut self, settings: SafetySettings, now: Instant) -> Vec<Command> {
+        self.settings = settings;
+        self.refresh(now);
+
+        self.resend(now)
+    }
+
+    pub(crate) fn strength(&self) -> f32 {
+        self.strength
+    }
+
+    // Returns the commands resending the requested forces at the new strength
+    pub(crate) fn set_strength(&mut self, strength: f32, now: Instant) -> Vec<Command> {
+        self.strength = if strength.is_nan() {
+            0.0
+        } else {
+            strength.clamp(0.0, 1.0)
+        };
+
+        self.resend(now)
+    }
+
+    pub(crate) fn refresh(&mut self, now: Instant) {
+        self.refreshed = now;
+        self.ramp = 1.0;
+    }
+
+    pub(crate) fn tripped(&self) -> bool {
+        self.ramp < 1.0
+    }
+
+    pub(crate) fn stopped(&self) -> bool {
+        self.stopped
+    }
+
+    // Forget every force in the slots and refuse new ones until `clear_stop`
+    pub(crate) fn stop(&mut self) {
+        self.stopped = true;
+        for slot in 0..AUTO_CENTER {
+            self.requested[slot] = None;
+            self.sent[slot] = None;
+        }
+    }
+
+    pub(crate) fn clear_stop(&mut self) {
+        self.stopped = false;
+    }
+
+    // The commands to send in place of `command`. A force refreshes the watchdog
+    pub(crate) fn filter(&mut self, command: Command, now: Instant) -> Vec<Command> {
+        self.apply(command, now, true)
+    }
+
+    // The commands to send in place of a force streamed by the mixer, which keeps playing when
+    // the application stops, so it leaves the watchdog alone
+    pub(crate) fn filter_streamed(&mut self, command: Command, now: Instant) -> Vec<Command> {
+        self.apply(command, now, false)
+    }
+
+    fn apply(&mut self, command: Command, now: Instant, refresh: bool) -> Vec<Command> {
+        match command {
+            Command::StopForces { slots } => {
+                for slot in slots_in(slots) {
+                    self.requested[slot] = None;
+                    self.sent[slot] = None;
+                }
+
+                vec![command]
+            }
+            Command::ConstantForce { slots, .. }
+            | Command::Friction { slots, .. }
+            | Command::DownloadAndPlay { slots, .. } => {
+                if self.stopped {
+                    return Vec::new();
+                }
+
+                if refresh {
+                    self.refresh(now);
+                }
+
+                let limited: Vec<Command> = slots_in(slots)
+                    .filter_map(|slot| {
+                        self.requested[slot] = Some(with_slots(command, 1 << slot));
+                        self.limit(slot, now)
+                    })
+                    .collect();
+
+                // send one command to every slot when the limits left them the same
+                match limited.first() {
+                    Some(first)
+                        if limited.len() > 1
+                            && limited
+                                .iter()
+                                .all(|c| with_slots(*c, slots) == with_slots(*first, slots)) =>
+                    {
+                        vec![with_slots(*first, slots)]
+                    }
+                    _ => limited,
+                }
+            }
+            // auto-center keeps playing when the application stops, so it leaves the watchdog alone
+            Command::SetAutoCenterForce { .. } | Command::AutoCenterAdvanced(_) => {
+                self.requested[AUTO_CENTER] = Some(command);
+                self.limit(AUTO_CENTER, now).into_iter().collect()
+            }
+            Command::AutoCenterOff => {
+                self.requested[AUTO_CENTER] = None;
+                self.sent[AUTO_CENTER] = None;
+
+                vec![command]
+            }
+            _ => vec![command],
+        }
+    }
+
+    // Ramp forces down once the watchdog times out, and move rate limited forces on
+    pub(crate) fn tick(&mut self, now: Instant) -> Vec<Command> {
+        let timed_out = self
+            .settings
+            .watchdog_timeout
+            .is_some_and(|timeout| now.duration_since(self.refreshed) > timeout);
+
+        if timed_out && self.ramp > 0.0 {
+            let step = WATCHDOG_INTERVAL.as_secs_f32() / self.settings.ramp_down.as_secs_f32();
+            self.ramp = (self.ramp - step).max(0.0);
+
+            return self.resend(now);
+        }
+
+        (0..=AUTO_CENTER)
+            .filter(|slot| {
+                let target = self.target(*slot).and_then(strengths);
+                let sent = self.sent[*slot].map(|(strengths, _)| strengths);
+                target.is_some() && target != sent
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .filter_map(|slot| self.limit(slot, now))
+            .collect()
+    }
+
+    fn resend(&mut self, now: Instant) -> Vec<Command> {
+        (0..=AUTO_CENTER)
+            .filter_map(|slot| self.limit(slot, now))
+            .collect()
+    }
+
+    // How much the requested forces are scaled down by the torque cap, strength and watchdog
+    fn factor(&self) -> f32 {
+        self.settings.max_torque * self.strength * self.ramp
+    }
+
+    // The force the slot should reach, after the torque cap, strength and watchdog
+    fn target(&self, slot: usize) -> Option<Command> {
+        self.requested[slot].map(|command| scale(command, self.factor()))
+    }
+
+    // The requested force in the slot within the limits
+    fn limit(&mut self, slot: usize, now: Instant) -> Option<Command> {
+        let command = self.target(slot)?;
+
+        let Some(target) = strengths(command) else {
+            return Some(command);
+        };
+
+        let limited = match self.settings.max_torque_rate {
+            Some(rate) => {
+                let (from, elapsed) = match self.sent[slot] {
+                    Some((sent, at)) => (sent, now.duration_since(at).min(WATCHDOG_INTERVAL)),
+                    // after a pause the force may only move one step
+                    None => ([0.0; 3], WATCHDOG_INTERVAL),
+                };
+                let step = rate.max(0.0) * elapsed.as_secs_f32();
+
+                std::array::from_fn(|i| target[i].clamp(from[i] - step, from[i] + step))
+            }
+            None => target,
+        };
+
+        self.sent[slot] = Some((limited, now));
+
+        Some(with_strengths(command, limited))
+    }
+}
+
+// The strengths of a force that the rate limit moves, -1.0 to 1.0 of full force: the level of a
+// constant force, the coefficients of friction, the coefficients and saturation of springs and
+// dampers and the forces of auto-center. Unused strengths are 0.0
+fn strengths(command: Command) -> Option<[f32; 3]> {
+    let coefficient = |k: u8, negative: bool| {
+        let k = (k & 0x0f) as f32 / 15.0;
+        if negative {
+            -k
+        } else {
+            k
+        }
+    };
+
+    match command {
+        Command::ConstantForce { level, .. } => Some([(level as f32 - 128.0) / 127.0, 0.0, 0.0]),
+        Command::Friction { left, right, .. } => {
+            Some([left as f32 / 255.0, right as f32 / 255.0, 0.0])
+        }
+        Command::DownloadAndPlay {
+            params: [0x0b, _, _, k, signs, clip],
+            ..
+        } => Some([
+            coefficient(k, signs & 0x01 != 0),
+            coefficient(k >> 4, signs & 0x10 != 0),
+            clip as f32 / 255.0,
+        ]),
+        Command::DownloadAndPlay {
+            params: [0x0c, k1, s1, k2, s2, clip],
+            ..
+        } => Some([
+            coefficient(k1, s1 != 0),
+            coefficient(k2, s2 != 0),
+            clip as f32 / 255.0,
+        ]),
+        Command::SetAutoCenterForce { strength, .. } => Some([strength as f32 / 15.0, 0.0, 0.0]),
+        Command::AutoCenterAdvanced(auto_center) => Some([
+            auto_center.clockwise_force as f32 / 15.0,
+            auto_center.counter_clockwise_force as f32 / 15.0,
+            auto_center.centering_force as f32 / 255.0,
+        ]),
+        _ => None,
+    }
+}
+
+// The force with the strengths moved by the rate limit, the inverse of `strengths`
+fn with_strengths(command: Command, strengths: [f32; 3]) -> Command {
+    let [a, b, c] = strengths;
+    let byte = |value: f32, steps: f32| (value.abs() * steps).round().min(steps) as u8;
+
+    match command {
+        Command::ConstantForce { slots, .. } => Command::ConstantForce {
+            slots,
+            level: (128.0 + a * 127.0).round().clamp(1.0, 255.0) as u8,
+        },
+        Command::Friction { slots, .. } => Command::Friction {
+            slots,
+            left: byte(a, 255.0),
+            right: byte(b, 255.0),
+        },
+        Command::DownloadAndPlay {
+            slots,
+            params: [0x0b, d1, d2, _, signs, _],
+        } => Command::DownloadAndPlay {
+            slots,
+            params: [
+                0x0b,
+                d1,
+                d2,
+                byte(b, 15.0) << 4 | byte(a, 15.0),
+                signs & !0x11 | ((b < 0.0) as u8) << 4 | (a < 0.0) as u8,
+                byte(c, 255.0),
+            ],
+        },
+        Command::DownloadAndPlay {
+            slots,
+            params: [0x0c, ..],
+        } => Command::DownloadAndPlay {
+            slots,
+            params: [
+                0x0c,
+                byte(a, 15.0),
+                (a < 0.0) as u8,
+                byte(b, 15.0),
+                (b < 0.0) as u8,
+                byte(c, 255.0),
+            ],
+        },
+        Command::SetAutoCenterForce {
+            turning_multiplier, ..
+        } => Command::SetAutoCenterForce {
+            strength: byte(a, 15.0),
+            turning_multiplier,
+        },
+        Command::AutoCenterAdvanced(mut auto_center) => {
+            auto_center.clockwise_force = byte(a, 15.0);
+            auto_center.counter_clockwise_force = byte(b, 15.0);
+            auto_center.centering_force = byte(c, 255.0);
+            Command::AutoCenterAdvanced(auto_center)
+        }
+        command => command,
+    }
+}
+
+// The indices of the slots in a mask
+fn slots_in(slots: u8) -> impl Iterator<Item = usize> {
+    Slot::ALL
+        .into_iter()
+        .filter(move |slot| slots & slot.mask() != 0)
+        .map(|slot| slot.number() as usize - 1)
+}
+
+fn with_slots(command: Command, slots: u8) -> Command {
+    match command {
+        Command::ConstantForce { level, .. } => Command::ConstantForce { slots, level },
+        Command::Friction { left, right, .. } => Command::Friction { slots, left, right },
+        Command::DownloadAndPlay { params, .. } => Command::DownloadAndPlay { slots, params },
+        command => command,
+    }
+}
+
+// A constant force level scaled towards no force, 0x80
+fn scale_level(level: u8, factor: f32) -> u8 {
+    (128.0 + (level as f32 - 128.0) * factor).round() as u8
+}
+
+fn scale_u8(value: u8, factor: f32) -> u8 {
+    (value as f32 * factor).round() as u8
+}
+
+/// Scale the strength of a force command, `factor` is 0.0 to 1.0.
+/// Commands that are not forces are returned unchanged.
+pub fn scale(command: Command, factor: f32) -> Command {
+    let factor = if factor.is_nan() {
+        0.0
+    } else {
+        factor.clamp(0.0, 1.0)
+    };
+
+    if factor == 1.0 {
+        return command;
+    }
+
+    match command {
+        Command::ConstantForce { slots, level } => Command::ConstantForce {
+            slots,
+            level: scale_level(level, factor),
+        },
+        Command::Friction { slots, left, right } => Command::Friction {
+            slots,
+            left: scale_u8(left, factor),
+            right: scale_u8(right, factor),
+        },
+        // constant force: four levels around 0x80
+        Command::DownloadAndPlay {
+            slots,
+            params: [0x00, a, b, c, d, e],
+        } => Command::DownloadAndPlay {
+            slots,
+            params: [
+                0x00,
+                scale_level(a, factor),
+                scale_level(b, factor),
+                scale_level(c, factor),
+                scale_level(d, factor),
+                e,
+            ],
+        },
+        // spring and damper: the last byte is the saturation
+        Command::DownloadAndPlay {
+            slots,
+            params: [kind @ (0x0b | 0x0c), a, b, c, d, clip],
+        } => Command::DownloadAndPlay {
+            slots,
+            params: [kind, a, b, c, d, scale_u8(clip, factor)],
+        },
+        Command::SetAutoCenterForce {
+            strength,
+            turning_multiplier,
+        } => Command::SetAutoCenterForce {
+            strength: scale_u8(strength, factor),
+            turning_multiplier,
+        },
+        Command::AutoCenterAdvanced(mut auto_center) => {
+            auto_center.clockwise_force = scale_u8(auto_center.clockwise_force, factor);
+            auto_center.counter_clockwise_force =
+                scale_u8(auto_center.counter_clockwise_force, factor);
+            auto_center.centering_force = scale_u8(auto_center.centering_force, factor);
+            Command::AutoCenterAdvanced(auto_center)
+        }
+        command => command,
+    }
+}
+
+// Start the thread running the watchdog and rate limit, if the settings need it
+pub(crate) fn start(g29: &G29, safety: &Arc<Mutex<Safety>>) {
+    let mut locked = safety.lock().unwrap();
+
+    if !locked.settings.needs_thread() || locked.running.load(Ordering::Acquire) {
+        return;
+    }
+
+    let running = Arc::new(AtomicBool::new(true));
+    let clock = g29.clock();
+    let sleeper = clock.register();
+    locked.running = running.clone();
+    locked.clock = clock.clone();
+
+    let g29 = g29.clone();
+    let safety = safety.clone();
+    locked.thread = Some(thread::spawn(move || {
+        let mut next = clock.now();
+
+        while running.load(Ordering::Acquire) && g29.connected() {
+            {
+                let mut locked = safety.lock().unwrap();
+
+                if !locked.settings.needs_thread() {
+                    break;
+                }
+
+                for command in locked.tick(clock.now()) {
+                    g29.relay_os(command.encode(), command.name());
+                }
+            }
+
+            next += WATCHDOG_INTERVAL;
+            sleeper.sleep_until(next, &running);
+        }
+
+        running.store(false, Ordering::Release);
+    }));
+}
+
+// Stop the thread and wait for it
+pub(crate) fn stop(safety: &Arc<Mutex<Safety>>) {
+    let thread = {
+        let mut locked = safety.lock().unwrap();
+        locked.running.store(false, Ordering::Release);
+        locked.clock.wake();
+        locked.thread.take()
+    };
+
+    if let Some(thread) = thread {
+        thread.join().unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use crate::{
+        command::Command,
+        encoder::IDLE_FRAME,
+        safety::{scale, Safety, SafetySettings, WATCHDOG_INTERVAL},
+        simulator::Simulator,
+        Button, G29State, Options,
+    };
+
+    fn constant(level: u8) -> Command {
+        Command::ConstantForce { slots: 0x01, level }
+    }
+
+    #[test]
+    fn test_scale() {
+        assert_eq!(scale(constant(0xff), 0.5), constant(0xc0));
+        assert_eq!(scale(constant(0x01), 0.5), constant(0x41));
+        assert_eq!(scale(constant(0xff), 1.0), constant(0xff));
+        assert_eq!(
+            scale(
+                Command::DownloadAndPlay {
+                    slots: 0x04,
+                    params: [0x0b, 0x7f, 0x80, 0xff, 0x00, 0xff],
+                },
+                0.5
+            ),
+            Command::DownloadAndPlay {
+                slots: 0x04,
+                params: [0x0b, 0x7f, 0x80, 0xff, 0x00, 0x80],
+            }
+        );
+        assert_eq!(
+            scale(Command::SetLeds(crate::Led::All), 0.0),
+            Command::SetLeds(crate::Led::All)
+        );
+    }
+
+    #[test]
+    fn test_max_torque() {
+        let mut safety = Safety::default();
+        let now = Instant::now();
+        safety.set_settings(
+            SafetySettings {
+                max_torque: 0.5,
+                ..Default::default()
+            },
+            now,
+        );
+
+        assert_eq!(safety.filter(constant(0xff), now), vec![constant(0xc0)]);
+        assert_eq!(
+            safety.filter(
+                Command::Friction {
+                    slots: 0x03,
+                    left: 100,
+                    right: 0
+                },
+                now
+            ),
+            vec![Command::Friction {
+                slots: 0x03,
+                left: 50,
+                right: 0
+            }]
+        );
+    }
+
+    #[test]
+    fn test_strength() {
+        let mut safety = Safety::default();
+        let now = Instant::now();
+        safety.set_settings(
+            SafetySettings {
+                max_torque: 0.5,
+                ..Default::default()
+            },
+            now,
+        );
+
+        let spring = Command::DownloadAndPlay {
+            slots: 0x02,
+            params: [0x0b, 0x7f, 0x80, 0xff, 0x00, 0xff],
+        };
+        assert_eq!(safety.filter(spring, now), vec![scale(spring, 0.5)]);
+
+        // the playing forces are sent again, within the torque cap
+        assert_eq!(safety.set_strength(0.5, now), vec![scale(spring, 0.25)]);
+        assert_eq!(safety.filter(constant(0xff), now), vec![constant(0xa0)]);
+        assert_eq!(
+            safety.filter(
+                Command::SetAutoCenterForce {
+                    strength: 0x08,
+                    turning_multiplier: 0x80
+                },
+                now
+            ),
+            vec![Command::SetAutoCenterForce {
+                strength: 0x02,
+                turning_multiplier: 0x80
+            }]
+        );
+
+        // the spring, the constant force and auto-center
+        assert_eq!(safety.set_strength(f32::NAN, now).len(), 3);
+        assert_eq!(safety.strength(), 0.0);
+    }
+
+    #[test]
+    fn test_rate_limit() {
+        let mut safety = Safety::default();
+        let now = Instant::now();
+        safety.set_settings(
+            SafetySettings {
+                max_torque_rate: Some(100.0),
+                ..Default::default()
+            },
+            now,
+        );
+
+        // 0.4 of full force in one step, then the rest from the watchdog
+        assert_eq!(safety.filter(constant(0xff), now), vec![constant(0xb3)]);
+        assert_eq!(safety.tick(now + WATCHDOG_INTERVAL), vec![constant(0xe6)]);
+        assert_eq!(
+            safety.tick(now + WATCHDOG_INTERVAL * 2),
+            vec![constant(0xff)]
+        );
+        assert!(safety.tick(now + WATCHDOG_INTERVAL * 3).is_empty());
+    }
+
+    #[test]
+    fn test_rate_limit_conditions() {
+        let mut safety = Safety::default();
+        let now = Instant::now();
+        safety.set_settings(
+            SafetySettings {
+                max_torque_rate: Some(125.0),
+                ..Default::default()
+            },
+            now,
+        );
+
+        // half of full force a step: the coefficients and saturation rise together
+        let friction = Command::Friction {
+            slots: 0x02,
+            left: 0xff,
+            right: 0x00,
+        };
+        assert_eq!(
+            safety.filter(friction, now),
+            vec![Command::Friction {
+                slots: 0x02,
+                left: 0x80,
+                right: 0x00
+            }]
+        );
+        assert_eq!(safety.tick(now + WATCHDOG_INTERVAL), vec![friction]);
+
+        let damper = Command::DownloadAndPlay {
+            slots: 0x04,
+            params: [0x0c, 0x0f, 0x01, 0x04, 0x00, 0xff],
+        };
+        assert_eq!(
+            safety.filter(damper, now),
+            vec![Command::DownloadAndPlay {
+                slots: 0x04,
+                params: [0x0c, 0x08, 0x01, 0x04, 0x00, 0x80],
+            }]
+        );
+
+        // the deadband edges of a spring are kept, only the strengths move
+        let spring = Command::DownloadAndPlay {
+            slots: 0x08,
+            params: [0x0b, 0x73, 0x8c, 0xf2, 0xa3, 0xff],
+        };
+        assert_eq!(
+            safety.filter(spring, now),
+            vec![Command::DownloadAndPlay {
+                slots: 0x08,
+                params: [0x0b, 0x73, 0x8c, 0x82, 0xa3, 0x80],
+            }]
+        );
+        assert_eq!(safety.tick(now + WATCHDOG_INTERVAL), vec![damper, spring]);
+
+        let auto_center = Command::SetAutoCenterForce {
+            strength: 0x0f,
+            turning_multiplier: 0xff,
+        };
+        assert_eq!(
+            safety.filter(auto_center, now),
+            vec![Command::SetAutoCenterForce {
+                strength: 0x08,
+                turning_multiplier: 0xff
+            }]
+        );
+        assert_eq!(safety.tick(now + WATCHDOG_INTERVAL), vec![auto_center]);
+    }
+
+    #[test]
+    fn test_watchdog() {
+        let mut safety = Safety::default();
+        let now = Instant::now();
+        safety.set_settings(
+            SafetySettings {
+                watchdog_timeout: Some(Duration::from_millis(10)),
+                ramp_down: WATCHDOG_INTERVAL * 2,
+                ..Default::default()
+            },
+            now,
+        );
+
+        let auto_center = Command::SetAutoCenterForce {
+            strength: 0x0f,
+            turning_multiplier: 0xff,
+        };
+        safety.filter(auto_center, now);
+        safety.filter(constant(0xff), now);
+        assert!(safety.tick(now + Duration::from_millis(5)).is_empty());
+        assert!(!safety.tripped());
+
+        // auto-center ramps down with the slots
+        let late = now + Duration::from_millis(20);
+        let auto_center_at = |strength| Command::SetAutoCenterForce {
+            strength,
+            turning_multiplier: 0xff,
+        };
+        assert_eq!(
+            safety.tick(late),
+            vec![constant(0xc0), auto_center_at(0x08)]
+        );
+        assert_eq!(
+            safety.tick(late),
+            vec![constant(0x80), auto_center_at(0x00)]
+        );
+        assert!(safety.tripped());
+        assert!(safety.tick(late).is_empty());
+
+        // a streamed force stays ramped down, a new force refreshes the watchdog
+        assert_eq!(
+            safety.filter_streamed(constant(0xff), late),
+            vec![constant(0x80)]
+        );
+        assert!(safety.tripped());
+        assert_eq!(safety.filter(constant(0xff), late), vec![constant(0xff)]);
+        assert!(!safety.tripped());
+    }
+
+    #[test]
+    fn test_emergency_stop() {
+        let mut safety = Safety::default();
+        let now = Instant::now();
+        let settings = SafetySettings {
+            emergency_stop: vec![Button::L3, Button::R3],
+            ..Default::default()
+        };
+
+        let mut state = G29State::from_frame(&IDLE_FRAME, 0, Duration::ZERO);
+        state.l3_button = true;
+        assert!(!settings.emergency_stop_pressed(&state));
+        state.r3_button = true;
+        assert!(settings.emergency_stop_pressed(&state));
+        assert!(!SafetySettings::default().emergency_stop_pressed(&state));
+
+        safety.stop();
+        assert!(safety.filter(constant(0xff), now).is_empty());
+        assert_eq!(
+            safety.filter(Command::StopForces { slots: 0x0f }, now),
+            vec![Command::StopForces { slots: 0x0f }]
+        );
+
+        safety.clear_stop();
+        assert_eq!(safety.filter(constant(0xff), now), vec![constant(0xff)]);
+    }
+
+    #[test]
+    fn test_set_safety() {
+        let mut sim = Simulator::new(Options::default());
+        sim.g29().clear_written_commands();
+
+        let settings = SafetySettings {
+            max_torque: 0.5,
+            emergency_stop: vec![Button::L3, Button::R3],
+            ..Default::default()
+        };
+        sim.g29().set_safety(settings.clone());
+        assert_eq!(sim.g29().safety(), settings);
+        sim.g29().set_constant_force(1.0, 1).unwrap();
+        assert_eq!(
+            sim.g29().written_commands(),
+            vec![
+                [0xfe, 0x0d, 0x04, 0x04, 0xff, 0x00, 0x00],
+                [0x11, 0x08, 0xc0, 0x80, 0x00, 0x00, 0x00],
+            ]
+        );
+
+        sim.g29().clear_written_commands();
+        sim.press(Button::L3);
+        assert!(!sim.g29().emergency_stopped());
+        sim.press(Button::R3);
+        assert!(sim.g29().emergency_stopped());
+        assert!(sim.g29().active_effects().is_empty());
+
+        sim.g29().set_constant_force(1.0, 1).unwrap();
+        assert_eq!(
+            sim.g29().written_commands(),
+            vec![[0xf3, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]]
+        );
+
+        sim.g29().clear_emergency_stop();
+        sim.g29().clear_written_commands();
+        sim.g29().set_constant_force(-1.0, 1).unwrap();
+        assert_eq!(
+            sim.g29().written_commands(),
+            vec![[0x11, 0x08, 0x41, 0x80, 0x00, 0x00, 0x00]]
+        );
+    }
+
+    #[test]
+    fn test_wheel_watchdog() {
+        let mut sim = Simulator::new(Options::default());
+        sim.g29().set_safety(SafetySettings {
+            watchdog_timeout: Some(Duration::from_millis(10)),
+            ramp_down: Duration::from_millis(20),
+            ..Default::default()
+        });
+        let last = |sim: &Simulator, first: u8| {
+            sim.g29()
+                .written_commands()
+                .into_iter()
+                .rev()
+                .find(|command| command[0] == first)
+        };
+
+        sim.g29().set_constant_force(1.0, 1).unwrap();
+        sim.wait(Duration::from_millis(10));
+        assert!(!sim.g29().watchdog_tripped());
+
+        // timed out at the 12 ms tick, each tick takes a fifth off until no force is left
+        sim.wait(Duration::from_millis(12));
+        assert!(sim.g29().watchdog_tripped());
+        assert_eq!(
+            last(&sim, 0x11),
+            Some([0x11, 0x08, 0xb3, 0x80, 0x00, 0x00, 0x00])
+        );
+
+        // auto-center is ramped down too
+        sim.wait(Duration::from_millis(20));
+        assert_eq!(
+            last(&sim, 0x11),
+            Some([0x11, 0x08, 0x80, 0x80, 0x00, 0x00, 0x00])
+        );
+        assert_eq!(
+            last(&sim, 0xfe),
+            Some([0xfe, 0x0d, 0x00, 0x00, 0xff, 0x00, 0x00])
+        );
+
+        // and comes back on the next tick once the watchdog is refreshed
+        sim.g29().refresh_watchdog();
+        assert!(!sim.g29().watchdog_tripped());
+        sim.wait(Duration::from_millis(4));
+        assert_eq!(
+            last(&sim, 0xfe),
+            Some([0xfe, 0x0d, 0x07, 0x07, 0xff, 0x00, 0x00])
+        );
+
+        // a force loop does not keep the watchdog alive on its own
+        let force_loop = sim.g29().start_force_loop(500, |_, _| 1.0).unwrap();
+        sim.wait(Duration::from_millis(50));
+        assert!(sim.g29().watchdog_tripped());
+        assert_eq!(
+            last(&sim, 0x21),
+            Some([0x21, 0x08, 0x80, 0x80, 0x00, 0x00, 0x00])
+        );
+
+        drop(force_loop);
+        sim.g29_mut().disconnect();
+    }
+}
@@ -0,0 +1,407 @@
+use crate::GearSelector;
+
+///
+/// ShifterGear
+///
+/// A gear of the calibrated H-shifter.
+/// Unlike `GearSelector` it has the 7th and 8th gates of the 7 and 8 speed layouts.
+///
+#[derive(Debug, PartialEq, Copy, Clone, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ShifterGear {
+    Neutral,
+    First,
+    Second,
+    Third,
+    Fourth,
+    Fifth,
+    Sixth,
+    /// Only selected with a 7 or 8 speed layout
+    Seventh,
+    /// Only selected with an 8 speed layout
+    Eighth,
+    Reverse,
+}
+
+impl ShifterGear {
+    /// Get the gear selector position of the gear, `GearSelector::Neutral` for 7th and 8th.
+    pub fn gear_selector(&self) -> GearSelector {
+        match self {
+            ShifterGear::First => GearSelector::First,
+            ShifterGear::Second => GearSelector::Second,
+            ShifterGear::Third => GearSelector::Third,
+            ShifterGear::Fourth => GearSelector::Fourth,
+            ShifterGear::Fifth => GearSelector::Fifth,
+            ShifterGear::Sixth => GearSelector::Sixth,
+            ShifterGear::Reverse => GearSelector::Reverse,
+            _ => GearSelector::Neutral,
+        }
+    }
+}
+
+impl From<GearSelector> for ShifterGear {
+    fn from(gear: GearSelector) -> Self {
+        match gear {
+            GearSelector::Neutral => ShifterGear::Neutral,
+            GearSelector::First => ShifterGear::First,
+            GearSelector::Second => ShifterGear::Second,
+            GearSelector::Third => ShifterGear::Third,
+            GearSelector::Fourth => ShifterGear::Fourth,
+            GearSelector::Fifth => ShifterGear::Fifth,
+            GearSelector::Sixth => ShifterGear::Sixth,
+            GearSelector::Reverse => ShifterGear::Reverse,
+        }
+    }
+}
+
+///
+/// ShifterLayout
+///
+/// The gates of the H-shifter. The stick has three columns with a gate at the top and bottom of each.
+/// Pushing the stick down (`shifter_pressed`) selects the extra gates.
+///
+/// | Layout       | Top row   | Bottom row | Pushed down                                |
+/// |--------------|-----------|------------|--------------------------------------------|
+/// | `SixSpeed`   | 1, 3, 5   | 2, 4, 6    | bottom right is reverse                    |
+/// | `SevenSpeed` | 1, 3, 5   | 2, 4, 6    | top right is 7th, bottom right is reverse  |
+/// | `EightSpeed` | 1, 3, 5   | 2, 4, 6    | top right is 7th, bottom right is 8th, bottom left is reverse |
+///
+#[derive(Debug, PartialEq, Copy, Clone, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ShifterLayout {
+    #[default]
+    SixSpeed,
+    SevenSpeed,
+    EightSpeed,
+}
+
+impl ShifterLayout {
+    fn gear(&self, column: Column, top: bool, pressed: bool) -> ShifterGear {
+        match (column, top, pressed, self) {
+            (Column::Left, true, false, _) => ShifterGear::First,
+            (Column::Left, false, false, _) => ShifterGear::Second,
+            (Column::Middle, true, false, _) => ShifterGear::Third,
+            (Column::Middle, false, false, _) => ShifterGear::Fourth,
+            (Column::Right, true, false, _) => ShifterGear::Fifth,
+            (Column::Right, false, false, _) => ShifterGear::Sixth,
+            (Column::Right, true, true, ShifterLayout::SevenSpeed | ShifterLayout::EightSpeed) => {
+                ShifterGear::Seventh
+            }
+            (Column::Right, false, true, ShifterLayout::EightSpeed) => ShifterGear::Eighth,
+            (Column::Right, false, true, _) => ShifterGear::Reverse,
+            (Column::Left, false, true, ShifterLayout::EightSpeed) => ShifterGear::Reverse,
+            _ => ShifterGear::Neutral,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+enum Column {
+    Left,
+    Middle,
+    Right,
+}
+
+///
+/// ShifterCalibration
+///
+/// The raw positions the shifter stick reaches
+/// - x_min, x_max: `u8` - The raw x values of the left and right columns
+/// - y_min, y_max: `u8` - The raw y values of the two rows
+/// - center: `(u8, u8)` - The raw x and y values in neutral
+///
+#[derive(Debug, PartialEq, Copy, Clone, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ShifterCalibration {
+    pub x_min: u8,
+    pub x_max: u8,
+    pub y_min: u8,
+    pub y_max: u8,
+    pub center: (u8, u8),
+}
+
+impl Default for ShifterCalibration {
+    fn default() -> Self {
+        ShifterCalibration {
+            x_min: 0,
+            x_max: 255,
+            y_min: 0,
+            y_max: 255,
+            center: (128, 128),
+        }
+    }
+}
+
+impl ShifterCalibration {
+    /// Start a calibration where the stick has only been seen in neutral.
+    pub fn at_rest(x: u8, y: u8) -> ShifterCalibration {
+        ShifterCalibration {
+            x_min: x,
+            x_max: x,
+            y_min: y,
+            y_max: y,
+            center: (x, y),
+        }
+    }
+
+    /// Extend the calibrated range to include a stick position.
+    pub fn observe(&mut self, x: u8, y: u8) {
+        self.x_min = self.x_min.min(x);
+        self.x_max = self.x_max.max(x);
+        self.y_min = self.y_min.min(y);
+        self.y_max = self.y_max.max(y);
+    }
+
+    // -1.0 to 1.0 around the center
+    fn normalize(raw: u8, min: u8, center: u8, max: u8) -> f32 {
+        let offset = raw as f32 - center as f32;
+        let span = if offset >= 0.0 {
+            max as f32 - center as f32
+        } else {
+            center as f32 - min as f32
+        };
+
+        if span == 0.0 {
+            0.0
+        } else {
+            (offset / span).clamp(-1.0, 1.0)
+        }
+    }
+}
+
+///
+/// ShifterSettings
+///
+/// How raw shifter positions are classified into gears
+/// - layout: `ShifterLayout` - The gates of the shifter (default: `ShifterLayout::SixSpeed`)
+/// - engage: `f32` - How far from neutral towards a row the stick must travel to engage a gear, 0.0 - 1.0 (default: `0.6`)
+/// - release: `f32` - How far the stick can move back towards neutral before the gear disengages, 0.0 - 1.0 (default: `0.4`)
+/// - column_width: `f32` - How far from the center the stick must be to select the left or right column, 0.0 - 1.0 (default: `0.5`)
+/// - invert_y: `bool` - Treat high y values as the bottom row (default: `false`)
+/// - calibration: `ShifterCalibration` - The learned positions of the stick
+///
+/// Keeping `release` below `engage` stops the gear flickering when the stick sits near a threshold.
+///
+#[derive(Debug, PartialEq, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ShifterSettings {
+    pub layout: ShifterLayout,
+    pub engage: f32,
+    pub release: f32,
+    pub column_width: f32,
+    pub invert_y: bool,
+    pub calibration: ShifterCalibration,
+}
+
+impl Default for ShifterSettings {
+    fn default() -> Self {
+        ShifterSettings {
+            layout: ShifterLayout::SixSpeed,
+            engage: 0.6,
+            release: 0.4,
+            column_width: 0.5,
+            invert_y: false,
+            calibration: ShifterCalibration::default(),
+        }
+    }
+}
+
+///
+/// HShifter
+///
+/// Classifies raw shifter positions into gears, remembering the current gear for hysteresis.
+///
+/// # Example
+///
+/// ```rust
+/// use lib_g29::shifter::{HShifter, ShifterSettings};
+///
+/// let mut shifter = HShifter::new(ShifterSettings::default());
+/// let gear = shifter.update(g29.shifter_x(), g29.shifter_y(), g29.shifter_pressed());
+/// ```
+///
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct HShifter {
+    settings: ShifterSettings,
+    gear: ShifterGear,
+    learning: bool,
+}
+
+impl HShifter {
+    pub fn new(settings: ShifterSettings) -> HShifter {
+        HShifter {
+            settings,
+            gear: ShifterGear::Neutral,
+            learning: false,
+        }
+    }
+
+    /// Get the settings of the shifter.
+    pub fn settings(&self) -> ShifterSettings {
+        self.settings
+    }
+
+    /// Get the currently selected gear.
+    pub fn gear(&self) -> ShifterGear {
+        self.gear
+    }
+
+    /// Classify a raw stick position, returning the selected gear.
+    pub fn update(&mut self, x: u8, y: u8, pressed: bool) -> ShifterGear {
+        if self.learning {
+            self.settings.calibration.observe(x, y);
+        }
+
+        let settings = self.settings;
+
+        let calibration = settings.calibration;
+        let x = ShifterCalibration::normalize(
+            x,
+            calibration.x_min,
+            calibration.center.0,
+            calibration.x_max,
+        );
+        let mut y = ShifterCalibration::normalize(
+            y,
+            calibration.y_min,
+            calibration.center.1,
+            calibration.y_max,
+        );
+
+        if settings.invert_y {
+            y = -y;
+        }
+
+        let threshold = if self.gear == ShifterGear::Neutral {
+            settings.engage
+        } else {
+            settings.release
+        };
+
+        let column = if x <= -settings.column_width {
+            Column::Left
+        } else if x >= settings.column_width {
+            Column::Right
+        } else {
+            Column::Middle
+        };
+
+        self.gear = if y >= threshold {
+            settings.layout.gear(column, true, pressed)
+        } else if y <= -threshold {
+            settings.layout.gear(column, false, pressed)
+        } else {
+            ShifterGear::Neutral
+        };
+
+        self.gear
+    }
+
+    /// Start learning a new calibration from the positions passed to `update`,
+    /// treating the given position as neutral.
+    /// Move the stick into every gate while learning.
+    pub fn start_learning(&mut self, x: u8, y: u8) {
+        self.settings.calibration = ShifterCalibration::at_rest(x, y);
+        self.gear = ShifterGear::Neutral;
+        self.learning = true;
+    }
+
+    /// Stop learning the calibration.
+    pub fn stop_learning(&mut self) {
+        self.learning = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::shifter::{
+        HShifter, ShifterCalibration, ShifterGear, ShifterLayout, ShifterSettings,
+    };
+    use crate::GearSelector;
+
+    fn get_test_settings(layout: ShifterLayout) -> ShifterSettings {
+        ShifterSettings {
+            layout,
+            calibration: ShifterCalibration {
+                x_min: 40,
+                x_max: 220,
+                y_min: 30,
+                y_max: 230,
+                center: (130, 130),
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_six_speed() {
+        let mut shifter = HShifter::new(get_test_settings(ShifterLayout::SixSpeed));
+
+        assert_eq!(shifter.update(130, 130, false), ShifterGear::Neutral);
+        assert_eq!(shifter.update(40, 230, false), ShifterGear::First);
+        assert_eq!(shifter.update(45, 35, false), ShifterGear::Second);
+        assert_eq!(shifter.update(130, 225, false), ShifterGear::Third);
+        assert_eq!(shifter.update(128, 30, false), ShifterGear::Fourth);
+        assert_eq!(shifter.update(220, 230, false), ShifterGear::Fifth);
+        assert_eq!(shifter.update(215, 30, false), ShifterGear::Sixth);
+        assert_eq!(shifter.update(215, 30, true), ShifterGear::Reverse);
+        assert_eq!(shifter.update(215, 230, true), ShifterGear::Neutral);
+    }
+
+    #[test]
+    fn test_extra_gates() {
+        let mut seven = HShifter::new(get_test_settings(ShifterLayout::SevenSpeed));
+        assert_eq!(seven.update(220, 230, true), ShifterGear::Seventh);
+        assert_eq!(seven.update(220, 30, true), ShifterGear::Reverse);
+
+        let mut eight = HShifter::new(get_test_settings(ShifterLayout::EightSpeed));
+        assert_eq!(eight.update(220, 230, true), ShifterGear::Seventh);
+        assert_eq!(eight.update(220, 30, true), ShifterGear::Eighth);
+        assert_eq!(eight.update(40, 30, true), ShifterGear::Reverse);
+    }
+
+    #[test]
+    fn test_gear_selector() {
+        assert_eq!(ShifterGear::Seventh.gear_selector(), GearSelector::Neutral);
+        assert_eq!(ShifterGear::Reverse.gear_selector(), GearSelector::Reverse);
+        assert_eq!(ShifterGear::from(GearSelector::Third), ShifterGear::Third);
+    }
+
+    #[test]
+    fn test_hysteresis() {
+        let mut shifter = HShifter::new(get_test_settings(ShifterLayout::SixSpeed));
+
+        // half way to the top row is not enough to engage
+        assert_eq!(shifter.update(130, 180, false), ShifterGear::Neutral);
+
+        assert_eq!(shifter.update(130, 230, false), ShifterGear::Third);
+
+        // half way back towards neutral keeps the gear
+        assert_eq!(shifter.update(130, 180, false), ShifterGear::Third);
+
+        assert_eq!(shifter.update(130, 150, false), ShifterGear::Neutral);
+    }
+
+    #[test]
+    fn test_learning() {
+        let mut shifter = HShifter::new(ShifterSettings::default());
+        shifter.start_learning(120, 125);
+
+        for (x, y) in [(120, 125), (30, 240), (30, 20), (210, 240), (210, 20)] {
+            shifter.update(x, y, false);
+        }
+
+        assert_eq!(
+            shifter.settings().calibration,
+            ShifterCalibration {
+                x_min: 30,
+                x_max: 210,
+                y_min: 20,
+                y_max: 240,
+                center: (120, 125),
+            }
+        );
+        assert_eq!(shifter.update(120, 125, false), ShifterGear::Neutral);
+        assert_eq!(shifter.update(30, 240, false), ShifterGear::First);
+    }
+}
@@ -0,0 +1,455 @@
+use std::{ops::Range, sync::Mutex, time::Duration};
+
+use crate::{
+    axis::Axis, clock::Clock, encoder::IDLE_FRAME, layout::Layout, recording::DeviceDetails,
+    shifter::ShifterGear, Button, DpadPosition, Frame, GearSelector, Options, Wheel, FRAME_SIZE,
+    G29,
+};
+
+///
+/// Simulator
+///
+/// Drives a virtual G29 from a script instead of a real wheel.
+///
+/// Every change is encoded into a frame and fed through the same code path as frames read from
+/// a real wheel, so getters, events, calibration and gearboxes behave the same.
+/// Time is virtual: it only moves forward when the script waits, ramps, taps or spins,
+/// so scripts run instantly and always produce the same frames and timestamps.
+/// Periodic effects, force loops and the safety watchdog run on the same virtual time.
+///
+/// Axis values use the uncalibrated mapping of `AxisConfig`: pedals are 0.0 (released) to 1.0
+/// (fully pressed), every other axis is -1.0 to 1.0.
+///
+/// # Example
+///
+/// ```rust
+/// use lib_g29::{axis::Axis, simulator::Simulator, Button, GearSelector, Options};
+/// use std::time::Duration;
+///
+/// let mut sim = Simulator::new(Options::default());
+///
+/// sim.g29().register_event_handler(Event::XButtonPressed, |g29| {
+///     println!("X pressed");
+/// });
+///
+/// sim.press(Button::X);
+/// sim.ramp(Axis::Throttle, 0.0..1.0, Duration::from_millis(500));
+/// sim.shift(GearSelector::Third);
+/// sim.spin(3);
+///
+/// assert_eq!(sim.g29().gear_selector(), GearSelector::Third);
+/// ```
+///
+#[derive(Debug)]
+pub struct Simulator {
+    g29: G29,
+    frame: Frame,
+    now: Duration,
+    tick: Duration,
+}
+
+impl Simulator {
+    /// Create a simulated wheel, connected with `options`, with every control at rest.
+    pub fn new(options: Options) -> Simulator {
+        let device = DeviceDetails {
+            vendor_id: 1133,
+            product_id: 49743,
+            serial_number: None,
+            product: Some("G29 Driving Force Racing Wheel".to_string()),
+        };
+
+        let g29 = G29::with_wheel(options, device, Wheel::Virtual(Mutex::new(Vec::new())));
+        g29.inner.write().unwrap().clock = Clock::new_virtual();
+        g29.send_range();
+        g29.set_auto_center();
+
+        let mut simulator = Simulator {
+            g29,
+            frame: IDLE_FRAME,
+            now: Duration::ZERO,
+            tick: Duration::from_millis(10),
+        };
+        simulator.feed();
+
+        simulator
+    }
+
+    /// Get the simulated wheel.
+    pub fn g29(&self) -> &G29 {
+        &self.g29
+    }
+
+    /// Get the simulated wheel mutably, for `unregister_event_handler` and `disconnect`.
+    pub fn g29_mut(&mut self) -> &mut G29 {
+        &mut self.g29
+    }
+
+    /// Get the current virtual time, starting at zero.
+    pub fn now(&self) -> Duration {
+        self.now
+    }
+
+    /// Get the last frame fed to the wheel.
+    pub fn frame(&self) -> [u8; FRAME_SIZE] {
+        self.frame
+    }
+
+    /// Set the time between the frames of ramps, taps and spins (default: 10 ms).
+    pub fn set_tick(&mut self, tick: Duration) {
+        self.tick = tick.max(Duration::from_micros(1));
+    }
+
+    /// Advance the virtual clock without changing any control.
+    /// Every tick of the periodic effects, force loops and watchdog that falls due runs before
+    /// this returns.
+    pub fn wait(&mut self, duration: Duration) {
+        self.now += duration;
+        self.g29.clock().advance(duration);
+    }
+
+    /// Press a button and keep it held.
+    pub fn press(&mut self, button: Button) {
+        self.set_button(button, true);
+    }
+
+    /// Release a button.
+    pub fn release(&mut self, button: Button) {
+        self.set_button(button, false);
+    }
+
+    /// Press a button, hold it for one tick and release it.
+    pub fn tap(&mut self, button: Button) {
+        self.press(button);
+        self.wait(self.tick);
+        self.release(button);
+    }
+
+    /// Set the Dpad position.
+    pub fn dpad(&mut self, position: DpadPosition) {
+        Layout::G29.set_dpad(&mut self.frame, position);
+        self.feed();
+    }
+
+    /// Set an axis to a value immediately.
+    /// `CombinedPedals` presses the throttle for positive values and the brake for negative values.
+    pub fn set_axis(&mut self, axis: Axis, value: f32) {
+        self.encode_axis(axis, value);
+        self.feed();
+    }
+
+    /// Move an axis from `values.start` to `values.end` over `duration`, one frame per tick.
+    ///
+    /// # Example
+    /// ```rust
+    /// // turn the wheel from center to full right lock in a second
+    /// sim.ramp(Axis::Steering, 0.0..1.0, Duration::from_secs(1));
+    /// ```
+    pub fn ramp(&mut self, axis: Axis, values: Range<f32>, duration: Duration) {
+        self.set_axis(axis, values.start);
+
+        let steps = (duration.as_nanos() / self.tick.as_nanos()).max(1) as u32;
+
+        for step in 1..=steps {
+            let progress = step as f32 / steps as f32;
+
+            self.wait(duration / steps);
+            self.set_axis(axis, values.start + (values.end - values.start) * progress);
+        }
+    }
+
+    /// Move the shifter into a gear, setting the gear selector and the stick position.
+    ///
+    /// The stick is placed in the gates of `ShifterLayout::SixSpeed` with the default calibration.
+    pub fn shift(&mut self, gear: GearSelector) {
+        self.shift_stick(gear.into());
+    }
+
+    /// Move the shifter stick into the gate of a calibrated shifter gear.
+    ///
+    /// Gates are placed like `shift`. `Seventh` and `Eighth` use the pushed down gates of
+    /// `ShifterLayout::EightSpeed`, which the G29 itself reports as neutral.
+    pub fn shift_stick(&mut self, gear: ShifterGear) {
+        const LEFT: u8 = 16;
+        const RIGHT: u8 = 240;
+        const TOP: u8 = 240;
+        const BOTTOM: u8 = 16;
+        const CENTER: u8 = 128;
+
+        let (x, y, pressed) = match gear {
+            ShifterGear::Neutral => (CENTER, CENTER, false),
+            ShifterGear::First => (LEFT, TOP, false),
+            ShifterGear::Second => (LEFT, BOTTOM, false),
+            ShifterGear::Third => (CENTER, TOP, false),
+            ShifterGear::Fourth => (CENTER, BOTTOM, false),
+            ShifterGear::Fifth => (RIGHT, TOP, false),
+            ShifterGear::Sixth => (RIGHT, BOTTOM, false),
+            ShifterGear::Seventh => (RIGHT, TOP, true),
+            ShifterGear::Eighth | ShifterGear::Reverse => (RIGHT, BOTTOM, true),
+        };
+
+        Layout::G29.set_gear_selector(&mut self.frame, gear.gear_selector());
+        self.set_value("shifter_x", x as u32);
+        self.set_value("shifter_y", y as u32);
+        self.set_value("shifter_pressed", pressed as u32);
+        self.feed();
+    }
+
+    /// Turn the spinner by a number of detents, positive to the right and negative to the left.
+    /// Every detent is a press and release of one tick each.
+    pub fn spin(&mut self, detents: i32) {
+        let button = if detents >= 0 {
+            Button::SpinnerRight
+        } else {
+            Button::SpinnerLeft
+        };
+
+        for _ in 0..detents.unsigned_abs() {
+            self.tap(button);
+            self.wait(self.tick);
+        }
+    }
+
+    fn set_button(&mut self, button: Button, pressed: bool) {
+        Layout::G29.set_button(&mut self.frame, button, pressed);
+        self.feed();
+    }
+
+    fn encode_axis(&mut self, axis: Axis, value: f32) {
+        // the inverse of the uncalibrated mapping in `AxisConfig::value`
+        let pedal = |value: f32| (255.0 * (1.0 - value.clamp(0.0, 1.0))).round() as u32;
+        let centered = |value: f32| (127.5 + 127.5 * value.clamp(-1.0, 1.0)).round() as u32;
+
+        match axis {
+            Axis::Steering => {
+                let raw = (32767.5 + 32767.5 * value.clamp(-1.0, 1.0)).round() as u32;
+                self.set_value("steering_raw", raw);
+            }
+            Axis::Throttle => self.set_value("throttle", pedal(value)),
+            Axis::Brake => self.set_value("brake", pedal(value)),
+            Axis::Clutch => self.set_value("clutch", pedal(value)),
+            Axis::ShifterX => self.set_value("shifter_x", centered(value)),
+            Axis::ShifterY => self.set_value("shifter_y", centered(value)),
+            Axis::CombinedPedals => {
+                self.set_value("throttle", pedal(value));
+                self.set_value("brake", pedal(-value));
+            }
+        }
+    }
+
+    fn set_value(&mut self, name: &str, value: u32) {
+        Layout::G29.set_value(&mut self.frame, name, value);
+    }
+
+    fn feed(&mut self) {
+        self.g29.process_frame(self.frame, self.now);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{
+        axis::{Axis, AxisCalibration},
+        effects::{Condition, Effect, EffectKind, Slot},
+        gearbox::{GearboxSettings, SequentialGear},
+        shifter::{ShifterGear, ShifterLayout, ShifterSettings},
+        simulator::Simulator,
+        Button, DpadPosition, GearSelector, Options,
+    };
+
+    #[test]
+    fn test_idle() {
+        let sim = Simulator::new(Options::default());
+        let state = sim.g29().snapshot();
+
+        assert!(sim.g29().connected());
+        assert_eq!(state.dpad, DpadPosition::None);
+        assert_eq!(state.gear_selector, GearSelector::Neutral);
+        assert_eq!(sim.g29().axis(Axis::Throttle), 0.0);
+        assert!(sim.g29().axis(Axis::Steering).abs() < 0.0001);
+    }
+
+    #[test]
+    fn test_buttons() {
+        let mut sim = Simulator::new(Options::default());
+
+        sim.press(Button::X);
+        sim.press(Button::Plus);
+        sim.dpad(DpadPosition::Left);
+        assert!(sim.g29().x_button());
+        assert!(sim.g29().plus_button());
+        assert_eq!(sim.g29().dpad(), DpadPosition::Left);
+
+        sim.release(Button::X);
+        assert!(!sim.g29().x_button());
+        assert!(sim.g29().plus_button());
+        assert_eq!(sim.g29().dpad(), DpadPosition::Left);
+    }
+
+    #[test]
+    fn test_ramp_uses_virtual_clock() {
+        let mut sim = Simulator::new(Options::default());
+
+        sim.ramp(Axis::Throttle, 0.0..1.0, Duration::from_millis(500));
+
+        assert_eq!(sim.now(), Duration::from_millis(500));
+        assert_eq!(sim.g29().throttle(), 0);
+        assert_eq!(sim.g29().axis(Axis::Throttle), 1.0);
+        assert_eq!(sim.g29().snapshot().timestamp, Duration::from_millis(500));
+        assert_eq!(sim.g29().snapshot().sequence, 51);
+
+        sim.set_axis(Axis::Steering, -1.0);
+        assert_eq!(sim.g29().steering_raw(), 0);
+
+        sim.set_axis(Axis::CombinedPedals, -0.5);
+        assert_eq!(sim.g29().axis(Axis::Throttle), 0.0);
+        assert!((sim.g29().axis(Axis::Brake) - 0.5).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_shift() {
+        let mut sim = Simulator::new(Options::default());
+        sim.g29()
+            .enable_calibrated_shifter(ShifterSettings::default());
+
+        for gear in [
+            GearSelector::First,
+            GearSelector::Second,
+            GearSelector::Third,
+            GearSelector::Fourth,
+            GearSelector::Fifth,
+            GearSelector::Sixth,
+            GearSelector::Reverse,
+            GearSelector::Neutral,
+        ] {
+            sim.shift(gear);
+            assert_eq!(sim.g29().gear_selector(), gear);
+            assert_eq!(sim.g29().shifter_gear(), gear.into());
+        }
+
+        sim.g29().enable_calibrated_shifter(ShifterSettings {
+            layout: ShifterLayout::EightSpeed,
+            ..Default::default()
+        });
+        sim.shift_stick(ShifterGear::Eighth);
+        assert_eq!(sim.g29().gear_selector(), GearSelector::Neutral);
+        assert_eq!(sim.g29().shifter_gear(), ShifterGear::Eighth);
+    }
+
+    #[test]
+    fn test_steering_trim() {
+        let mut sim = Simulator::new(Options::default());
+        sim.set_axis(Axis::Steering, 0.1);
+        assert!((sim.g29().steering_angle_degrees() - 45.0).abs() < 0.1);
+
+        sim.g29().clear_written_commands();
+        sim.g29().recenter_steering();
+        assert!((sim.g29().steering_trim() + 45.0).abs() < 0.1);
+        assert!(sim.g29().steering_angle_degrees().abs() < 0.1);
+        assert_eq!(sim.g29().profile().steering_trim, sim.g29().steering_trim());
+
+        // auto-center plays as a spring centered on the trim
+        assert!(sim
+            .g29()
+            .written_commands()
+            .contains(&[0xf5, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]));
+        assert_eq!(
+            sim.g29().active_effects(),
+            vec![(Slot::One, EffectKind::Spring)]
+        );
+        assert_eq!(
+            sim.g29().auto_center_spring(),
+            Some((Slot::One, Condition::auto_center(0x07, 0xff)))
+        );
+
+        // springs are centered on the trim, the readings stop at full lock
+        sim.g29().set_steering_trim(45.0);
+        let spring = Effect::Spring(Condition::new(0.5));
+        let centered = Effect::Spring(Condition {
+            center: -3277.0 / 32767.5,
+            ..Condition::new(0.5)
+        });
+        assert_eq!(
+            sim.g29().effect_command(spring, Slot::Two.mask()),
+            centered.command(Slot::Two.mask())
+        );
+
+        sim.set_axis(Axis::Steering, 1.0);
+        assert_eq!(sim.g29().steering_raw(), 65535);
+        sim.set_axis(Axis::Steering, -1.0);
+        assert_eq!(sim.g29().steering_raw(), 3277);
+
+        // the trim is kept in degrees across range changes
+        sim.g29().set_range(450);
+        assert!((sim.g29().steering_angle_degrees() + 180.0).abs() < 0.1);
+
+        sim.g29().clear_written_commands();
+        sim.g29().set_steering_trim(0.0);
+        assert!(sim.g29().active_effects().is_empty());
+        assert_eq!(sim.g29().auto_center_spring(), None);
+        assert_eq!(
+            sim.g29().written_commands().last(),
+            Some(&[0xfe, 0x0d, 0x07, 0x07, 0xff, 0x00, 0x00])
+        );
+    }
+
+    #[test]
+    fn test_steering_calibration() {
+        let mut sim = Simulator::new(Options::default());
+        let mut config = sim.g29().axis_config();
+        config.settings_mut(Axis::Steering).calibration = Some(AxisCalibration {
+            min: 0,
+            max: 65535,
+            rest: 39321,
+        });
+        sim.g29().set_axis_config(config);
+
+        // the calibrated rest reads as center
+        sim.set_axis(Axis::Steering, 0.2);
+        assert!(sim.g29().steering_normalized().abs() < 0.001);
+        assert!(sim.g29().steering_angle_degrees().abs() < 0.1);
+
+        sim.g29().recenter_steering();
+        assert!(sim.g29().steering_trim().abs() < 0.1);
+
+        // a fifth of the way left of the calibrated center is a fifth of half the range
+        sim.set_axis(Axis::Steering, 0.0);
+        assert!((sim.g29().steering_normalized() + 0.2).abs() < 0.001);
+        assert!((sim.g29().steering_angle_degrees() + 90.0).abs() < 0.1);
+
+        // learning the pedals leaves the steering alone
+        sim.g29().set_axis_learning(true);
+        sim.set_axis(Axis::Throttle, 1.0);
+        sim.set_axis(Axis::Steering, 0.1);
+        sim.g29().set_axis_learning(false);
+        assert_eq!(
+            sim.g29().axis_config().settings(Axis::Steering).calibration,
+            Some(AxisCalibration {
+                min: 0,
+                max: 65535,
+                rest: 39321,
+            })
+        );
+        assert!((sim.g29().steering_angle_degrees() + 45.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_spin_and_paddles() {
+        let mut sim = Simulator::new(Options::default());
+        sim.g29()
+            .enable_sequential_gearbox(GearboxSettings::default())
+            .unwrap();
+
+        sim.spin(3);
+        assert!(!sim.g29().spinner_right());
+        assert_eq!(sim.now(), Duration::from_millis(60));
+
+        sim.tap(Button::RightShifter);
+        sim.tap(Button::RightShifter);
+        assert_eq!(
+            sim.g29().sequential_gear(),
+            Some(SequentialGear::Forward(2))
+        );
+    }
+}
@@ -0,0 +1,164 @@
+use std::time::Duration;
+
+use crate::{force_loop::ForceLoop, G29State, G29};
+
+// How often the soft lock force is computed
+const SOFT_LOCK_RATE: u32 = 500;
+
+///
+/// SoftLock
+///
+/// A stiff spring, damped by the steering speed, that pushes the wheel back once it turns past
+/// a lock. Computed in software, see `G29::enable_soft_lock`
+/// - degrees: `u16` - The rotation from lock to lock (default: `900`)
+/// - stiffness: `f32` - The force for each degree past the lock, where 1.0 is full force
+///   (default: `0.1`)
+/// - damping: `f32` - The force for each degree per second the wheel turns while past the lock
+///   (default: `0.002`)
+///
+/// The wheel only reports positions up to the range set with `G29::set_range`, so the range
+/// should be wider than the soft lock.
+///
+#[derive(Debug, PartialEq, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SoftLock {
+    pub degrees: u16,
+    pub stiffness: f32,
+    pub damping: f32,
+}
+
+impl Default for SoftLock {
+    fn default() -> Self {
+        SoftLock {
+            degrees: 900,
+            stiffness: 0.1,
+            damping: 0.002,
+        }
+    }
+}
+
+impl SoftLock {
+    /// A soft lock at `degrees` from lock to lock, with the default stiffness and damping.
+    pub fn new(degrees: u16) -> SoftLock {
+        SoftLock {
+            degrees,
+            ..Default::default()
+        }
+    }
+
+    /// The force at a steering angle and speed, in degrees and degrees per second.
+    /// Zero inside the lock.
+    pub fn torque(&self, angle: f32, velocity: f32) -> f32 {
+        let excess = angle.abs() - self.degrees as f32 / 2.0;
+
+        if excess.is_nan() || excess <= 0.0 {
+            return 0.0;
+        }
+
+        let spring = self.stiffness.max(0.0) * excess * angle.signum();
+        let damper = self.damping.max(0.0) * velocity;
+
+        (-(spring + damper)).clamp(-1.0, 1.0)
+    }
+}
+
+// Start a force loop playing the soft lock, reading the range and steering calibration of the
+// wheel as they change
+pub(crate) fn start(g29: &G29, soft_lock: SoftLock) -> std::io::Result<ForceLoop> {
+    let wheel = g29.clone();
+    let mut previous: Option<(f32, Duration)> = None;
+    let mut velocity = 0.0;
+
+    g29.start_force_loop(SOFT_LOCK_RATE, move |state: &G29State, _| {
+        let angle = wheel.steering_angle(state);
+
+        // the speed between the last two frames
+        match previous {
+            Some((_, timestamp)) if timestamp == state.timestamp => {}
+            Some((last, timestamp)) if state.timestamp > timestamp => {
+                velocity = (angle - last) / (state.timestamp - timestamp).as_secs_f32();
+                previous = Some((angle, state.timestamp));
+            }
+            _ => previous = Some((angle, state.timestamp)),
+        }
+
+        soft_lock.torque(angle, velocity)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{
+        axis::Axis,
+        effects::{EffectKind, Slot},
+        simulator::Simulator,
+        soft_lock::SoftLock,
+        Options,
+    };
+
+    #[test]
+    fn test_torque() {
+        let soft_lock = SoftLock::new(540);
+
+        assert_eq!(soft_lock.torque(0.0, 500.0), 0.0);
+        assert_eq!(soft_lock.torque(-270.0, 0.0), 0.0);
+        assert!((soft_lock.torque(275.0, 0.0) + 0.5).abs() < 1e-4);
+        assert!((soft_lock.torque(-275.0, 0.0) - 0.5).abs() < 1e-4);
+
+        // turning further out pushes back harder, turning back in eases off
+        assert!((soft_lock.torque(275.0, 100.0) + 0.7).abs() < 1e-4);
+        assert!((soft_lock.torque(275.0, -100.0) + 0.3).abs() < 1e-4);
+        assert_eq!(soft_lock.torque(400.0, 0.0), -1.0);
+    }
+
+    #[test]
+    fn test_set_range() {
+        let sim = Simulator::new(Options::default());
+        sim.g29().clear_written_commands();
+
+        sim.g29().set_range(540);
+        sim.g29().set_range(10);
+
+        assert_eq!(sim.g29().range(), 40);
+        assert_eq!(
+            sim.g29().written_commands(),
+            vec![
+                [0xf8, 0x81, 0x1c, 0x02, 0x00, 0x00, 0x00],
+                [0xf8, 0x81, 0x28, 0x00, 0x00, 0x00, 0x00],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_enable_soft_lock() {
+        let mut sim = Simulator::new(Options::default());
+        sim.g29().enable_soft_lock(SoftLock::new(540)).unwrap();
+        assert_eq!(sim.g29().soft_lock(), Some(SoftLock::new(540)));
+        assert_eq!(
+            sim.g29().active_effects(),
+            vec![(Slot::One, EffectKind::Constant)]
+        );
+
+        // inside the soft lock there is no force
+        sim.set_axis(Axis::Steering, 0.5);
+        sim.wait(Duration::from_millis(20));
+        assert_eq!(
+            sim.g29().written_commands().last(),
+            Some(&[0x13, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00])
+        );
+
+        // full lock of the 900 degree range is far past the soft lock
+        sim.set_axis(Axis::Steering, 1.0);
+        sim.wait(Duration::from_millis(20));
+        assert_eq!(
+            sim.g29().written_commands().last(),
+            Some(&[0x11, 0x08, 0x01, 0x80, 0x00, 0x00, 0x00])
+        );
+
+        sim.g29().disable_soft_lock();
+        assert_eq!(sim.g29().soft_lock(), None);
+        assert!(sim.g29().active_effects().is_empty());
+    }
+}
@@ -1,4 +1,10 @@
-use crate::{DpadPosition, GearSelector};
+use std::time::Duration;
+
+use crate::{
+    axis::{Axis, AxisConfig},
+    encoder::FrameBuilder,
+    Button, DpadPosition, Frame, GearSelector,
+};
 
 pub fn throttle(data: &[u8; 12]) -> u8 {
     data[6]
@@ -19,6 +25,17 @@ pub fn steering_fine(data: &[u8; 12]) -> u8 {
     data[4]
 }
 
+/// Convert a 16-bit steering position to a value between -1.0 (fully left) and 1.0 (fully right).
+pub fn steering_normalized(raw: u16) -> f32 {
+    (raw as f32 - 32767.5) / 32767.5
+}
+
+/// Convert a 16-bit steering position to an angle in degrees, where 0 is center
+/// and ±`range / 2` is full lock.
+pub fn steering_angle_degrees(raw: u16, range: u16) -> f32 {
+    steering_normalized(raw) * range as f32 / 2.0
+}
+
 /// Get the Dpad position.
 /// # Example
 /// ```rust
@@ -27,17 +44,7 @@ pub fn steering_fine(data: &[u8; 12]) -> u8 {
 /// }
 /// ````
 pub fn dpad(data: &[u8; 12]) -> DpadPosition {
-    match data[0] & 15 {
-        0 => DpadPosition::Up,
-        1 => DpadPosition::TopRight,
-        2 => DpadPosition::Right,
-        3 => DpadPosition::BottomRight,
-        4 => DpadPosition::Down,
-        5 => DpadPosition::BottomLeft,
-        6 => DpadPosition::Left,
-        7 => DpadPosition::TopLeft,
-        _ => DpadPosition::None,
-    }
+    dpad_from_value((data[0] & 15) as u32)
 }
 
 /// Returns `true` if the x button is pressed.
@@ -111,17 +118,7 @@ pub fn l3_button(data: &[u8; 12]) -> bool {
 /// ```
 ///
 pub fn gear_selector(data: &[u8; 12]) -> GearSelector {
-    match data[2] & 127 {
-        0 => GearSelector::Neutral,
-        1 => GearSelector::First,
-        2 => GearSelector::Second,
-        4 => GearSelector::Third,
-        8 => GearSelector::Fourth,
-        16 => GearSelector::Fifth,
-        32 => GearSelector::Sixth,
-        64 => GearSelector::Reverse,
-        _ => GearSelector::Neutral,
-    }
+    gear_from_value((data[2] & 127) as u32)
 }
 
 /// Returns true if the plus button is pressed.
@@ -174,6 +171,359 @@ pub fn shifter_pressed(data: &[u8; 12]) -> bool {
     data[11] == 1
 }
 
+// Conversions between the Dpad and gear selector and their values in a frame
+
+pub(crate) fn dpad_value(position: DpadPosition) -> u32 {
+    match position {
+        DpadPosition::Up => 0,
+        DpadPosition::TopRight => 1,
+        DpadPosition::Right => 2,
+        DpadPosition::BottomRight => 3,
+        DpadPosition::Down => 4,
+        DpadPosition::BottomLeft => 5,
+        DpadPosition::Left => 6,
+        DpadPosition::TopLeft => 7,
+        DpadPosition::None => 8,
+    }
+}
+
+pub(crate) fn dpad_from_value(value: u32) -> DpadPosition {
+    match value {
+        0 => DpadPosition::Up,
+        1 => DpadPosition::TopRight,
+        2 => DpadPosition::Right,
+        3 => DpadPosition::BottomRight,
+        4 => DpadPosition::Down,
+        5 => DpadPosition::BottomLeft,
+        6 => DpadPosition::Left,
+        7 => DpadPosition::TopLeft,
+        _ => DpadPosition::None,
+    }
+}
+
+// One bit per gear, in this order
+const GEARS: [GearSelector; 7] = [
+    GearSelector::First,
+    GearSelector::Second,
+    GearSelector::Third,
+    GearSelector::Fourth,
+    GearSelector::Fifth,
+    GearSelector::Sixth,
+    GearSelector::Reverse,
+];
+
+/// The G29 has no seventh or eighth gear, so they are encoded as neutral.
+pub(crate) fn gear_value(gear: GearSelector) -> u32 {
+    GEARS
+        .iter()
+        .position(|g| *g == gear)
+        .map_or(0, |index| 1 << index)
+}
+
+/// More than one gear at once is neutral.
+pub(crate) fn gear_from_value(value: u32) -> GearSelector {
+    if value.count_ones() == 1 {
+        GEARS
+            .get(value.trailing_zeros() as usize)
+            .copied()
+            .unwrap_or(GearSelector::Neutral)
+    } else {
+        GearSelector::Neutral
+    }
+}
+
+// Lists the byte and flag fields of `G29State` with their `Field` variants, so `Field` names every
+// field once. `steering_raw`, the Dpad and the gear selector are encoded and added by hand
+macro_rules! fields {
+    ($callback:ident) => {
+        $callback! {
+            bytes: Steering steering,
+            SteeringFine steering_fine,
+            Throttle throttle,
+            Brake brake,
+            Clutch clutch,
+            ShifterX shifter_x,
+            ShifterY shifter_y;
+            flags: XButton x_button,
+            SquareButton square_button,
+            CircleButton circle_button,
+            TriangleButton triangle_button,
+            RightShifter right_shifter,
+            LeftShifter left_shifter,
+            R2Button r2_button,
+            L2Button l2_button,
+            ShareButton share_button,
+            OptionsButton options_button,
+            R3Button r3_button,
+            L3Button l3_button,
+            PlusButton plus_button,
+            MinusButton minus_button,
+            SpinnerRight spinner_right,
+            SpinnerLeft spinner_left,
+            SpinnerButton spinner_button,
+            PlaystationButton playstation_button,
+            ShifterPressed shifter_pressed
+        }
+    };
+}
+
+macro_rules! field {
+    (bytes: $($byte_variant:ident $byte:ident),*; flags: $($flag_variant:ident $flag:ident),*) => {
+        ///
+        /// Field
+        ///
+        /// A field of `G29State` a control is decoded into, see `Control::field`
+        ///
+        #[derive(Debug, PartialEq, Copy, Clone, Eq, Hash)]
+        pub enum Field {
+            $($byte_variant,)*
+            $($flag_variant,)*
+            SteeringRaw,
+            Dpad,
+            GearSelector,
+        }
+
+        impl Field {
+            /// Every field.
+            pub const ALL: &'static [Field] = &[
+                $(Field::$byte_variant,)*
+                $(Field::$flag_variant,)*
+                Field::SteeringRaw,
+                Field::Dpad,
+                Field::GearSelector,
+            ];
+
+            /// The name of the field, the same as the name of its control.
+            pub const fn name(&self) -> &'static str {
+                match self {
+                    $(Field::$byte_variant => stringify!($byte),)*
+                    $(Field::$flag_variant => stringify!($flag),)*
+                    Field::SteeringRaw => "steering_raw",
+                    Field::Dpad => "dpad",
+                    Field::GearSelector => "gear_selector",
+                }
+            }
+
+            /// Get the value of the field in a state, encoded as in a G29 frame.
+            pub fn get(&self, state: &G29State) -> u32 {
+                match self {
+                    $(Field::$byte_variant => state.$byte as u32,)*
+                    $(Field::$flag_variant => state.$flag as u32,)*
+                    Field::SteeringRaw => state.steering_raw() as u32,
+                    Field::Dpad => dpad_value(state.dpad),
+                    Field::GearSelector => gear_value(state.gear_selector),
+                }
+            }
+
+            /// Set the field in a state from a value encoded as in a G29 frame.
+            pub fn set(&self, state: &mut G29State, value: u32) {
+                match self {
+                    $(Field::$byte_variant => state.$byte = value as u8,)*
+                    $(Field::$flag_variant => state.$flag = value == 1,)*
+                    Field::SteeringRaw => {
+                        [state.steering_fine, state.steering] = (value as u16).to_le_bytes()
+                    }
+                    Field::Dpad => state.dpad = dpad_from_value(value),
+                    Field::GearSelector => state.gear_selector = gear_from_value(value),
+                }
+            }
+        }
+    };
+}
+
+fields!(field);
+
+impl Field {
+    /// Find a field by name. Usable in constants, so layout tables find their fields once.
+    pub const fn from_name(name: &str) -> Option<Field> {
+        let mut index = 0;
+
+        while index < Field::ALL.len() {
+            if same(Field::ALL[index].name(), name) {
+                return Some(Field::ALL[index]);
+            }
+            index += 1;
+        }
+
+        None
+    }
+}
+
+// String equality for constants
+const fn same(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+
+    let mut index = 0;
+    while index < a.len() {
+        if a[index] != b[index] {
+            return false;
+        }
+        index += 1;
+    }
+
+    true
+}
+
+///
+/// G29State
+///
+/// A snapshot of every control on the G29, decoded from a single frame.
+/// - sequence: `u64` - Counts the changes to the frame since connecting, from 1 for the first
+///   frame. Frames the wheel repeats unchanged are not counted, 0 means none was read yet
+/// - timestamp: `Duration` - When the frame was received, relative to the connection
+///
+/// Pedals report 255 when released and 0 when fully pressed.
+///
+#[derive(Debug, PartialEq, Copy, Clone, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct G29State {
+    pub sequence: u64,
+    pub timestamp: Duration,
+    pub steering: u8,
+    pub steering_fine: u8,
+    pub throttle: u8,
+    pub brake: u8,
+    pub clutch: u8,
+    pub dpad: DpadPosition,
+    pub x_button: bool,
+    pub square_button: bool,
+    pub circle_button: bool,
+    pub triangle_button: bool,
+    pub right_shifter: bool,
+    pub left_shifter: bool,
+    pub r2_button: bool,
+    pub l2_button: bool,
+    pub share_button: bool,
+    pub options_button: bool,
+    pub r3_button: bool,
+    pub l3_button: bool,
+    pub gear_selector: GearSelector,
+    pub plus_button: bool,
+    pub minus_button: bool,
+    pub spinner_right: bool,
+    pub spinner_left: bool,
+    pub spinner_button: bool,
+    pub playstation_button: bool,
+    pub shifter_x: u8,
+    pub shifter_y: u8,
+    pub shifter_pressed: bool,
+}
+
+impl G29State {
+    /// Decode every control from a single frame.
+    pub fn from_frame(data: &Frame, sequence: u64, timestamp: Duration) -> G29State {
+        G29State {
+            sequence,
+            timestamp,
+            steering: steering(data),
+            steering_fine: steering_fine(data),
+            throttle: throttle(data),
+            brake: brake(data),
+            clutch: clutch(data),
+            dpad: dpad(data),
+            x_button: x_button(data),
+            square_button: square_button(data),
+            circle_button: circle_button(data),
+            triangle_button: triangle_button(data),
+            right_shifter: right_shifter(data),
+            left_shifter: left_shifter(data),
+            r2_button: r2_button(data),
+            l2_button: l2_button(data),
+            share_button: share_button(data),
+            options_button: options_button(data),
+            r3_button: r3_button(data),
+            l3_button: l3_button(data),
+            gear_selector: gear_selector(data),
+            plus_button: plus_button(data),
+            minus_button: minus_button(data),
+            spinner_right: spinner_right(data),
+            spinner_left: spinner_left(data),
+            spinner_button: spinner_button(data),
+            playstation_button: playstation_button(data),
+            shifter_x: shifter_x(data),
+            shifter_y: shifter_y(data),
+            shifter_pressed: shifter_pressed(data),
+        }
+    }
+
+    /// Encode the state back into the frame the wheel would send.
+    pub fn to_frame(&self) -> Frame {
+        FrameBuilder::from_state(self).build()
+    }
+
+    /// Returns `true` if a button is pressed.
+    pub fn button(&self, button: Button) -> bool {
+        self.value(button.name()) == Some(1)
+    }
+
+    /// Get the 16-bit steering position.
+    /// 65535 is fully right, 0 is fully left
+    pub fn steering_raw(&self) -> u16 {
+        u16::from_le_bytes([self.steering_fine, self.steering])
+    }
+
+    /// Get the steering position between -1.0 (fully left) and 1.0 (fully right).
+    pub fn steering_normalized(&self) -> f32 {
+        steering_normalized(self.steering_raw())
+    }
+
+    /// Get the steering angle in degrees for the given wheel range.
+    /// 0 is center, negative is left and ±`range / 2` is full lock.
+    pub fn steering_angle_degrees(&self, range: u16) -> f32 {
+        steering_angle_degrees(self.steering_raw(), range)
+    }
+
+    /// Get the normalized value of an axis with the default orientation.
+    /// Pedals are 0.0 (released) to 1.0 (fully pressed), every other axis is -1.0 to 1.0.
+    ///
+    /// Use [`AxisConfig::value`] to apply custom axis settings.
+    pub fn axis(&self, axis: Axis) -> f32 {
+        AxisConfig::default().value(axis, self)
+    }
+
+    /// Returns the names of the controls, as listed in `Layout::G29`, that differ between two
+    /// states. The sequence number and timestamp are not compared. Requires the `serde` feature.
+    ///
+    /// # Example
+    /// ```rust
+    /// let before = g29.snapshot();
+    /// sleep(Duration::from_millis(100));
+    /// let after = g29.snapshot();
+    ///
+    /// for control in before.diff(&after) {
+    ///     println!("{} changed", control);
+    /// }
+    /// ```
+    #[cfg(feature = "serde")]
+    pub fn diff(&self, other: &G29State) -> Vec<&'static str> {
+        crate::layout::Layout::G29
+            .controls()
+            .iter()
+            .filter(|control| self.value(control.name) != other.value(control.name))
+            .map(|control| control.name)
+            .collect()
+    }
+
+    /// Get the value of a control by name, as listed in [`Layout`](crate::layout::Layout).
+    ///
+    /// Buttons are 0 or 1, the Dpad and gear selector are encoded as in a G29 frame and axes
+    /// are raw. Returns `None` for unknown names.
+    pub fn value(&self, name: &str) -> Option<u32> {
+        Field::from_name(name).map(|field| field.get(self))
+    }
+
+    /// Set the value of a control by name, the inverse of [`G29State::value`].
+    /// Returns `false` for unknown names.
+    pub fn set_value(&mut self, name: &str, value: u32) -> bool {
+        Field::from_name(name)
+            .map(|field| field.set(self, value))
+            .is_some()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     fn get_test_state() -> [u8; 12] {
@@ -232,6 +582,39 @@ mod tests {
         assert_eq!(crate::state::steering_fine(&state), 128);
     }
 
+    #[test]
+    fn test_steering_raw() {
+        let steering_raw = |state: &[u8; 12]| {
+            crate::G29State::from_frame(state, 0, std::time::Duration::ZERO).steering_raw()
+        };
+
+        let mut state = get_test_state();
+        assert_eq!(steering_raw(&state), 0);
+
+        state[4] = 0x34;
+        state[5] = 0x12;
+        assert_eq!(steering_raw(&state), 0x1234);
+
+        state[4] = 255;
+        state[5] = 255;
+        assert_eq!(steering_raw(&state), 65535);
+    }
+
+    #[test]
+    fn test_steering_normalized() {
+        assert_eq!(crate::state::steering_normalized(0), -1.0);
+        assert_eq!(crate::state::steering_normalized(65535), 1.0);
+        assert!(crate::state::steering_normalized(32768).abs() < 0.0001);
+    }
+
+    #[test]
+    fn test_steering_angle_degrees() {
+        assert_eq!(crate::state::steering_angle_degrees(0, 900), -450.0);
+        assert_eq!(crate::state::steering_angle_degrees(65535, 900), 450.0);
+        assert_eq!(crate::state::steering_angle_degrees(65535, 540), 270.0);
+        assert!(crate::state::steering_angle_degrees(32768, 900).abs() < 0.01);
+    }
+
     #[test]
     fn test_dpad() {
         let mut state = get_test_state();
@@ -557,4 +940,41 @@ mod tests {
         state[11] = 1;
         assert!(crate::state::shifter_pressed(&state));
     }
+
+    #[test]
+    fn test_g29_state_from_frame() {
+        let mut state = get_test_state();
+        state[0] = 16 | 2;
+        state[2] = 4;
+        state[3] = 16;
+        state[5] = 128;
+        state[6] = 255;
+
+        let snapshot = crate::G29State::from_frame(&state, 3, std::time::Duration::from_millis(10));
+
+        assert_eq!(snapshot.sequence, 3);
+        assert_eq!(snapshot.timestamp, std::time::Duration::from_millis(10));
+        assert_eq!(snapshot.dpad, crate::DpadPosition::Right);
+        assert!(snapshot.x_button);
+        assert!(!snapshot.square_button);
+        assert_eq!(snapshot.gear_selector, crate::GearSelector::Third);
+        assert!(snapshot.playstation_button);
+        assert_eq!(snapshot.steering, 128);
+        assert_eq!(snapshot.throttle, 255);
+        assert_eq!(snapshot.brake, 0);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_g29_state_diff() {
+        let mut state = get_test_state();
+        let before = crate::G29State::from_frame(&state, 0, std::time::Duration::ZERO);
+
+        state[1] = 4;
+        state[7] = 200;
+        let after = crate::G29State::from_frame(&state, 1, std::time::Duration::from_millis(1));
+
+        assert_eq!(before.diff(&after), vec!["r2_button", "brake"]);
+        assert!(before.diff(&before).is_empty());
+    }
 }